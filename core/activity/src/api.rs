@@ -22,8 +22,8 @@ mod common {
 
     use crate::common::{
         agreement_provider_service, authorize_activity_executor, authorize_activity_initiator,
-        get_activity_agreement, get_persisted_state, get_persisted_usage, set_persisted_state,
-        set_persisted_usage, PathActivity, QueryTimeout,
+        authorize_activity_scope, get_activity_agreement, get_persisted_state, get_persisted_usage,
+        set_persisted_state, set_persisted_usage, PathActivity, QueryTimeout,
     };
 
     pub fn extend_web_scope(scope: actix_web::Scope) -> actix_web::Scope {
@@ -39,6 +39,8 @@ mod common {
         query: web::Query<QueryTimeout>,
         id: Identity,
     ) -> impl Responder {
+        authorize_activity_scope(&db, &id, &path.activity_id).await?;
+
         // check if caller is the Provider
         if authorize_activity_executor(&db, id.identity, &path.activity_id)
             .await
@@ -81,6 +83,8 @@ mod common {
         query: web::Query<QueryTimeout>,
         id: Identity,
     ) -> impl Responder {
+        authorize_activity_scope(&db, &id, &path.activity_id).await?;
+
         // check if caller is the Provider
         if authorize_activity_executor(&db, id.identity, &path.activity_id)
             .await
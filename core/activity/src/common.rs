@@ -162,6 +162,28 @@ pub(crate) async fn authorize_agreement_executor(
     authorize_caller(caller.to_string().parse()?, executor_id)
 }
 
+/// Rejects app keys scoped to agreements other than `agreement_id`.
+pub(crate) fn authorize_agreement_scope(id: &Identity, agreement_id: &str) -> Result<(), Error> {
+    match id.is_agreement_allowed(agreement_id) {
+        true => Ok(()),
+        false => Err(Error::Forbidden(format!(
+            "agreement {} is out of application key scope",
+            agreement_id
+        ))),
+    }
+}
+
+pub(crate) async fn authorize_activity_scope(
+    db: &DbExecutor,
+    id: &Identity,
+    activity_id: &str,
+) -> Result<(), Error> {
+    if id.scope.is_none() {
+        return Ok(());
+    }
+    authorize_agreement_scope(id, &get_agreement_id(db, activity_id).await?)
+}
+
 #[inline(always)]
 pub(crate) fn authorize_caller(caller: NodeId, authorized: NodeId) -> Result<(), Error> {
     let msg = format!("caller: {} is not authorized: {}", caller, authorized);
@@ -173,3 +195,55 @@ pub(crate) fn authorize_caller(caller: NodeId, authorized: NodeId) -> Result<(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya_core_model::appkey::Scope;
+
+    fn identity(agreements: Option<Vec<&str>>) -> Identity {
+        Identity {
+            identity: "0xbabe000000000000000000000000000000000000"
+                .parse()
+                .unwrap(),
+            name: "app".to_string(),
+            role: "requestor".to_string(),
+            scope: agreements.map(|agreements| Scope {
+                agreements: agreements.into_iter().map(str::to_string).collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn activities_are_limited_to_agreements_in_scope() {
+        let dir = tempdir::TempDir::new("activity").unwrap();
+        let db = DbExecutor::from_data_dir(dir.path(), "activity").unwrap();
+        db.apply_migration(crate::db::migrations::run_with_output)
+            .unwrap();
+        db.as_dao::<ActivityDao>()
+            .create("act-1", "agr-1")
+            .await
+            .unwrap();
+
+        // unscoped keys are not limited, not even to known activities
+        authorize_activity_scope(&db, &identity(None), "act-1")
+            .await
+            .unwrap();
+        authorize_activity_scope(&db, &identity(None), "unknown")
+            .await
+            .unwrap();
+
+        authorize_activity_scope(&db, &identity(Some(vec!["agr-1"])), "act-1")
+            .await
+            .unwrap();
+        match authorize_activity_scope(&db, &identity(Some(vec!["agr-2"])), "act-1").await {
+            Err(Error::Forbidden(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match authorize_activity_scope(&db, &identity(Some(vec!["agr-1"])), "unknown").await {
+            Err(Error::Dao(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
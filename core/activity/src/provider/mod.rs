@@ -7,7 +7,8 @@ use ya_service_api_web::middleware::Identity;
 use ya_service_bus::timeout::IntoTimeoutFuture;
 
 use crate::common::{
-    authorize_activity_executor, set_persisted_state, PathActivity, QueryTimeoutMaxEvents,
+    authorize_activity_executor, authorize_activity_scope, set_persisted_state, PathActivity,
+    QueryTimeoutMaxEvents,
 };
 use crate::dao::EventDao;
 use crate::error::Error;
@@ -28,6 +29,7 @@ async fn set_activity_state_web(
     id: Identity,
 ) -> impl Responder {
     log::debug!("set_activity_state_web {:?}", state);
+    authorize_activity_scope(&db, &id, &path.activity_id).await?;
     authorize_activity_executor(&db, id.identity, &path.activity_id).await?;
 
     set_persisted_state(&db, &path.activity_id, state.into_inner())
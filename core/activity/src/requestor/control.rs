@@ -9,9 +9,9 @@ use ya_service_api_web::middleware::Identity;
 use ya_service_bus::{timeout::IntoTimeoutFuture, RpcEndpoint};
 
 use crate::common::{
-    agreement_provider_service, authorize_activity_initiator, authorize_agreement_initiator,
-    generate_id, get_activity_agreement, get_agreement, set_persisted_state, PathActivity,
-    QueryTimeout, QueryTimeoutCommandIndex,
+    agreement_provider_service, authorize_activity_initiator, authorize_activity_scope,
+    authorize_agreement_initiator, authorize_agreement_scope, generate_id, get_activity_agreement,
+    get_agreement, set_persisted_state, PathActivity, QueryTimeout, QueryTimeoutCommandIndex,
 };
 use crate::dao::ActivityDao;
 use crate::error::Error;
@@ -33,6 +33,7 @@ async fn create_activity(
     id: Identity,
 ) -> impl Responder {
     let agreement_id = body.into_inner();
+    authorize_agreement_scope(&id, &agreement_id)?;
     authorize_agreement_initiator(id.identity, &agreement_id).await?;
 
     let agreement = get_agreement(&agreement_id).await?;
@@ -68,6 +69,7 @@ async fn destroy_activity(
     query: web::Query<QueryTimeout>,
     id: Identity,
 ) -> impl Responder {
+    authorize_activity_scope(&db, &id, &path.activity_id).await?;
     authorize_activity_initiator(&db, id.identity, &path.activity_id).await?;
 
     let agreement = get_activity_agreement(&db, &path.activity_id).await?;
@@ -103,6 +105,7 @@ async fn exec(
     body: web::Json<ExeScriptRequest>,
     id: Identity,
 ) -> impl Responder {
+    authorize_activity_scope(&db, &id, &path.activity_id).await?;
     authorize_activity_initiator(&db, id.identity, &path.activity_id).await?;
 
     let commands: Vec<ExeScriptCommand> =
@@ -134,6 +137,7 @@ async fn get_batch_results(
     query: web::Query<QueryTimeoutCommandIndex>,
     id: Identity,
 ) -> impl Responder {
+    authorize_activity_scope(&db, &id, &path.activity_id).await?;
     authorize_activity_initiator(&db, id.identity, &path.activity_id).await?;
 
     let agreement = get_activity_agreement(&db, &path.activity_id).await?;
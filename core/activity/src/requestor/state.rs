@@ -6,8 +6,8 @@ use ya_service_api_web::middleware::Identity;
 use ya_service_bus::{timeout::IntoTimeoutFuture, RpcEndpoint};
 
 use crate::common::{
-    agreement_provider_service, authorize_activity_initiator, get_activity_agreement, PathActivity,
-    QueryTimeout,
+    agreement_provider_service, authorize_activity_initiator, authorize_activity_scope,
+    get_activity_agreement, PathActivity, QueryTimeout,
 };
 use crate::error::Error;
 
@@ -23,6 +23,7 @@ async fn get_running_command(
     query: web::Query<QueryTimeout>,
     id: Identity,
 ) -> impl Responder {
+    authorize_activity_scope(&db, &id, &path.activity_id).await?;
     authorize_activity_initiator(&db, id.identity, &path.activity_id).await?;
 
     let agreement = get_activity_agreement(&db, &path.activity_id).await?;
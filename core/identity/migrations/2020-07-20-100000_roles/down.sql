@@ -0,0 +1 @@
+DELETE FROM "role" WHERE "name" IN ("requestor", "provider", "read-only");
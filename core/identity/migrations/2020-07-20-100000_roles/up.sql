@@ -0,0 +1,3 @@
+INSERT INTO "role"("name") VALUES ("requestor");
+INSERT INTO "role"("name") VALUES ("provider");
+INSERT INTO "role"("name") VALUES ("read-only");
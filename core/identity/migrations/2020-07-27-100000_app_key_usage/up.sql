@@ -0,0 +1,3 @@
+ALTER TABLE "app_key" ADD COLUMN "expires_at" DATETIME;
+ALTER TABLE "app_key" ADD COLUMN "last_used" DATETIME;
+ALTER TABLE "app_key" ADD COLUMN "request_count" INTEGER NOT NULL DEFAULT 0;
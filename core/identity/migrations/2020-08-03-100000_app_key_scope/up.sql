@@ -0,0 +1 @@
+ALTER TABLE "app_key" ADD COLUMN "scope" TEXT;
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono::Utc;
 use structopt::*;
 
 use ya_core_model::appkey as model;
@@ -13,10 +14,28 @@ use ya_service_bus::{typed as bus, RpcEndpoint};
 pub enum AppKeyCommand {
     Create {
         name: String,
-        #[structopt(default_value = model::DEFAULT_ROLE, long)]
+        #[structopt(default_value = model::DEFAULT_ROLE, long, possible_values = &model::ROLES)]
         role: String,
         #[structopt(long)]
         id: Option<String>,
+        /// Time after which the key expires, e.g. `30days`
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        valid_for: Option<std::time::Duration>,
+        /// Restricts the key to the market subscription, may be repeated
+        #[structopt(long = "subscription", number_of_values = 1)]
+        subscriptions: Vec<String>,
+        /// Restricts the key to the agreement and its activities, may be repeated
+        #[structopt(long = "agreement", number_of_values = 1)]
+        agreements: Vec<String>,
+        /// Restricts the key to the payment allocation, may be repeated
+        #[structopt(long = "allocation", number_of_values = 1)]
+        allocations: Vec<String>,
+    },
+    /// Issues a new key for the entry, keeping its name, role and expiry
+    Rotate {
+        name: String,
+        #[structopt(long)]
+        id: Option<String>,
     },
     Drop {
         name: String,
@@ -45,7 +64,15 @@ impl AppKeyCommand {
 
     pub async fn run_command(&self, _ctx: &CliCtx) -> Result<CommandOutput> {
         match &self {
-            AppKeyCommand::Create { name, role, id } => {
+            AppKeyCommand::Create {
+                name,
+                role,
+                id,
+                valid_for,
+                subscriptions,
+                agreements,
+                allocations,
+            } => {
                 let identity = match id {
                     Some(id) => {
                         if id.starts_with("0x") {
@@ -58,10 +85,29 @@ impl AppKeyCommand {
                     }
                     None => Self::get_identity(idm::Get::ByDefault).await?.node_id,
                 };
+                let scope = if subscriptions.is_empty()
+                    && agreements.is_empty()
+                    && allocations.is_empty()
+                {
+                    None
+                } else {
+                    Some(model::Scope {
+                        subscriptions: subscriptions.clone(),
+                        agreements: agreements.clone(),
+                        allocations: allocations.clone(),
+                    })
+                };
                 let create = model::Create {
                     name: name.clone(),
                     role: role.clone(),
                     identity,
+                    expires_at: match valid_for {
+                        Some(valid_for) => {
+                            Some(Utc::now().naive_utc() + chrono::Duration::from_std(*valid_for)?)
+                        }
+                        None => None,
+                    },
+                    scope,
                 };
                 let key = bus::service(model::BUS_ID)
                     .send(create)
@@ -70,6 +116,18 @@ impl AppKeyCommand {
                     .unwrap();
                 Ok(CommandOutput::Object(serde_json::to_value(key)?))
             }
+            AppKeyCommand::Rotate { name, id } => {
+                let rotate = model::Rotate {
+                    name: name.clone(),
+                    identity: id.clone(),
+                };
+                let key = bus::service(model::BUS_ID)
+                    .send(rotate)
+                    .await
+                    .map_err(anyhow::Error::msg)?
+                    .map_err(anyhow::Error::msg)?;
+                Ok(CommandOutput::Object(serde_json::to_value(key)?))
+            }
             AppKeyCommand::Drop { name, id } => {
                 let remove = model::Remove {
                     name: name.clone(),
@@ -101,6 +159,9 @@ impl AppKeyCommand {
                         "id".into(),
                         "role".into(),
                         "created".into(),
+                        "expires".into(),
+                        "last used".into(),
+                        "requests".into(),
                     ],
                     values: result
                         .0
@@ -108,7 +169,8 @@ impl AppKeyCommand {
                         .map(|app_key| {
                             serde_json::json! {[
                                 app_key.name, app_key.key, app_key.identity,
-                                app_key.role, app_key.created_date
+                                app_key.role, app_key.created_date,
+                                app_key.expires_at, app_key.last_used, app_key.request_count
                             ]}
                         })
                         .collect(),
@@ -103,6 +103,12 @@ pub enum IdentityCommand {
         node_or_alias: Option<NodeOrAlias>,
     },
 
+    /// Change password of identity, or set one for identity created without it
+    ChangePassword {
+        /// Identity to change password of
+        node_or_alias: Option<NodeOrAlias>,
+    },
+
     /// Create identity
     Create {
         /// Identity alias to create
@@ -132,6 +138,79 @@ pub enum IdentityCommand {
         /// Identity alias to drop
         node_or_alias: NodeOrAlias,
     },
+
+    /// Export identity as an encrypted keystore
+    Export {
+        /// Identity to export
+        node_or_alias: Option<NodeOrAlias>,
+
+        /// Export all identities into a single backup
+        #[structopt(long, conflicts_with = "node-or-alias")]
+        all: bool,
+
+        /// File to write to, instead of printing
+        #[structopt(long = "file-path")]
+        file_path: Option<PathBuf>,
+
+        /// Encrypt exported keys with a new password instead of the current one, which may be empty
+        #[structopt(long = "new-password")]
+        new_password: bool,
+    },
+
+    /// Import identities from a keystore or a backup made with `export --all`,
+    /// which also restores its default identity
+    Import {
+        /// Keystore or backup file
+        file_path: PathBuf,
+    },
+}
+
+fn read_new_password() -> Result<String> {
+    let password = rpassword::read_password_from_tty(Some("Password: "))?;
+    let password2 = rpassword::read_password_from_tty(Some("Confirm password: "))?;
+    if password != password2 {
+        anyhow::bail!("Password and confirmation do not match.")
+    }
+    Ok(password)
+}
+
+/// Identities in `skipped` could not be exported. Printed backups list them on stderr,
+/// so that the output stays importable.
+fn export_output(
+    exported: Vec<identity::ExportedIdentity>,
+    skipped: Vec<identity::SkippedIdentity>,
+    file_path: &Option<PathBuf>,
+    single: bool,
+) -> Result<CommandOutput> {
+    let file_path = match file_path {
+        Some(file_path) => file_path,
+        None if single => {
+            let key_file: serde_json::Value = serde_json::from_str(&exported[0].key_file)?;
+            return CommandOutput::object(key_file);
+        }
+        None => {
+            for identity in skipped {
+                eprintln!("skipped {}: {}", identity.node_id, identity.reason);
+            }
+            return CommandOutput::object(exported);
+        }
+    };
+    if single {
+        std::fs::write(file_path, &exported[0].key_file)?;
+    } else {
+        std::fs::write(file_path, serde_json::to_string_pretty(&exported)?)?;
+    }
+    Ok(ResponseTable {
+        columns: vec!["alias".into(), "address".into(), "status".into()],
+        values: exported
+            .into_iter()
+            .map(|identity| serde_json::json! {[identity.alias, identity.node_id, "exported"]})
+            .chain(skipped.into_iter().map(|identity| {
+                serde_json::json! {[identity.alias, identity.node_id, identity.reason]}
+            }))
+            .collect(),
+    }
+    .into())
 }
 
 impl IdentityCommand {
@@ -202,14 +281,7 @@ impl IdentityCommand {
                     let password = if *no_password {
                         Protected::from("")
                     } else {
-                        let password: Protected =
-                            rpassword::read_password_from_tty(Some("Password: "))?.into();
-                        let password2: Protected =
-                            rpassword::read_password_from_tty(Some("Confirm password: "))?.into();
-                        if password.as_ref() != password2.as_ref() {
-                            anyhow::bail!("Password and confirmation do not match.")
-                        }
-                        password
+                        Protected::from(read_new_password()?)
                     };
                     crate::id_key::generate_new_keyfile(password)?
                 };
@@ -242,6 +314,21 @@ impl IdentityCommand {
                         .map_err(|e| anyhow::Error::msg(e))?,
                 )
             }
+            IdentityCommand::ChangePassword { node_or_alias } => {
+                let node_id = node_or_alias.clone().unwrap_or_default().resolve().await?;
+                let old_password = rpassword::read_password_from_tty(Some("Current password: "))?;
+                let new_password = read_new_password()?;
+                CommandOutput::object(
+                    bus::service(identity::BUS_ID)
+                        .send(identity::ChangePassword::with_id(
+                            node_id,
+                            old_password,
+                            new_password,
+                        ))
+                        .await
+                        .map_err(|e| anyhow::Error::msg(e))?,
+                )
+            }
             IdentityCommand::Drop { node_or_alias } => {
                 let command: identity::Get = node_or_alias.clone().into();
                 let id = bus::service(identity::BUS_ID)
@@ -264,6 +351,80 @@ impl IdentityCommand {
                         .map_err(|e| anyhow::Error::msg(e))?,
                 )
             }
+            IdentityCommand::Export {
+                node_or_alias,
+                all,
+                file_path,
+                new_password,
+            } => {
+                let new_password = if *new_password {
+                    Some(read_new_password()?)
+                } else {
+                    None
+                };
+                if *all {
+                    let identities = bus::service(identity::BUS_ID)
+                        .send(identity::ExportAll { new_password })
+                        .await
+                        .map_err(|e| anyhow::Error::msg(e))??;
+                    export_output(identities.exported, identities.skipped, file_path, false)
+                } else {
+                    let node_id = node_or_alias.clone().unwrap_or_default().resolve().await?;
+                    let exported = bus::service(identity::BUS_ID)
+                        .send(identity::Export::with_id(node_id).with_new_password(new_password))
+                        .await
+                        .map_err(|e| anyhow::Error::msg(e))??;
+                    export_output(vec![exported], Vec::new(), file_path, true)
+                }
+            }
+            IdentityCommand::Import { file_path } => {
+                let content = std::fs::read_to_string(file_path)?;
+                let identities = match serde_json::from_str::<serde_json::Value>(&content)? {
+                    serde_json::Value::Array(_) => serde_json::from_str::<
+                        Vec<identity::ExportedIdentity>,
+                    >(&content)?
+                    .into_iter()
+                    .map(|identity| (identity.alias, identity.key_file, identity.is_default))
+                    .collect(),
+                    _ => vec![(None, content, false)],
+                };
+
+                let mut values = Vec::new();
+                for (alias, key_file, is_default) in identities {
+                    let result = bus::service(identity::BUS_ID)
+                        .send(identity::CreateGenerated {
+                            alias: alias.clone(),
+                            from_keystore: Some(key_file),
+                        })
+                        .await
+                        .map_err(|e| anyhow::Error::msg(e))?;
+                    let id = match result {
+                        Ok(id) => id,
+                        Err(e) => {
+                            values.push(serde_json::json! {[alias, null, e.to_string()]});
+                            continue;
+                        }
+                    };
+                    if !is_default {
+                        values.push(serde_json::json! {[id.alias, id.node_id, "imported"]});
+                        continue;
+                    }
+                    // the backup was made with this identity as the default one
+                    let result = bus::service(identity::BUS_ID)
+                        .send(identity::Update::with_id(id.node_id).with_default(true))
+                        .await
+                        .map_err(|e| anyhow::Error::msg(e))?;
+                    values.push(match result {
+                        Ok(id) => serde_json::json! {[id.alias, id.node_id, "imported as default"]},
+                        Err(e) => serde_json::json! {[id.alias, id.node_id, format!("imported, not set as default: {}", e)]},
+                    });
+                }
+                Ok(ResponseTable {
+                    columns: vec!["alias".into(), "address".into(), "status".into()],
+                    values,
+                }
+                .into())
+            }
         }
     }
 }
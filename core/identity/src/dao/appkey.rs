@@ -1,6 +1,6 @@
 pub use crate::dao::Error as DaoError;
 pub use crate::db::models::{AppKey, Role};
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 use diesel::{Connection, ExpressionMethods, RunQueryDsl};
@@ -48,6 +48,8 @@ impl<'c> AppKeyDao<'c> {
         name: String,
         role: String,
         identity: NodeId,
+        expires_at: Option<NaiveDateTime>,
+        scope: Option<String>,
     ) -> Result<()> {
         use crate::db::schema::app_key as app_key_dsl;
         use crate::db::schema::role as role_dsl;
@@ -64,6 +66,8 @@ impl<'c> AppKeyDao<'c> {
                     app_key_dsl::key.eq(key),
                     app_key_dsl::identity_id.eq(identity),
                     app_key_dsl::created_date.eq(Utc::now().naive_utc()),
+                    app_key_dsl::expires_at.eq(expires_at),
+                    app_key_dsl::scope.eq(scope),
                 ))
                 .execute(conn)?;
 
@@ -87,6 +91,21 @@ impl<'c> AppKeyDao<'c> {
         .await
     }
 
+    pub async fn get_by_name(&self, name: String) -> Result<(AppKey, Role)> {
+        use crate::db::schema::app_key as app_key_dsl;
+        use crate::db::schema::role as role_dsl;
+
+        readonly_transaction(self.pool, move |conn| {
+            let result = app_key_dsl::table
+                .inner_join(role_dsl::table)
+                .filter(app_key_dsl::name.eq(name))
+                .first(conn)?;
+
+            Ok(result)
+        })
+        .await
+    }
+
     pub async fn get_for_id(&self, identity_id: String) -> Result<(AppKey, Role)> {
         use crate::db::schema::app_key as app_key_dsl;
         use crate::db::schema::role as role_dsl;
@@ -135,6 +154,54 @@ impl<'c> AppKeyDao<'c> {
         .await
     }
 
+    /// Replaces the key of the named entry with `new_key`.
+    pub async fn rotate(
+        &self,
+        name: String,
+        identity: Option<String>,
+        new_key: String,
+    ) -> Result<()> {
+        use crate::db::schema::app_key as app_key_dsl;
+
+        self.with_transaction(move |conn| {
+            let filter = app_key_dsl::table.filter(app_key_dsl::name.eq(name.as_str()));
+            let updated = if let Some(id) = identity {
+                diesel::update(filter.filter(app_key_dsl::identity_id.eq(id.as_str())))
+                    .set(app_key_dsl::key.eq(new_key))
+                    .execute(conn)
+            } else {
+                diesel::update(filter)
+                    .set(app_key_dsl::key.eq(new_key))
+                    .execute(conn)
+            }?;
+
+            match updated {
+                0 => Err(DaoError::NotFound),
+                _ => Ok(()),
+            }
+        })
+        .await
+    }
+
+    /// Adds `requests` to the count of each key and updates its last used time,
+    /// given as `(name, requests, last_used)`.
+    pub async fn record_usage(&self, usage: Vec<(String, u64, NaiveDateTime)>) -> Result<()> {
+        use crate::db::schema::app_key as app_key_dsl;
+
+        self.with_transaction(move |conn| {
+            for (name, requests, last_used) in usage {
+                diesel::update(app_key_dsl::table.filter(app_key_dsl::name.eq(name)))
+                    .set((
+                        app_key_dsl::last_used.eq(last_used),
+                        app_key_dsl::request_count.eq(app_key_dsl::request_count + requests as i64),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
     pub async fn remove(&self, name: String, identity: Option<String>) -> Result<()> {
         use crate::db::schema::app_key as app_key_dsl;
 
@@ -152,3 +219,90 @@ impl<'c> AppKeyDao<'c> {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya_persistence::executor::DbExecutor;
+
+    async fn test_db(dir: &tempdir::TempDir) -> DbExecutor {
+        let db = DbExecutor::from_data_dir(dir.path(), "identity").unwrap();
+        crate::dao::init(&db).await.unwrap();
+        db
+    }
+
+    fn node_id() -> NodeId {
+        "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn rotate_fails_for_unknown_name_or_identity() {
+        let dir = tempdir::TempDir::new("appkey").unwrap();
+        let db = test_db(&dir).await;
+        let dao = db.as_dao::<AppKeyDao>();
+        dao.create(
+            "key".into(),
+            "app".into(),
+            "manager".into(),
+            node_id(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let other_id = "0xdead000000000000000000000000000000000000".to_string();
+        match dao.rotate("other".into(), None, "key-2".into()).await {
+            Err(DaoError::NotFound) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match dao
+            .rotate("app".into(), Some(other_id), "key-2".into())
+            .await
+        {
+            Err(DaoError::NotFound) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(dao.get("key".into()).await.unwrap().0.name, "app");
+
+        dao.rotate("app".into(), Some(node_id().to_string()), "key-2".into())
+            .await
+            .unwrap();
+        assert!(dao.get("key".into()).await.is_err());
+        assert_eq!(dao.get("key-2".into()).await.unwrap().0.name, "app");
+    }
+
+    #[actix_rt::test]
+    async fn usage_is_added_to_request_count_across_rotation() {
+        let dir = tempdir::TempDir::new("appkey").unwrap();
+        let db = test_db(&dir).await;
+        let dao = db.as_dao::<AppKeyDao>();
+        dao.create(
+            "key".into(),
+            "app".into(),
+            "manager".into(),
+            node_id(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let now = Utc::now().naive_utc();
+        dao.record_usage(vec![("app".into(), 3, now)])
+            .await
+            .unwrap();
+        dao.rotate("app".into(), None, "key-2".into())
+            .await
+            .unwrap();
+        dao.record_usage(vec![("app".into(), 2, now), ("unknown".into(), 1, now)])
+            .await
+            .unwrap();
+
+        let (app_key, _) = dao.get("key-2".into()).await.unwrap();
+        assert_eq!(app_key.request_count, 5);
+        assert_eq!(app_key.last_used, Some(now));
+    }
+}
@@ -4,6 +4,8 @@ use diesel::prelude::*;
 
 use tokio::task;
 
+use ya_client_model::NodeId;
+
 use ya_persistence::executor::{AsDao, ConnType, PoolType};
 
 type Result<T> = std::result::Result<T, super::Error>;
@@ -72,6 +74,29 @@ impl<'c> IdentityDao<'c> {
         .await
     }
 
+    /// Replaces the key file of `node_id`, e.g. re-encrypted with a new password.
+    pub async fn update_key_file(&self, node_id: NodeId, new_key_file: String) -> Result<()> {
+        use crate::db::schema::identity::dsl::*;
+
+        self.with_transaction(move |conn| {
+            let rows = diesel::update(
+                identity
+                    .filter(identity_id.eq(&node_id))
+                    .filter(is_deleted.eq(false)),
+            )
+            .set(key_file_json.eq(&new_key_file))
+            .execute(conn)?;
+            if rows != 1 {
+                return Err(super::Error::internal(format!(
+                    "identity {} not found",
+                    node_id
+                )));
+            }
+            Ok(())
+        })
+        .await
+    }
+
     pub async fn init_default_key<KeyGenerator: Send + 'static + FnOnce() -> Result<Identity>>(
         &self,
         generator: KeyGenerator,
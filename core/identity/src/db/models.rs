@@ -29,6 +29,11 @@ pub struct AppKey {
     pub key: String,
     pub identity_id: NodeId,
     pub created_date: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used: Option<NaiveDateTime>,
+    pub request_count: i64,
+    /// JSON of `ya_core_model::appkey::Scope`.
+    pub scope: Option<String>,
 }
 
 #[derive(Queryable, Debug, Identifiable)]
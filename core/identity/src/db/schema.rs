@@ -6,6 +6,10 @@ table! {
         key -> Text,
         identity_id -> Text,
         created_date -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used -> Nullable<Timestamp>,
+        request_count -> BigInt,
+        scope -> Nullable<Text>,
     }
 }
 
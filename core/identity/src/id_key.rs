@@ -9,14 +9,28 @@ use rand::Rng;
 use std::convert::TryFrom;
 use ya_client_model::NodeId;
 
+/// Stored in place of the key file for keys held by an external signer.
+const EXTERNAL_KEY_FILE: &str = r#"{"signer":"external"}"#;
+
 pub struct IdentityKey {
     id: NodeId,
     alias: Option<String>,
-    key_file: KeyFile,
+    /// `None` for keys held by an external signer, see `crate::signer`.
+    key_file: Option<KeyFile>,
     secret: Option<SecretKey>,
 }
 
 impl IdentityKey {
+    /// Key held by an external signer, known here only by its id.
+    pub fn external(id: NodeId, alias: Option<String>) -> Self {
+        IdentityKey {
+            id,
+            alias,
+            key_file: None,
+            secret: None,
+        }
+    }
+
     #[inline]
     pub fn id(&self) -> NodeId {
         self.id
@@ -31,16 +45,63 @@ impl IdentityKey {
         std::mem::replace(&mut self.alias, new_alias)
     }
 
+    #[inline]
+    pub fn is_external(&self) -> bool {
+        self.key_file.is_none()
+    }
+
+    /// Key file to store in the identity table.
     pub fn to_key_file(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string_pretty(&self.key_file)
+        match &self.key_file {
+            Some(key_file) => serde_json::to_string_pretty(key_file),
+            None => Ok(EXTERNAL_KEY_FILE.to_string()),
+        }
+    }
+
+    /// Key file encrypted with `password` instead of the current one. Fails if the key is locked.
+    pub fn to_key_file_with_password(&self, password: &Protected) -> Result<String, Error> {
+        let secret = match &self.secret {
+            Some(secret) => secret,
+            None => return Err(Error::internal("identity is locked")),
+        };
+        let key_file = encrypt(secret, password)?;
+        serde_json::to_string_pretty(&key_file).map_err(Error::internal)
+    }
+
+    /// Key file re-encrypted with `new_password`, or `None` if `old_password` is wrong.
+    /// Works on locked keys too. The key itself is unchanged until `replace_key_file`.
+    pub fn reencrypt(
+        &self,
+        old_password: &Protected,
+        new_password: &Protected,
+    ) -> Result<Option<KeyFile>, Error> {
+        let key_file = match &self.key_file {
+            Some(key_file) => key_file,
+            None => return Err(Error::internal("key is held by an external signer")),
+        };
+        let secret = match key_file.to_secret_key(old_password) {
+            Ok(secret) => secret,
+            Err(ethsign::Error::InvalidPassword) => return Ok(None),
+            Err(e) => return Err(Error::internal(e)),
+        };
+        encrypt(&secret, new_password).map(Some)
     }
 
+    pub fn replace_key_file(&mut self, key_file: KeyFile) {
+        self.key_file = Some(key_file);
+    }
+
+    /// External keys are never locked here, the signer guards them on its own.
     pub fn is_locked(&self) -> bool {
-        self.secret.is_none()
+        self.key_file.is_some() && self.secret.is_none()
     }
 
     pub fn unlock(&mut self, password: Protected) -> Result<bool, Error> {
-        let secret = match self.key_file.to_secret_key(&password) {
+        let key_file = match &self.key_file {
+            Some(key_file) => key_file,
+            None => return Ok(true),
+        };
+        let secret = match key_file.to_secret_key(&password) {
             Ok(secret) => secret,
             Err(ethsign::Error::InvalidPassword) => return Ok(false),
             Err(e) => return Err(Error::internal(e)),
@@ -75,14 +136,17 @@ impl TryFrom<Identity> for IdentityKey {
     type Error = serde_json::Error;
 
     fn try_from(value: Identity) -> Result<Self, Self::Error> {
-        let key_file: KeyFile = serde_json::from_str(&value.key_file_json)?;
         let id = value.identity_id;
         let alias = value.alias;
+        if value.key_file_json == EXTERNAL_KEY_FILE {
+            return Ok(IdentityKey::external(id, alias));
+        }
+        let key_file: KeyFile = serde_json::from_str(&value.key_file_json)?;
         let secret = key_file.to_secret_key(&Protected::new("")).ok();
         Ok(IdentityKey {
             id,
             alias,
-            key_file,
+            key_file: Some(key_file),
             secret,
         })
     }
@@ -91,13 +155,24 @@ impl TryFrom<Identity> for IdentityKey {
 const KEY_ITERATIONS: u32 = 10240;
 const KEYSTORE_VERSION: u64 = 3;
 
+fn encrypt(secret: &SecretKey, password: &Protected) -> Result<KeyFile, Error> {
+    Ok(KeyFile {
+        id: format!("{}", uuid::Uuid::new_v4()),
+        version: KEYSTORE_VERSION,
+        crypto: secret
+            .to_crypto(password, KEY_ITERATIONS)
+            .map_err(Error::internal)?,
+        address: Some(Bytes(secret.public().address().to_vec())),
+    })
+}
+
 pub fn generate_new(alias: Option<String>, password: Protected) -> IdentityKey {
     let (key_file, secret) = generate_new_secret(password);
     let id = NodeId::from(secret.public().address().as_ref());
     IdentityKey {
         id,
         alias,
-        key_file,
+        key_file: Some(key_file),
         secret: Some(secret),
     }
 }
@@ -5,7 +5,9 @@ extern crate diesel;
 
 pub mod cli;
 pub mod service;
+pub mod signature;
 
 pub mod dao;
 mod db;
 mod id_key;
+mod signer;
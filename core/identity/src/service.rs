@@ -1,11 +1,13 @@
 /// Identity service
 use futures::lock::Mutex;
+use std::fmt::{Debug, Display};
 use std::sync::Arc;
 
 use crate::cli::Command;
 
 use ya_persistence::executor::DbExecutor;
 use ya_service_api_interfaces::{Provider, Service};
+use ya_service_bus::{typed as bus, RpcMessage};
 
 mod appkey;
 mod identity;
@@ -29,3 +31,17 @@ impl Identity {
         Ok(())
     }
 }
+
+/// Delivers an event to an endpoint registered with a `Subscribe` call.
+async fn forward_event<E>(endpoint: String, event: E)
+where
+    E: RpcMessage<Item = ()> + Unpin + Debug,
+    E::Error: Display,
+{
+    log::debug!("Sending event: {:?} to {}", event, endpoint);
+    match bus::service(&endpoint).call(event).await {
+        Err(e) => log::error!("Failed to send event: {}", e),
+        Ok(Err(e)) => log::error!("Failed to send event: {}", e),
+        Ok(Ok(_)) => log::debug!("Event sent to {}", endpoint),
+    }
+}
@@ -5,80 +5,80 @@ use ya_core_model::appkey as model;
 use ya_persistence::executor::DbExecutor;
 use ya_service_bus::typed as bus;
 
+use crate::dao::appkey::{AppKey, Role};
 use crate::dao::AppKeyDao;
-use actix_rt::Arbiter;
-use std::cell::{Ref, RefCell};
-use std::collections::HashMap;
-use std::rc::Rc;
-
-#[derive(Default)]
-struct Subscription {
-    subscriptions: HashMap<u64, String>,
-    last_id: u64,
-}
 
-impl Subscription {
-    fn subscribe(&mut self, endpoint: String) -> u64 {
-        let id = self.last_id;
-        self.last_id += 1;
-        let r = self.subscriptions.insert(id, endpoint);
-        assert!(r.is_none());
-        id
-    }
-}
-
-fn send_events(s: Ref<Subscription>, event: model::event::Event) -> impl Future<Output = ()> {
-    let destinations: Vec<String> = s.subscriptions.values().cloned().collect();
-
-    // TODO: Remove on no destination.
-    async move {
-        for endpoint in destinations {
-            match bus::service(&endpoint).call(event.clone()).await {
-                Err(e) => log::error!("fail to send event: {}", e),
-                Ok(Err(e)) => log::error!("fail to send event: {}", e),
-                Ok(Ok(_)) => log::debug!("send event: {:?} to {}", event, endpoint),
-            }
-        }
+fn to_model(app_key: AppKey, role: Role) -> model::AppKey {
+    let name = &app_key.name;
+    let scope = match &app_key.scope {
+        Some(scope) => Some(serde_json::from_str(scope).unwrap_or_else(|e| {
+            // restricted to nothing rather than unrestricted
+            log::error!("invalid scope of app key {}: {}", name, e);
+            model::Scope::default()
+        })),
+        None => None,
+    };
+    model::AppKey {
+        name: app_key.name,
+        key: app_key.key,
+        role: role.name,
+        identity: app_key.identity_id,
+        created_date: app_key.created_date,
+        expires_at: app_key.expires_at,
+        last_used: app_key.last_used,
+        request_count: app_key.request_count as u64,
+        scope,
     }
 }
 
 pub async fn activate(db: &DbExecutor) -> anyhow::Result<()> {
     let dbx = db.clone();
-    let (tx, rx) = futures::channel::mpsc::unbounded();
-
-    let subscription = Rc::new(RefCell::new(Subscription::default()));
-
-    {
-        let subscription = subscription.clone();
-        Arbiter::spawn(async move {
-            let _ = rx
-                .for_each(|event| send_events(subscription.borrow(), event))
-                .await;
-        })
-    }
-
+    let mut last_subscription_id = 0;
     let _ = bus::bind(&model::BUS_ID, move |s: model::Subscribe| {
-        let id = subscription.borrow_mut().subscribe(s.endpoint);
+        let id = last_subscription_id;
+        last_subscription_id += 1;
+        let _ = bus::subscribe(move |event: model::event::Event| {
+            super::forward_event(s.endpoint.clone(), event)
+        });
         future::ok(id)
     });
 
-    let create_tx = tx.clone();
     // Create a new application key entry
     let _ = bus::bind(&model::BUS_ID, move |create: model::Create| {
         let key = Uuid::new_v4().to_simple().to_string();
         let db = dbx.clone();
-        let mut create_tx = create_tx.clone();
         let identity = create.identity.clone();
         async move {
+            if !model::ROLES.contains(&create.role.as_str()) {
+                return Err(model::Error {
+                    code: 400,
+                    message: format!(
+                        "invalid role: {}, expected one of: {}",
+                        create.role,
+                        model::ROLES.join(", ")
+                    ),
+                });
+            }
+            let scope = match create.scope {
+                Some(scope) => Some(serde_json::to_string(&scope).map_err(model::Error::internal)?),
+                None => None,
+            };
             let result = db
                 .as_dao::<AppKeyDao>()
-                .create(key.clone(), create.name, create.role, create.identity)
+                .create(
+                    key.clone(),
+                    create.name,
+                    create.role,
+                    create.identity,
+                    create.expires_at,
+                    scope,
+                )
                 .await
                 .map_err(|e| model::Error::internal(e))
                 .map(|_| key)?;
-            let _ = create_tx
-                .send(model::event::Event::NewKey { identity })
-                .await;
+            if let Err(e) = bus::publish(model::event::Event::NewKey { identity }).await {
+                log::error!("fail to publish event: {}", e);
+            }
             Ok(result)
         }
     });
@@ -94,13 +94,21 @@ pub async fn activate(db: &DbExecutor) -> anyhow::Result<()> {
                 .await
                 .map_err(|e| model::Error::internal(e.to_string()))?;
 
-            Ok(model::AppKey {
-                name: appkey.name,
-                key: appkey.key,
-                role: role.name,
-                identity: appkey.identity_id,
-                created_date: appkey.created_date,
-            })
+            Ok(to_model(appkey, role))
+        }
+    });
+
+    let dbx = db.clone();
+    let _ = bus::bind(&model::BUS_ID, move |get: model::GetByName| {
+        let db = dbx.clone();
+        async move {
+            let (appkey, role) = db
+                .as_dao::<AppKeyDao>()
+                .get_by_name(get.name)
+                .await
+                .map_err(|e| model::Error::internal(e.to_string()))?;
+
+            Ok(to_model(appkey, role))
         }
     });
 
@@ -118,13 +126,7 @@ pub async fn activate(db: &DbExecutor) -> anyhow::Result<()> {
             let keys = result
                 .0
                 .into_iter()
-                .map(|(app_key, role)| model::AppKey {
-                    name: app_key.name,
-                    key: app_key.key,
-                    role: role.name,
-                    identity: app_key.identity_id,
-                    created_date: app_key.created_date,
-                })
+                .map(|(app_key, role)| to_model(app_key, role))
                 .collect();
 
             Ok((keys, result.1))
@@ -143,5 +145,35 @@ pub async fn activate(db: &DbExecutor) -> anyhow::Result<()> {
         }
     });
 
+    let dbx = db.clone();
+    let _ = bus::bind(&model::BUS_ID, move |rotate: model::Rotate| {
+        let key = Uuid::new_v4().to_simple().to_string();
+        let db = dbx.clone();
+        async move {
+            db.as_dao::<AppKeyDao>()
+                .rotate(rotate.name, rotate.identity, key.clone())
+                .await
+                .map_err(Into::into)?;
+            Ok(key)
+        }
+    });
+
+    let dbx = db.clone();
+    let _ = bus::bind(&model::BUS_ID, move |record: model::RecordUsage| {
+        let db = dbx.clone();
+        let usage = record
+            .usage
+            .into_iter()
+            .map(|usage| (usage.name, usage.requests, usage.last_used))
+            .collect();
+        async move {
+            db.as_dao::<AppKeyDao>()
+                .record_usage(usage)
+                .await
+                .map_err(Into::into)?;
+            Ok(())
+        }
+    });
+
     Ok(())
 }
@@ -3,6 +3,7 @@ use ethsign::{KeyFile, Protected};
 use futures::lock::Mutex;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::rc::Rc;
 use std::sync::Arc;
 
 use ya_client_model::NodeId;
@@ -12,30 +13,28 @@ use ya_service_bus::typed as bus;
 
 use crate::dao::identity::Identity;
 use crate::dao::{Error as DaoError, IdentityDao};
-use crate::id_key::{generate_new, IdentityKey};
-use actix_rt::Arbiter;
+use crate::id_key::IdentityKey;
+use crate::signer::{self, Signer};
 use futures::prelude::*;
-use std::cell::{Ref, RefCell};
-use std::rc::Rc;
-
-#[derive(Default)]
-struct Subscription {
-    subscriptions: Vec<String>,
-}
-
-impl Subscription {
-    fn subscribe(&mut self, endpoint: String) {
-        self.subscriptions.push(endpoint);
-    }
-}
 
 pub struct IdentityService {
     default_key: NodeId,
     ids: HashMap<NodeId, IdentityKey>,
     alias_to_id: HashMap<String, NodeId>,
-    sender: futures::channel::mpsc::UnboundedSender<model::event::Event>,
-    subscription: Rc<RefCell<Subscription>>,
     db: DbExecutor,
+    signer: Rc<dyn Signer>,
+}
+
+fn new_identity(key: &IdentityKey, is_default: bool) -> Result<Identity, serde_json::Error> {
+    Ok(Identity {
+        identity_id: key.id(),
+        key_file_json: key.to_key_file()?,
+        is_default,
+        is_deleted: false,
+        alias: key.alias().map(ToOwned::to_owned),
+        note: None,
+        created_date: Utc::now().naive_utc(),
+    })
 }
 
 fn to_info(default_key: &NodeId, key: &IdentityKey) -> model::IdentityInfo {
@@ -49,56 +48,37 @@ fn to_info(default_key: &NodeId, key: &IdentityKey) -> model::IdentityInfo {
     }
 }
 
-fn send_event(s: Ref<Subscription>, event: model::event::Event) -> impl Future<Output = ()> {
-    let subscriptions: Vec<String> = s.subscriptions.clone();
-    log::debug!("sending event: {:?} to {:?}", event, subscriptions);
-
-    async move {
-        for endpoint in subscriptions {
-            let msg = event.clone();
-            Arbiter::spawn(async move {
-                log::debug!("Sending event: {:?}", msg);
-                match bus::service(&endpoint).call(msg).await {
-                    Err(e) => log::error!("Failed to send event: {:?}", e),
-                    Ok(Err(e)) => log::error!("Failed to send event: {:?}", e),
-                    Ok(Ok(_)) => log::debug!("Event sent to {:?}", endpoint),
-                }
-            });
-        }
+async fn publish_event(event: model::event::Event) {
+    log::debug!("Publishing event: {:?}", event);
+    if let Err(e) = bus::publish(event).await {
+        log::error!("Failed to publish event: {}", e);
     }
 }
 
 impl IdentityService {
     pub async fn from_db(db: DbExecutor) -> anyhow::Result<Self> {
         crate::dao::init(&db).await?;
+        let signer = signer::from_env()?;
 
-        let (sender, receiver) = futures::channel::mpsc::unbounded();
-        let subscription = Rc::new(RefCell::new(Subscription::default()));
-        {
-            let subscription = subscription.clone();
-            Arbiter::spawn(async move {
-                let _ = receiver
-                    .for_each(|event| send_event(subscription.borrow(), event))
-                    .await;
-            })
-        }
+        // generated up front, since the signer may need to be asked for it
+        let has_default = db
+            .as_dao::<IdentityDao>()
+            .list_identities()
+            .await?
+            .iter()
+            .any(|identity| identity.is_default);
+        let new_default = if has_default {
+            None
+        } else {
+            let key = signer.generate(None, "".into()).await?;
+            Some(new_identity(&key, true)?)
+        };
 
         let default_key = db
             .as_dao::<IdentityDao>()
-            .init_default_key(|| {
+            .init_default_key(move || {
                 log::info!("generating new default identity");
-                let key: IdentityKey = generate_new(None, "".into()).into();
-                let new_identity = Identity {
-                    identity_id: key.id(),
-                    key_file_json: key.to_key_file().map_err(|e| DaoError::internal(e))?,
-                    is_default: true,
-                    is_deleted: false,
-                    alias: None,
-                    note: None,
-                    created_date: Utc::now().naive_utc(),
-                };
-
-                Ok(new_identity)
+                new_default.ok_or_else(|| DaoError::internal("default identity not generated"))
             })
             .await?
             .identity_id;
@@ -116,20 +96,28 @@ impl IdentityService {
             let _ = ids.insert(key.id(), key);
         }
 
+        // keys created in the signer directly become identities as well
+        for node_id in signer.list().await? {
+            if ids.contains_key(&node_id) {
+                continue;
+            }
+            log::info!("adding identity {:?} held by the signer", node_id);
+            let key = IdentityKey::external(node_id, None);
+            db.as_dao::<IdentityDao>()
+                .create_identity(new_identity(&key, false)?)
+                .await?;
+            let _ = ids.insert(node_id, key);
+        }
+
         Ok(IdentityService {
             default_key,
             db,
             ids,
-            sender,
-            subscription,
             alias_to_id,
+            signer,
         })
     }
 
-    fn sender(&self) -> &futures::channel::mpsc::UnboundedSender<model::event::Event> {
-        &self.sender
-    }
-
     pub fn get_by_alias(&self, alias: &str) -> Result<Option<model::IdentityInfo>, model::Error> {
         let addr = match self.alias_to_id.get(alias) {
             None => return Ok(None),
@@ -170,19 +158,10 @@ impl IdentityService {
         &mut self,
         alias: Option<String>,
     ) -> Result<model::IdentityInfo, model::Error> {
-        let key = generate_new(alias.clone(), "".into());
+        let key = self.signer.generate(alias.clone(), "".into()).await?;
 
-        let new_identity = Identity {
-            identity_id: key.id(),
-            key_file_json: key
-                .to_key_file()
-                .map_err(|e| model::Error::InternalErr(e.to_string()))?,
-            is_default: false,
-            is_deleted: false,
-            alias: key.alias().map(ToOwned::to_owned),
-            note: None,
-            created_date: Utc::now().naive_utc(),
-        };
+        let new_identity =
+            new_identity(&key, false).map_err(|e| model::Error::InternalErr(e.to_string()))?;
 
         self.db
             .as_dao::<IdentityDao>()
@@ -261,13 +240,102 @@ impl IdentityService {
         Ok(output)
     }
 
+    /// Signer and a copy of the key of `node_id` if it is held by an external signer,
+    /// to sign with without holding up the service while the signer answers.
+    pub fn external_key(
+        &self,
+        node_id: NodeId,
+    ) -> Result<Option<(Rc<dyn Signer>, IdentityKey)>, model::Error> {
+        match self.ids.get(&node_id) {
+            Some(key) if key.is_external() => Ok(Some((
+                self.signer.clone(),
+                IdentityKey::external(key.id(), None),
+            ))),
+            Some(_) => Ok(None),
+            None => Err(model::Error::NodeNotFound(Box::new(node_id))),
+        }
+    }
+
     pub async fn sign(&mut self, node_id: NodeId, data: Vec<u8>) -> Result<Vec<u8>, model::Error> {
+        let key = match self.ids.get(&node_id) {
+            Some(key) => key,
+            None => return Err(model::Error::NodeNotFound(Box::new(node_id))),
+        };
+        self.signer.sign(key, data.as_slice()).await
+    }
+
+    pub async fn change_password(
+        &mut self,
+        node_id: NodeId,
+        old_password: Protected,
+        new_password: Protected,
+    ) -> Result<model::IdentityInfo, model::Error> {
+        let default_key = self.default_key;
+        let db = self.db.clone();
         let key = self.get_key_by_id(&node_id)?;
-        if let Some(signature) = key.sign(data.as_slice()) {
-            Ok(signature)
-        } else {
-            Err(model::Error::new_err_msg("sign error"))
+        let key_file = match key
+            .reencrypt(&old_password, &new_password)
+            .map_err(model::Error::new_err_msg)?
+        {
+            Some(key_file) => key_file,
+            None => return Err(model::Error::InvalidPassword),
+        };
+        let key_file_json = serde_json::to_string(&key_file).map_err(model::Error::new_err_msg)?;
+
+        // stored first, so the key in memory never gets ahead of the database
+        db.as_dao::<IdentityDao>()
+            .update_key_file(node_id, key_file_json)
+            .await
+            .map_err(model::Error::new_err_msg)?;
+        key.replace_key_file(key_file);
+        Ok(to_info(&default_key, key))
+    }
+
+    pub fn export(
+        &self,
+        node_id: &NodeId,
+        new_password: Option<&Protected>,
+    ) -> Result<model::ExportedIdentity, model::Error> {
+        let key = match self.ids.get(node_id) {
+            Some(key) => key,
+            None => return Err(model::Error::NodeNotFound(Box::new(node_id.clone()))),
+        };
+        if key.is_external() {
+            return Err(model::Error::new_err_msg(
+                "key held by an external signer cannot be exported",
+            ));
         }
+        let key_file = match new_password {
+            Some(_) if key.is_locked() => return Err(model::Error::Locked(Box::new(key.id()))),
+            Some(password) => key
+                .to_key_file_with_password(password)
+                .map_err(model::Error::new_err_msg)?,
+            None => key.to_key_file().map_err(model::Error::new_err_msg)?,
+        };
+        Ok(model::ExportedIdentity {
+            alias: key.alias().map(ToOwned::to_owned),
+            node_id: key.id(),
+            is_default: self.default_key == key.id(),
+            key_file,
+        })
+    }
+
+    pub fn export_all(
+        &self,
+        new_password: Option<Protected>,
+    ) -> Result<model::ExportedIdentities, model::Error> {
+        let mut identities = model::ExportedIdentities::default();
+        for (node_id, key) in &self.ids {
+            match self.export(node_id, new_password.as_ref()) {
+                Ok(exported) => identities.exported.push(exported),
+                Err(e) => identities.skipped.push(model::SkippedIdentity {
+                    alias: key.alias().map(ToOwned::to_owned),
+                    node_id: *node_id,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        Ok(identities)
     }
 
     pub async fn update_identity(
@@ -326,14 +394,6 @@ impl IdentityService {
         })
     }
 
-    pub async fn subscribe(
-        &mut self,
-        subscribe: model::Subscribe,
-    ) -> Result<model::Ack, model::Error> {
-        self.subscription.borrow_mut().subscribe(subscribe.endpoint);
-        Ok(model::Ack {})
-    }
-
     pub fn bind_service(me: Arc<Mutex<Self>>) {
         let this = me.clone();
         let _ = bus::bind(model::BUS_ID, move |_list: model::List| {
@@ -388,16 +448,13 @@ impl IdentityService {
         let _ = bus::bind(model::BUS_ID, move |lock: model::Lock| {
             let this = this.clone();
             async move {
-                let mut lock_sender = this.lock().await.sender().clone();
-
                 let result = this.lock().await.lock(lock.node_id).await;
 
                 if result.is_ok() {
-                    let _ = lock_sender
-                        .send(model::event::Event::AccountLocked {
-                            identity: lock.node_id,
-                        })
-                        .await;
+                    publish_event(model::event::Event::AccountLocked {
+                        identity: lock.node_id,
+                    })
+                    .await;
                 }
 
                 result
@@ -407,18 +464,16 @@ impl IdentityService {
         let _ = bus::bind(model::BUS_ID, move |unlock: model::Unlock| {
             let this = this.clone();
             async move {
-                let mut unlock_sender = this.lock().await.sender().clone();
                 let result = this
                     .lock()
                     .await
                     .unlock(unlock.node_id, unlock.password.into())
                     .await;
                 if result.is_ok() {
-                    let _ = unlock_sender
-                        .send(model::event::Event::AccountUnlocked {
-                            identity: unlock.node_id,
-                        })
-                        .await;
+                    publish_event(model::event::Event::AccountUnlocked {
+                        identity: unlock.node_id,
+                    })
+                    .await;
                 }
                 result
             }
@@ -426,12 +481,141 @@ impl IdentityService {
         let this = me.clone();
         let _ = bus::bind(model::BUS_ID, move |sign: model::Sign| {
             let this = this.clone();
-            async move { this.lock().await.sign(sign.node_id, sign.payload).await }
+            async move {
+                let external_key = this.lock().await.external_key(sign.node_id)?;
+                match external_key {
+                    Some((signer, key)) => signer.sign(&key, &sign.payload).await,
+                    None => this.lock().await.sign(sign.node_id, sign.payload).await,
+                }
+            }
+        });
+        let _ = bus::bind(model::BUS_ID, move |verify: model::Verify| {
+            future::ready(crate::signature::verify(
+                &verify.node_id,
+                &verify.data,
+                &verify.signature,
+            ))
         });
         let this = me.clone();
-        let _ = bus::bind(model::BUS_ID, move |subscribe: model::Subscribe| {
+        let _ = bus::bind(model::BUS_ID, move |change: model::ChangePassword| {
+            let this = this.clone();
+            async move {
+                this.lock()
+                    .await
+                    .change_password(
+                        change.node_id,
+                        change.old_password.into(),
+                        change.new_password.into(),
+                    )
+                    .await
+            }
+        });
+        let this = me.clone();
+        let _ = bus::bind(model::BUS_ID, move |export: model::Export| {
             let this = this.clone();
-            async move { this.lock().await.subscribe(subscribe).await }
+            async move {
+                let new_password = export.new_password.map(Protected::from);
+                this.lock()
+                    .await
+                    .export(&export.node_id, new_password.as_ref())
+            }
+        });
+        let this = me.clone();
+        let _ = bus::bind(model::BUS_ID, move |export: model::ExportAll| {
+            let this = this.clone();
+            async move {
+                let new_password = export.new_password.map(Protected::from);
+                this.lock().await.export_all(new_password)
+            }
+        });
+        let _ = bus::bind(model::BUS_ID, move |subscribe: model::Subscribe| {
+            let _ = bus::subscribe(move |event: model::event::Event| {
+                super::forward_event(subscribe.endpoint.clone(), event)
+            });
+            future::ok(model::Ack {})
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_service(dir: &tempdir::TempDir) -> IdentityService {
+        let db = DbExecutor::from_data_dir(dir.path(), "identity").unwrap();
+        IdentityService::from_db(db).await.unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn export_all_skips_identities_which_cannot_be_exported() {
+        let dir = tempdir::TempDir::new("identity").unwrap();
+        let mut service = test_service(&dir).await;
+        let default_id = service.default_key;
+        let external_id: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        service.ids.insert(
+            external_id,
+            IdentityKey::external(external_id, Some("signer".to_string())),
+        );
+        service.lock(default_id).await.unwrap();
+
+        // exported as stored, even when locked
+        let identities = service.export_all(None).unwrap();
+        assert_eq!(identities.exported.len(), 1);
+        assert_eq!(identities.exported[0].node_id, default_id);
+        assert!(identities.exported[0].is_default);
+        assert_eq!(identities.skipped.len(), 1);
+        assert_eq!(identities.skipped[0].node_id, external_id);
+        assert_eq!(identities.skipped[0].alias.as_deref(), Some("signer"));
+
+        // re-encrypting needs the key unlocked
+        let identities = service.export_all(Some("new".into())).unwrap();
+        assert!(identities.exported.is_empty());
+        assert_eq!(identities.skipped.len(), 2);
+
+        service.unlock(default_id, "".into()).await.unwrap();
+        let identities = service.export_all(Some("new".into())).unwrap();
+        assert_eq!(identities.exported.len(), 1);
+        let key_file: KeyFile = serde_json::from_str(&identities.exported[0].key_file).unwrap();
+        assert!(key_file.to_secret_key(&"new".into()).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn change_password_reencrypts_stored_keystore() {
+        let dir = tempdir::TempDir::new("identity").unwrap();
+        let mut service = test_service(&dir).await;
+        let default_id = service.default_key;
+
+        let result = service
+            .change_password(default_id, "wrong".into(), "secret".into())
+            .await;
+        match result {
+            Err(model::Error::InvalidPassword) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        service
+            .change_password(default_id, "".into(), "secret".into())
+            .await
+            .unwrap();
+        let stored = service
+            .db
+            .as_dao::<IdentityDao>()
+            .list_identities()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|identity| identity.identity_id == default_id)
+            .unwrap();
+        let key_file: KeyFile = serde_json::from_str(&stored.key_file_json).unwrap();
+        assert!(key_file.to_secret_key(&"secret".into()).is_ok());
+        assert!(key_file.to_secret_key(&"".into()).is_err());
+
+        service.lock(default_id).await.unwrap();
+        let info = service.unlock(default_id, "".into()).await.unwrap();
+        assert!(info.is_locked);
+        let info = service.unlock(default_id, "secret".into()).await.unwrap();
+        assert!(!info.is_locked);
+    }
+}
@@ -0,0 +1,64 @@
+//! Checking signatures made by identities.
+
+use std::convert::TryInto;
+
+use ya_client_model::NodeId;
+use ya_core_model::identity as model;
+
+/// Recovers the identity that signed the 32-byte `data`, given 65 bytes of `v`, `r`
+/// and `s` as produced by `model::Sign`.
+pub fn recover(data: &[u8], signature: &[u8]) -> Result<NodeId, model::Error> {
+    if data.len() != 32 {
+        return Err(model::Error::InvalidSignature(format!(
+            "signed data must be 32 bytes, got {}",
+            data.len()
+        )));
+    }
+    if signature.len() != 65 {
+        return Err(model::Error::InvalidSignature(format!(
+            "signature must be 65 bytes, got {}",
+            signature.len()
+        )));
+    }
+    let signature = ethsign::Signature {
+        v: signature[0],
+        r: signature[1..33].try_into().unwrap(),
+        s: signature[33..65].try_into().unwrap(),
+    };
+    let public = signature
+        .recover(data)
+        .map_err(|e| model::Error::InvalidSignature(e.to_string()))?;
+    Ok(NodeId::from(public.address().as_ref()))
+}
+
+/// Tells if `signature` of `data` was made with the key of `node_id`.
+pub fn verify(node_id: &NodeId, data: &[u8], signature: &[u8]) -> Result<bool, model::Error> {
+    Ok(recover(data, signature)? == *node_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethsign::SecretKey;
+
+    fn sign(secret: &SecretKey, data: &[u8]) -> Vec<u8> {
+        let signature = secret.sign(data).unwrap();
+        let mut bytes = vec![signature.v];
+        bytes.extend_from_slice(&signature.r);
+        bytes.extend_from_slice(&signature.s);
+        bytes
+    }
+
+    #[test]
+    fn verify_checks_signer() {
+        let secret = SecretKey::from_raw(&[1u8; 32]).unwrap();
+        let other = SecretKey::from_raw(&[2u8; 32]).unwrap();
+        let node_id = NodeId::from(secret.public().address().as_ref());
+        let data = [7u8; 32];
+
+        assert!(verify(&node_id, &data, &sign(&secret, &data)).unwrap());
+        assert!(!verify(&node_id, &data, &sign(&other, &data)).unwrap());
+        assert!(!verify(&node_id, &[8u8; 32], &sign(&secret, &data)).unwrap());
+        assert!(verify(&node_id, &data, &[0u8; 64]).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+//! Backends holding identity keys and signing with them.
+//!
+//! By default keys live in keystores kept in the identity table and are used in process.
+//! With `YAGNA_SIGNER_SOCKET` set, new keys are generated and used by an external signer
+//! process instead, see `remote`. Keys created or imported before stay in the keystore.
+//! Requests to the external signer fail after `YAGNA_SIGNER_TIMEOUT`, 10s by default.
+
+use ethsign::Protected;
+use std::rc::Rc;
+
+use ya_client_model::NodeId;
+use ya_core_model::identity as model;
+
+use crate::id_key::{generate_new, IdentityKey};
+
+#[cfg(unix)]
+pub mod remote;
+
+pub const SIGNER_SOCKET_ENV_VAR: &str = "YAGNA_SIGNER_SOCKET";
+pub const SIGNER_TIMEOUT_ENV_VAR: &str = "YAGNA_SIGNER_TIMEOUT";
+
+#[async_trait::async_trait(?Send)]
+pub trait Signer {
+    /// Generates a new key.
+    async fn generate(
+        &self,
+        alias: Option<String>,
+        password: Protected,
+    ) -> Result<IdentityKey, model::Error>;
+
+    /// Keys held by the backend itself, listed with identities even when not created
+    /// through this service.
+    async fn list(&self) -> Result<Vec<NodeId>, model::Error>;
+
+    /// Signs a 32-byte `payload` with `key`, giving 65 bytes of `v`, `r` and `s`.
+    async fn sign(&self, key: &IdentityKey, payload: &[u8]) -> Result<Vec<u8>, model::Error>;
+}
+
+/// Keys in keystores, decrypted in process memory when unlocked.
+pub struct KeystoreSigner;
+
+#[async_trait::async_trait(?Send)]
+impl Signer for KeystoreSigner {
+    async fn generate(
+        &self,
+        alias: Option<String>,
+        password: Protected,
+    ) -> Result<IdentityKey, model::Error> {
+        Ok(generate_new(alias, password))
+    }
+
+    async fn list(&self) -> Result<Vec<NodeId>, model::Error> {
+        // all of them are in the identity table already
+        Ok(Vec::new())
+    }
+
+    async fn sign(&self, key: &IdentityKey, payload: &[u8]) -> Result<Vec<u8>, model::Error> {
+        sign_in_process(key, payload)
+    }
+}
+
+fn sign_in_process(key: &IdentityKey, payload: &[u8]) -> Result<Vec<u8>, model::Error> {
+    if key.is_external() {
+        return Err(model::Error::new_err_msg(format!(
+            "key of {:?} is held by an external signer, which is not configured",
+            key.id()
+        )));
+    }
+    key.sign(payload)
+        .ok_or_else(|| model::Error::new_err_msg("sign error"))
+}
+
+/// Backend selected with `YAGNA_SIGNER_SOCKET`.
+pub fn from_env() -> anyhow::Result<Rc<dyn Signer>> {
+    match std::env::var_os(SIGNER_SOCKET_ENV_VAR) {
+        None => Ok(Rc::new(KeystoreSigner)),
+        #[cfg(unix)]
+        Some(socket) => {
+            let mut signer = remote::RemoteSigner::new(&socket);
+            if let Ok(timeout) = std::env::var(SIGNER_TIMEOUT_ENV_VAR) {
+                let timeout = humantime::parse_duration(&timeout)
+                    .map_err(|e| anyhow::anyhow!("invalid {}: {}", SIGNER_TIMEOUT_ENV_VAR, e))?;
+                signer = signer.with_timeout(timeout);
+            }
+            log::info!("using external signer at {:?}", socket);
+            Ok(Rc::new(signer))
+        }
+        #[cfg(not(unix))]
+        Some(_) => anyhow::bail!("{} is supported on unix only", SIGNER_SOCKET_ENV_VAR),
+    }
+}
@@ -0,0 +1,255 @@
+//! External signer reached over a unix socket.
+//!
+//! Each request is a JSON-RPC 2.0 call on its own connection, written as a single line
+//! and answered with a single line. Addresses and binary data are `0x` prefixed hex.
+//!
+//! * `generate` with no params creates a key and returns its address,
+//! * `list` with no params returns addresses of all keys held,
+//! * `sign` with `{"address", "payload"}` signs the 32-byte payload and returns the
+//!   65-byte signature as `v`, `r`, `s`.
+
+use ethsign::Protected;
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use ya_client_model::NodeId;
+use ya_core_model::identity as model;
+
+use super::{sign_in_process, Signer};
+use crate::id_key::IdentityKey;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct RemoteSigner {
+    socket: PathBuf,
+    timeout: Duration,
+    last_id: Cell<u64>,
+}
+
+impl RemoteSigner {
+    pub fn new(socket: impl AsRef<Path>) -> Self {
+        RemoteSigner {
+            socket: socket.as_ref().to_path_buf(),
+            timeout: DEFAULT_TIMEOUT,
+            last_id: Cell::new(0),
+        }
+    }
+
+    /// Fails requests not answered within `timeout`, including connecting.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, model::Error> {
+        let id = self.last_id.get() + 1;
+        self.last_id.set(id);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let response = tokio::time::timeout(self.timeout, self.exchange(request))
+            .await
+            .map_err(|_| {
+                model::Error::new_err_msg(format!(
+                    "external signer: no response to {} within {:?}",
+                    method, self.timeout
+                ))
+            })?
+            .map_err(|e| model::Error::new_err_msg(format!("external signer: {}", e)))?;
+
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(model::Error::new_err_msg(format!(
+                "external signer {} failed: {}",
+                method, message
+            )));
+        }
+        response
+            .get("result")
+            .cloned()
+            .ok_or_else(|| model::Error::new_err_msg("external signer sent no result"))
+    }
+
+    async fn exchange(&self, request: Value) -> anyhow::Result<Value> {
+        let mut stream = UnixStream::connect(&self.socket).await?;
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        stream.write_all(&line).await?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).await?;
+        Ok(serde_json::from_str(&response)?)
+    }
+}
+
+fn parse_address(value: &Value) -> Result<NodeId, model::Error> {
+    value
+        .as_str()
+        .and_then(|address| address.parse().ok())
+        .ok_or_else(|| model::Error::new_err_msg(format!("invalid address: {}", value)))
+}
+
+#[async_trait::async_trait(?Send)]
+impl Signer for RemoteSigner {
+    async fn generate(
+        &self,
+        alias: Option<String>,
+        _password: Protected,
+    ) -> Result<IdentityKey, model::Error> {
+        let address = self.call("generate", json!({})).await?;
+        Ok(IdentityKey::external(parse_address(&address)?, alias))
+    }
+
+    async fn list(&self) -> Result<Vec<NodeId>, model::Error> {
+        match self.call("list", json!({})).await? {
+            Value::Array(addresses) => addresses.iter().map(parse_address).collect(),
+            other => Err(model::Error::new_err_msg(format!(
+                "invalid key list: {}",
+                other
+            ))),
+        }
+    }
+
+    async fn sign(&self, key: &IdentityKey, payload: &[u8]) -> Result<Vec<u8>, model::Error> {
+        if !key.is_external() {
+            return sign_in_process(key, payload);
+        }
+        let params = json!({
+            "address": key.id().to_string(),
+            "payload": format!("0x{}", hex::encode(payload)),
+        });
+        let signature = self.call("sign", params).await?;
+        signature
+            .as_str()
+            .map(|signature| signature.trim_start_matches("0x"))
+            .and_then(|signature| hex::decode(signature).ok())
+            .filter(|signature| signature.len() == 65)
+            .ok_or_else(|| model::Error::new_err_msg(format!("invalid signature: {}", signature)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethsign::SecretKey;
+    use rand::Rng;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+    use std::rc::Rc;
+    use tokio::net::UnixListener;
+
+    type Keys = Rc<RefCell<HashMap<NodeId, SecretKey>>>;
+
+    fn handle(keys: &Keys, request: &Value) -> Value {
+        let result = match request["method"].as_str() {
+            Some("generate") => {
+                let random_bytes: [u8; 32] = rand::thread_rng().gen();
+                let secret = SecretKey::from_raw(&random_bytes).unwrap();
+                let node_id = NodeId::from(secret.public().address().as_ref());
+                keys.borrow_mut().insert(node_id, secret);
+                json!(node_id.to_string())
+            }
+            Some("list") => json!(keys
+                .borrow()
+                .keys()
+                .map(|node_id| node_id.to_string())
+                .collect::<Vec<_>>()),
+            Some("sign") => {
+                let node_id: NodeId = request["params"]["address"]
+                    .as_str()
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let payload = request["params"]["payload"].as_str().unwrap();
+                let payload = hex::decode(payload.trim_start_matches("0x")).unwrap();
+                let signature = keys.borrow()[&node_id].sign(&payload).unwrap();
+                let mut bytes = vec![signature.v];
+                bytes.extend_from_slice(&signature.r);
+                bytes.extend_from_slice(&signature.s);
+                json!(format!("0x{}", hex::encode(bytes)))
+            }
+            _ => {
+                let error = json!({"code": -32601, "message": "no such method"});
+                return json!({"jsonrpc": "2.0", "id": request["id"], "error": error});
+            }
+        };
+        json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+    }
+
+    /// Stand-in for an external signer, keeping keys in memory.
+    async fn serve(mut listener: UnixListener) {
+        let keys = Keys::default();
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let response = handle(&keys, &serde_json::from_str(&line).unwrap());
+            let mut response = serde_json::to_vec(&response).unwrap();
+            response.push(b'\n');
+            stream.get_mut().write_all(&response).await.unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn remote_signer_generates_lists_and_signs() {
+        let socket =
+            std::env::temp_dir().join(format!("yagna-signer-{}.sock", uuid::Uuid::new_v4()));
+        actix_rt::spawn(serve(UnixListener::bind(&socket).unwrap()));
+        let signer = RemoteSigner::new(&socket);
+
+        let key = signer
+            .generate(Some("ops".into()), "".into())
+            .await
+            .unwrap();
+        assert!(key.is_external());
+        assert!(!key.is_locked());
+        assert_eq!(key.alias(), Some("ops"));
+        assert_eq!(signer.list().await.unwrap(), vec![key.id()]);
+
+        let payload = [7u8; 32];
+        let signature = signer.sign(&key, &payload).await.unwrap();
+        let signature = ethsign::Signature {
+            v: signature[0],
+            r: signature[1..33].try_into().unwrap(),
+            s: signature[33..].try_into().unwrap(),
+        };
+        let public = signature.recover(&payload).unwrap();
+        assert_eq!(NodeId::from(public.address().as_ref()), key.id());
+
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[actix_rt::test]
+    async fn unanswered_requests_time_out() {
+        let socket =
+            std::env::temp_dir().join(format!("yagna-signer-{}.sock", uuid::Uuid::new_v4()));
+        let mut listener = UnixListener::bind(&socket).unwrap();
+        actix_rt::spawn(async move {
+            // connections are kept open, but never answered
+            let mut streams = Vec::new();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                streams.push(stream);
+            }
+        });
+        let signer = RemoteSigner::new(&socket).with_timeout(Duration::from_millis(100));
+
+        let started = std::time::Instant::now();
+        assert!(signer.list().await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -38,5 +38,6 @@ pub fn generate_identity(name: &str) -> Identity {
         name: name.to_string(),
         role: "manager".to_string(),
         identity: NodeId::from(random_node_id.as_bytes()),
+        scope: None,
     }
 }
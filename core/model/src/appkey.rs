@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use ya_client_model::NodeId;
@@ -7,6 +7,13 @@ use ya_service_bus::RpcMessage;
 pub const BUS_ID: &'static str = "/local/appkey";
 
 pub const DEFAULT_ROLE: &str = "manager";
+pub const REQUESTOR_ROLE: &str = "requestor";
+pub const PROVIDER_ROLE: &str = "provider";
+pub const READ_ONLY_ROLE: &str = "read-only";
+
+/// Roles app keys can be created with, see `ya_service_api_web::middleware::auth::role`
+/// for what each of them permits.
+pub const ROLES: [&str; 4] = [DEFAULT_ROLE, REQUESTOR_ROLE, PROVIDER_ROLE, READ_ONLY_ROLE];
 
 const DEFAULT_PAGE_SIZE: u32 = 20;
 
@@ -32,6 +39,25 @@ pub struct Create {
     pub name: String,
     pub role: String,
     pub identity: NodeId,
+    /// Key is rejected after this time (UTC), never when not set.
+    #[serde(default)]
+    pub expires_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub scope: Option<Scope>,
+}
+
+/// Resources an app key is restricted to. A scoped key may only act on market
+/// subscriptions, agreements (and their activities) and allocations listed here;
+/// requests not naming any of them, like creating new ones, are rejected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scope {
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    #[serde(default)]
+    pub agreements: Vec<String>,
+    #[serde(default)]
+    pub allocations: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -46,6 +72,13 @@ impl Get {
     }
 }
 
+/// Looks an entry up by its name, e.g. taken from a TLS client certificate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetByName {
+    pub name: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct List {
@@ -71,6 +104,30 @@ pub struct Remove {
     pub identity: Option<String>,
 }
 
+/// Issues a new key for the named entry, keeping its name, role and expiry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotate {
+    pub name: String,
+    pub identity: Option<String>,
+}
+
+/// Notes requests authorized with app keys, sent periodically by the REST API middleware.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordUsage {
+    pub usage: Vec<KeyUsage>,
+}
+
+/// Requests authorized with the app key named `name` since the previous `RecordUsage`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyUsage {
+    pub name: String,
+    pub requests: u64,
+    pub last_used: NaiveDateTime,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppKey {
@@ -79,6 +136,23 @@ pub struct AppKey {
     pub role: String,
     pub identity: NodeId,
     pub created_date: NaiveDateTime,
+    #[serde(default)]
+    pub expires_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub last_used: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub request_count: u64,
+    #[serde(default)]
+    pub scope: Option<Scope>,
+}
+
+impl AppKey {
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= Utc::now().naive_utc(),
+            None => false,
+        }
+    }
 }
 
 impl RpcMessage for Create {
@@ -93,6 +167,12 @@ impl RpcMessage for Get {
     type Error = Error;
 }
 
+impl RpcMessage for GetByName {
+    const ID: &'static str = "GetByName";
+    type Item = AppKey;
+    type Error = Error;
+}
+
 impl RpcMessage for List {
     const ID: &'static str = "List";
     type Item = (Vec<AppKey>, u32);
@@ -105,6 +185,20 @@ impl RpcMessage for Remove {
     type Error = Error;
 }
 
+impl RpcMessage for Rotate {
+    const ID: &'static str = "Rotate";
+    type Item = String;
+    type Error = Error;
+}
+
+impl RpcMessage for RecordUsage {
+    const ID: &'static str = "RecordUsage";
+    type Item = ();
+    type Error = Error;
+}
+
+/// Asks to forward every `event::Event` to `endpoint`. In-process consumers
+/// may subscribe to the event with `ya_service_bus::typed::subscribe` instead.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Subscribe {
@@ -121,7 +215,7 @@ pub mod event {
     use super::Error;
     use serde::{Deserialize, Serialize};
     use ya_client_model::NodeId;
-    use ya_service_bus::RpcMessage;
+    use ya_service_bus::{BusEvent, RpcMessage};
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -134,4 +228,38 @@ pub mod event {
         type Item = ();
         type Error = Error;
     }
+
+    impl BusEvent for Event {
+        const TOPIC: &'static str = "appkey-events";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn app_key(expires_at: Option<NaiveDateTime>) -> AppKey {
+        AppKey {
+            name: "app".to_string(),
+            key: "key".to_string(),
+            role: DEFAULT_ROLE.to_string(),
+            identity: "0xbabe000000000000000000000000000000000000"
+                .parse()
+                .unwrap(),
+            created_date: Utc::now().naive_utc(),
+            expires_at,
+            last_used: None,
+            request_count: 0,
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn key_expires_at_given_time() {
+        let now = Utc::now().naive_utc();
+        assert!(!app_key(None).is_expired());
+        assert!(app_key(Some(now - Duration::seconds(1))).is_expired());
+        assert!(!app_key(Some(now + Duration::hours(1))).is_expired());
+    }
 }
@@ -0,0 +1,43 @@
+//! Service bus router introspection.
+//!
+//! These calls are answered by the GSB router itself, not by any yagna service.
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ya_service_bus::RpcMessage;
+
+pub use ya_sb_proto::introspection::{
+    BusStatus, CallStats, EndpointInfo, PendingCallInfo, SubscriptionInfo, TraceFilter, BUS_ID,
+    LATENCY_BUCKETS_MS,
+};
+
+#[derive(Clone, Error, Debug, Serialize, Deserialize)]
+#[error("bus introspection error: {0}")]
+pub struct Error(pub String);
+
+/// Snapshot of router state: endpoints, subscriptions, pending calls and call statistics.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStatus {}
+
+impl RpcMessage for GetStatus {
+    const ID: &'static str = ya_sb_proto::introspection::GET_STATUS;
+    type Item = BusStatus;
+    type Error = Error;
+}
+
+/// Replaces address prefixes of calls logged by the router.
+/// Empty filter disables tracing. Returns prefixes in effect.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SetTrace(pub TraceFilter);
+
+impl SetTrace {
+    pub fn with_prefixes(prefixes: Vec<String>) -> Self {
+        SetTrace(TraceFilter { prefixes })
+    }
+}
+
+impl RpcMessage for SetTrace {
+    const ID: &'static str = ya_sb_proto::introspection::SET_TRACE;
+    type Item = Vec<String>;
+    type Error = Error;
+}
@@ -20,6 +20,12 @@ pub enum Error {
     InternalErr(String),
     #[error("bad keystore format: {0}")]
     BadKeyStoreFormat(String),
+    #[error("node {0:?} is locked")]
+    Locked(Box<NodeId>),
+    #[error("invalid password")]
+    InvalidPassword,
+    #[error("invalid signature: {0}")]
+    InvalidSignature(String),
 }
 
 impl Error {
@@ -199,6 +205,124 @@ impl RpcMessage for Sign {
     type Error = Error;
 }
 
+/// Re-encrypts the identity key with `new_password`, after checking `old_password`.
+/// An empty password stands for none, so unprotected identities can be given one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ChangePassword {
+    pub node_id: NodeId,
+    pub old_password: String,
+    pub new_password: String,
+}
+
+impl ChangePassword {
+    pub fn with_id(node_id: NodeId, old_password: String, new_password: String) -> Self {
+        Self {
+            node_id,
+            old_password,
+            new_password,
+        }
+    }
+}
+
+impl RpcMessage for ChangePassword {
+    const ID: &'static str = "ChangePassword";
+    type Item = IdentityInfo;
+    type Error = Error;
+}
+
+/// Exports an identity key as an encrypted keystore, suitable for `CreateGenerated`.
+///
+/// The key is exported as stored, encrypted with its current password, unless
+/// `new_password` is given. Re-encrypting requires the identity to be unlocked.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct Export {
+    pub node_id: NodeId,
+    pub new_password: Option<String>,
+}
+
+impl Export {
+    pub fn with_id(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            new_password: Default::default(),
+        }
+    }
+
+    pub fn with_new_password(mut self, new_password: impl Into<Option<String>>) -> Self {
+        self.new_password = new_password.into();
+        self
+    }
+}
+
+impl RpcMessage for Export {
+    const ID: &'static str = "Export";
+    type Item = ExportedIdentity;
+    type Error = Error;
+}
+
+/// Exports all identities, see `Export`. Identities which cannot be exported,
+/// e.g. held by an external signer, are skipped and reported one by one.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportAll {
+    pub new_password: Option<String>,
+}
+
+impl RpcMessage for ExportAll {
+    const ID: &'static str = "ExportAll";
+    type Item = ExportedIdentities;
+    type Error = Error;
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedIdentities {
+    pub exported: Vec<ExportedIdentity>,
+    pub skipped: Vec<SkippedIdentity>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedIdentity {
+    pub alias: Option<String>,
+    pub node_id: NodeId,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedIdentity {
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub node_id: NodeId,
+    #[serde(default)]
+    pub is_default: bool,
+    /// Keystore JSON.
+    pub key_file: String,
+}
+
+/// Checks that `signature` of the 32-byte `data` was made with the key of `node_id`,
+/// as returned by `Sign`. Malformed signatures fail with `Error::InvalidSignature`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verify {
+    pub node_id: NodeId,
+    pub data: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl RpcMessage for Verify {
+    const ID: &'static str = "Verify";
+    type Item = bool;
+    type Error = Error;
+}
+
+/// Asks to forward every `event::Event` to `endpoint`. In-process consumers
+/// may subscribe to the event with `ya_service_bus::typed::subscribe` instead.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Subscribe {
@@ -215,7 +339,7 @@ pub mod event {
     use super::Error;
     use serde::{Deserialize, Serialize};
     use ya_client_model::NodeId;
-    use ya_service_bus::RpcMessage;
+    use ya_service_bus::{BusEvent, RpcMessage};
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -229,4 +353,8 @@ pub mod event {
         type Item = ();
         type Error = Error;
     }
+
+    impl BusEvent for Event {
+        const TOPIC: &'static str = "identity-events";
+    }
 }
@@ -6,6 +6,9 @@ pub mod activity;
 #[cfg(feature = "appkey")]
 pub mod appkey;
 
+#[cfg(feature = "bus")]
+pub mod bus;
+
 #[cfg(feature = "driver")]
 pub mod driver;
 
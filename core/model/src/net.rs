@@ -12,7 +12,8 @@ pub const PUBLIC_PREFIX: &str = "/public";
 pub mod local {
     use serde::de::DeserializeOwned;
     use serde::{Deserialize, Serialize};
-    use ya_service_bus::RpcMessage;
+    use ya_client_model::NodeId;
+    use ya_service_bus::{BusEvent, RpcMessage};
 
     pub const BUS_ID: &str = "/local/net";
 
@@ -34,6 +35,14 @@ pub mod local {
             Self { id, topic, body }
         }
 
+        /// Broadcasts on a subtopic of `M::TOPIC`, e.g. `<TOPIC>/<subnet>`, reaching only
+        /// subscribers of that subtopic or of a matching wildcard filter.
+        pub fn with_subtopic(subtopic: &str, body: M) -> Self {
+            let id = None;
+            let topic = format!("{}/{}", M::TOPIC, subtopic);
+            Self { id, topic, body }
+        }
+
         pub fn body(&self) -> &M {
             &self.body
         }
@@ -65,6 +74,14 @@ pub mod local {
     }
 
     impl Subscribe {
+        /// Subscribes `endpoint` to every topic matching `filter`, where `*` stands for
+        /// one topic segment and a trailing `**` for any number of them.
+        pub fn with_filter(filter: impl Into<String>, endpoint: impl Into<String>) -> Self {
+            let topic = filter.into();
+            let endpoint = endpoint.into();
+            Subscribe { topic, endpoint }
+        }
+
         pub fn topic(&self) -> &str {
             self.topic.as_ref()
         }
@@ -99,6 +116,60 @@ pub mod local {
         RuntimeException(String),
     }
 
+    /// State of the connection to the net hub, published locally by the net service.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Event {
+        /// Connection to the hub was lost. Remote calls fail until it is back.
+        HubDisconnected,
+        /// Connection was reestablished and all bindings were registered again.
+        HubReconnected { attempts: u32 },
+    }
+
+    impl BusEvent for Event {
+        const TOPIC: &'static str = "net-events";
+    }
+
+    /// Current state of the net service, see `Status`.
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GetStatus {}
+
+    impl RpcMessage for GetStatus {
+        const ID: &'static str = "GetStatus";
+        type Item = Status;
+        type Error = String;
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Status {
+        /// `hub` or `lan`.
+        pub mode: String,
+        /// Hub currently or last connected to, none in `lan` mode.
+        pub hub_addr: Option<String>,
+        /// Whether other nodes can be reached: connected to the hub, or discovering
+        /// nodes on the local network.
+        pub connected: bool,
+        /// Identities bound under `/net`.
+        pub node_ids: Vec<NodeId>,
+        /// Broadcast topics and filters subscribed by local services.
+        pub topics: Vec<String>,
+        /// Messages sent to other nodes.
+        pub outgoing: MessageCounters,
+        /// Messages received from other nodes.
+        pub incoming: MessageCounters,
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct MessageCounters {
+        pub calls: u64,
+        pub broadcasts: u64,
+        /// Calls which failed, either in delivery or in the callee.
+        pub failed_calls: u64,
+    }
+
     #[derive(thiserror::Error, Debug)]
     pub enum BindBroadcastError {
         #[error(transparent)]
@@ -108,6 +179,47 @@ pub mod local {
     }
 }
 
+/// Services exported by ya-net of every node, callable by other nodes through the hub.
+pub mod public {
+    use serde::{Deserialize, Serialize};
+    use ya_client_model::NodeId;
+    use ya_service_bus::RpcMessage;
+
+    pub const BUS_ID: &str = "/public/net";
+
+    /// Addresses on which the node accepts direct connections from other nodes.
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GetDirectEndpoints {}
+
+    impl RpcMessage for GetDirectEndpoints {
+        const ID: &'static str = "GetDirectEndpoints";
+        type Item = Vec<String>;
+        type Error = String;
+    }
+
+    /// Session key exchange for end-to-end encrypted calls. The initiator sends its
+    /// ephemeral key and the responder replies with its own, each signed with the
+    /// identity key of the sender.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Handshake {
+        pub initiator: NodeId,
+        pub responder: NodeId,
+        /// X25519 public key of the sender.
+        pub ephemeral_key: Vec<u8>,
+        /// Unix time in seconds, rejected when too far from the receiver's clock.
+        pub timestamp: i64,
+        pub signature: Vec<u8>,
+    }
+
+    impl RpcMessage for Handshake {
+        const ID: &'static str = "Handshake";
+        type Item = Handshake;
+        type Error = String;
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum NetApiError {
     #[error("service bus address should have {} prefix: {0}", PUBLIC_PREFIX)]
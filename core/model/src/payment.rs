@@ -242,6 +242,79 @@ pub mod local {
         type Item = Vec<Account>;
         type Error = GenericError;
     }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    pub enum OutboxStatus {
+        /// Not delivered yet, will be retried.
+        Pending,
+        /// Acknowledged by the recipient.
+        Delivered,
+        /// Refused by the recipient, will not be retried.
+        Rejected,
+        /// Not delivered before its expiration time.
+        Expired,
+    }
+
+    impl OutboxStatus {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                OutboxStatus::Pending => "PENDING",
+                OutboxStatus::Delivered => "DELIVERED",
+                OutboxStatus::Rejected => "REJECTED",
+                OutboxStatus::Expired => "EXPIRED",
+            }
+        }
+    }
+
+    impl std::str::FromStr for OutboxStatus {
+        type Err = GenericError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_uppercase().as_str() {
+                "PENDING" => Ok(OutboxStatus::Pending),
+                "DELIVERED" => Ok(OutboxStatus::Delivered),
+                "REJECTED" => Ok(OutboxStatus::Rejected),
+                "EXPIRED" => Ok(OutboxStatus::Expired),
+                _ => Err(GenericError::new(format!("Invalid outbox status: {}", s))),
+            }
+        }
+    }
+
+    impl Display for OutboxStatus {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    /// Message sent through the outbox, see [`DurableMessage`](super::public::DurableMessage).
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct OutboxEntry {
+        pub id: String,
+        pub owner_id: NodeId,
+        pub peer_id: NodeId,
+        pub message_type: String,
+        pub document_id: String,
+        pub status: OutboxStatus,
+        pub attempts: i32,
+        pub last_error: Option<String>,
+        pub created: DateTime<Utc>,
+        pub next_attempt: DateTime<Utc>,
+        pub expires: DateTime<Utc>,
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GetOutbox {
+        pub status: Option<OutboxStatus>,
+    }
+
+    impl RpcMessage for GetOutbox {
+        const ID: &'static str = "GetOutbox";
+        type Item = Vec<OutboxEntry>;
+        type Error = GenericError;
+    }
 }
 
 pub mod public {
@@ -253,6 +326,13 @@ pub mod public {
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct Ack {}
 
+    /// Message which may be sent through the payment outbox, which persists it
+    /// and redelivers it until the recipient acknowledges it or it expires.
+    pub trait DurableMessage: RpcMessage<Item = Ack, Error = SendError> + Clone + Unpin {
+        /// Id of the sent document. Sender sends it at most once per message type.
+        fn document_id(&self) -> &str;
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize, thiserror::Error)]
     pub enum SendError {
         #[error("Service error: {0}")]
@@ -295,6 +375,12 @@ pub mod public {
         type Error = SendError;
     }
 
+    impl DurableMessage for SendDebitNote {
+        fn document_id(&self) -> &str {
+            &self.0.debit_note_id
+        }
+    }
+
     #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct AcceptDebitNote {
@@ -354,6 +440,12 @@ pub mod public {
         type Error = SendError;
     }
 
+    impl DurableMessage for SendInvoice {
+        fn document_id(&self) -> &str {
+            &self.0.invoice_id
+        }
+    }
+
     #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct AcceptInvoice {
@@ -413,4 +505,10 @@ pub mod public {
         type Item = Ack;
         type Error = SendError;
     }
+
+    impl DurableMessage for SendPayment {
+        fn document_id(&self) -> &str {
+            &self.0.payment_id
+        }
+    }
 }
@@ -91,6 +91,8 @@ async fn main() -> Result<()> {
         .context(format!("Error binding local router"))?;
 
     std::env::set_var(ya_net::CENTRAL_ADDR_ENV_VAR, &options.hub_addr);
+    // example node ids have no identity keys to sign e2e handshakes with
+    std::env::set_var(ya_net::ALLOW_PLAINTEXT_ENV_VAR, "1");
     let registered_id: NodeId = "0xdad0000000000000000000000000000000000000".parse()?;
     let unregistered_id: NodeId = "0xbed0000000000000000000000000000000000000".parse()?;
     let registered_net_ids = match options.side {
@@ -24,12 +24,52 @@ where
         > + 'static,
     F: FnMut(String, SendBroadcastMessage<MsgType>) -> Output + 'static,
 {
-    log::debug!("Creating broadcast topic {}.", MsgType::TOPIC);
+    let subscribe_msg = MsgType::into_subscribe_msg(broadcast_address);
+    bind_subscription_with_caller::<MsgType, _, _>(subscribe_msg, handler).await
+}
+
+/// Like `bind_broadcast_with_caller`, but receives broadcasts on all topics matching
+/// `filter`, e.g. `<TOPIC>/*` for every subtopic of `MsgType::TOPIC`.
+pub async fn bind_broadcast_filter_with_caller<MsgType, Output, F>(
+    filter: &str,
+    broadcast_address: &str,
+    handler: F,
+) -> Result<Handle, BindBroadcastError>
+where
+    MsgType: BroadcastMessage + Send + Sync + 'static,
+    Output: Future<
+            Output = Result<
+                <SendBroadcastMessage<MsgType> as RpcMessage>::Item,
+                <SendBroadcastMessage<MsgType> as RpcMessage>::Error,
+            >,
+        > + 'static,
+    F: FnMut(String, SendBroadcastMessage<MsgType>) -> Output + 'static,
+{
+    let subscribe_msg = net::local::Subscribe::with_filter(filter, broadcast_address);
+    bind_subscription_with_caller::<MsgType, _, _>(subscribe_msg, handler).await
+}
+
+async fn bind_subscription_with_caller<MsgType, Output, F>(
+    subscribe_msg: net::local::Subscribe,
+    handler: F,
+) -> Result<Handle, BindBroadcastError>
+where
+    MsgType: BroadcastMessage + Send + Sync + 'static,
+    Output: Future<
+            Output = Result<
+                <SendBroadcastMessage<MsgType> as RpcMessage>::Item,
+                <SendBroadcastMessage<MsgType> as RpcMessage>::Error,
+            >,
+        > + 'static,
+    F: FnMut(String, SendBroadcastMessage<MsgType>) -> Output + 'static,
+{
+    let topic = subscribe_msg.topic().to_owned();
+    let broadcast_address = subscribe_msg.endpoint().to_owned();
+    log::debug!("Creating broadcast topic {}.", topic);
 
     // We send Subscribe message to local net, which will create Topic
     // and add broadcast_address to be endpoint, which will be called, when someone
     // will broadcast any Message related to this Topic.
-    let subscribe_msg = MsgType::into_subscribe_msg(broadcast_address);
     bus::service(net::local::BUS_ID)
         .send(subscribe_msg)
         .await??;
@@ -37,12 +77,12 @@ where
     log::debug!(
         "Binding handler '{}' for broadcast topic {}.",
         broadcast_address,
-        MsgType::TOPIC
+        topic
     );
 
     // We created endpoint address above. Now we must add handler, which will
     // handle broadcasts forwarded to this address.
-    Ok(bus::bind_with_caller(broadcast_address, handler))
+    Ok(bus::bind_with_caller(&broadcast_address, handler))
 }
 
 #[cfg(any(feature = "service", test))]
@@ -1,9 +1,10 @@
 // Broadcast support service
 
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
 use ya_core_model::net::local as local_net;
+use ya_sb_util::topic;
 
 #[derive(Clone, Default)]
 pub struct BCastService {
@@ -31,16 +32,49 @@ impl BCastService {
         (is_new, id)
     }
 
+    /// Endpoints subscribed to `topic`, either directly or with a wildcard filter.
+    /// Endpoints matched by several filters are returned once.
     pub fn resolve(&self, topic: &str) -> Vec<Rc<str>> {
         let me = self.inner.borrow();
+        let mut seen = HashSet::new();
         me.topics
-            .get(topic)
-            .map(|receivers| {
-                receivers
-                    .iter()
-                    .map(|(_, endpoint)| endpoint.clone())
-                    .collect()
-            })
-            .unwrap_or_default()
+            .iter()
+            .filter(|(filter, _)| topic::matches(filter, topic))
+            .flat_map(|(_, receivers)| receivers.iter().map(|(_, endpoint)| endpoint.clone()))
+            .filter(|endpoint| seen.insert(endpoint.clone()))
+            .collect()
+    }
+
+    /// Topics and filters with at least one subscriber.
+    pub fn topics(&self) -> Vec<String> {
+        self.inner.borrow().topics.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_each_endpoint_once() {
+        let bcast = BCastService::default();
+        let subscriptions = [
+            ("market/offers", "/local/market/a"),
+            ("market/*", "/local/market/a"),
+            ("market/**", "/local/market/a"),
+            ("market/**", "/local/market/b"),
+            ("market/demands", "/local/market/c"),
+        ];
+        for (filter, endpoint) in subscriptions.iter() {
+            bcast.add(local_net::Subscribe::with_filter(*filter, *endpoint));
+        }
+
+        let mut endpoints: Vec<String> = bcast
+            .resolve("market/offers")
+            .into_iter()
+            .map(|endpoint| endpoint.to_string())
+            .collect();
+        endpoints.sort();
+        assert_eq!(endpoints, vec!["/local/market/a", "/local/market/b"]);
     }
 }
@@ -0,0 +1,25 @@
+use structopt::StructOpt;
+
+use ya_core_model::net::local as local_net;
+use ya_service_api::{CliCtx, CommandOutput};
+use ya_service_bus::{typed as bus, RpcEndpoint};
+
+/// Network management.
+#[derive(StructOpt, Debug)]
+pub enum NetCommand {
+    /// Shows connection state, bound identities, subscribed topics and message counters
+    Status,
+}
+
+impl NetCommand {
+    pub async fn run_command(self, _ctx: &CliCtx) -> anyhow::Result<CommandOutput> {
+        match self {
+            NetCommand::Status => CommandOutput::object(
+                bus::service(local_net::BUS_ID)
+                    .send(local_net::GetStatus::default())
+                    .await?
+                    .map_err(anyhow::Error::msg)?,
+            ),
+        }
+    }
+}
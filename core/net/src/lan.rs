@@ -0,0 +1,198 @@
+// Local network discovery
+//
+// Without a hub, nodes find each other by periodically announcing their node ids and
+// direct link port to a UDP multicast group. Peers heard from recently are reachable
+// over direct links, see `p2p::DirectLinks`.
+
+use actix_rt::Arbiter;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+use ya_client_model::NodeId;
+
+pub const LAN_MULTICAST_ENV_VAR: &str = "NET_LAN_MULTICAST";
+pub const DEFAULT_LAN_MULTICAST: &str = "239.255.74.64:7464";
+
+/// Service of other nodes receiving broadcasts, under `/net/<node>`.
+pub(crate) const BROADCAST_SERVICE: &str = "/net/Broadcast";
+
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5);
+/// Peers not heard from for this long are considered gone.
+const PEER_TTL: Duration = Duration::from_secs(30);
+const MAX_ANNOUNCEMENT_SIZE: usize = 64 * 1024;
+
+/// Multicast group from `NET_LAN_MULTICAST`.
+pub fn multicast_addr() -> std::io::Result<SocketAddrV4> {
+    let addr =
+        std::env::var(LAN_MULTICAST_ENV_VAR).unwrap_or_else(|_| DEFAULT_LAN_MULTICAST.to_string());
+    match addr.to_socket_addrs()?.next() {
+        Some(SocketAddr::V4(addr)) if addr.ip().is_multicast() => Ok(addr),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not an IPv4 multicast address", addr),
+        )),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Announcement {
+    /// Node ids served by the announcing daemon, the default one first.
+    nodes: Vec<NodeId>,
+    /// Direct link port, reached at the source address of the announcement.
+    port: u16,
+    /// Explicitly advertised endpoints, used instead of the source address if given.
+    #[serde(default)]
+    endpoints: Vec<String>,
+}
+
+struct Peer {
+    nodes: Vec<NodeId>,
+    endpoints: Vec<String>,
+    seen: Instant,
+}
+
+/// Daemons recently heard from on the local network.
+#[derive(Clone, Default)]
+pub(crate) struct Peers {
+    inner: Rc<RefCell<HashMap<NodeId, Peer>>>,
+}
+
+impl Peers {
+    fn update(&self, announcement: Announcement, src: IpAddr) {
+        let default_id = match announcement.nodes.first() {
+            Some(node) => *node,
+            None => return,
+        };
+        let endpoints = if announcement.endpoints.is_empty() {
+            vec![SocketAddr::new(src, announcement.port).to_string()]
+        } else {
+            announcement.endpoints
+        };
+        let peer = Peer {
+            nodes: announcement.nodes,
+            endpoints,
+            seen: Instant::now(),
+        };
+        if self.inner.borrow_mut().insert(default_id, peer).is_none() {
+            log::info!("Discovered node {} on the local network", default_id);
+        }
+    }
+
+    /// Direct link endpoints of the daemon serving `node`.
+    pub fn endpoints(&self, node: NodeId) -> Option<Vec<String>> {
+        self.inner
+            .borrow()
+            .values()
+            .find(|peer| peer.seen.elapsed() < PEER_TTL && peer.nodes.contains(&node))
+            .map(|peer| peer.endpoints.clone())
+    }
+
+    /// Default node id of every daemon still around.
+    pub fn nodes(&self) -> Vec<NodeId> {
+        let mut peers = self.inner.borrow_mut();
+        peers.retain(|_, peer| peer.seen.elapsed() < PEER_TTL);
+        peers.keys().cloned().collect()
+    }
+}
+
+fn multicast_socket(group: SocketAddrV4) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::ipv4(), Type::dgram(), Some(Protocol::udp()))?;
+    // other daemons on the same host listen on the same group
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SockAddr::from(SocketAddrV4::new(
+        Ipv4Addr::UNSPECIFIED,
+        group.port(),
+    )))?;
+    socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into_udp_socket())
+}
+
+/// Announces `nodes` with direct links on `port` and collects announcements of
+/// other daemons into `peers`.
+pub(crate) fn discover(
+    nodes: Vec<NodeId>,
+    port: u16,
+    endpoints: Vec<String>,
+    peers: Peers,
+) -> std::io::Result<()> {
+    let group = multicast_addr()?;
+    let socket = UdpSocket::from_std(multicast_socket(group)?)?;
+    let (mut rx, mut tx) = socket.split();
+    log::info!("Discovering nodes on the local network through {}", group);
+
+    let own_id = nodes.first().cloned();
+    let announcement = serde_json::to_vec(&Announcement {
+        nodes,
+        port,
+        endpoints,
+    })?;
+    let group = SocketAddr::V4(group);
+    Arbiter::spawn(async move {
+        let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = tx.send_to(&announcement, &group).await {
+                log::warn!("Failed to announce on the local network: {}", e);
+            }
+        }
+    });
+
+    Arbiter::spawn(async move {
+        let mut buf = vec![0u8; MAX_ANNOUNCEMENT_SIZE];
+        loop {
+            let (len, src) = match rx.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    log::warn!("Failed to receive local network announcement: {}", e);
+                    continue;
+                }
+            };
+            match serde_json::from_slice::<Announcement>(&buf[..len]) {
+                Ok(announcement) if announcement.nodes.first() == own_id.as_ref() => (),
+                Ok(announcement) => peers.update(announcement, src.ip()),
+                Err(e) => log::trace!("Invalid announcement from {}: {}", src, e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_resolve_any_announced_node() {
+        let a: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let b: NodeId = "0xcafe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let unknown: NodeId = "0xdead000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let peers = Peers::default();
+        peers.update(
+            Announcement {
+                nodes: vec![a, b],
+                port: 7465,
+                endpoints: vec![],
+            },
+            "192.168.1.7".parse().unwrap(),
+        );
+        assert_eq!(peers.endpoints(b), Some(vec!["192.168.1.7:7465".into()]));
+        assert_eq!(peers.nodes(), vec![a]);
+        assert_eq!(peers.endpoints(unknown), None);
+    }
+}
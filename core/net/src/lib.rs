@@ -1,8 +1,24 @@
 #[cfg(any(feature = "service", test))]
 mod bcast;
 #[cfg(any(feature = "service", test))]
+mod cli;
+#[cfg(any(feature = "service", test))]
+mod lan;
+#[cfg(any(feature = "service", test))]
+mod p2p;
+#[cfg(any(feature = "service", test))]
+mod rest;
+#[cfg(any(feature = "service", test))]
+mod secure;
+#[cfg(any(feature = "service", test))]
 mod service;
+#[cfg(any(feature = "service", test))]
+mod status;
 
+#[cfg(feature = "service")]
+pub use cli::NetCommand;
+#[cfg(feature = "service")]
+pub use rest::NET_API_PATH;
 #[cfg(feature = "service")]
 pub use service::*;
 
@@ -0,0 +1,291 @@
+// Direct connections between nodes
+//
+// Calls to `/net/<node>` go over a direct TCP link when the destination node advertises
+// an address reachable from here, and are relayed through the hub otherwise. Both ends of
+// a link speak the GSB protocol, each side serving calls with the same handler it uses
+// for calls forwarded by the hub. Nothing vouches for the `caller` a peer claims on a link
+// though, so only handshakes and sealed calls are served there, even with plaintext allowed.
+
+use actix_rt::Arbiter;
+use futures::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio_util::codec::Framed;
+
+use ya_client_model::NodeId;
+use ya_core_model::net::{self, public as public_net, RemoteEndpoint};
+use ya_sb_proto::codec::GsbMessageCodec;
+use ya_service_bus::connection::{self, CallRequestHandler, ConnectionRef, TcpTransport};
+use ya_service_bus::{typed as bus, Error, RpcEndpoint, RpcRawCall};
+
+use crate::lan;
+
+pub const P2P_LISTEN_ENV_VAR: &str = "NET_P2P_LISTEN";
+pub const P2P_PUBLIC_ADDR_ENV_VAR: &str = "NET_P2P_PUBLIC_ADDR";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long to keep relaying through the hub before trying a direct link again.
+const RETRY_DIRECT_AFTER: Duration = Duration::from_secs(300);
+
+/// Listen address from `NET_P2P_LISTEN`. Nodes without one only make outgoing links.
+pub fn listen_addr() -> std::io::Result<Option<SocketAddr>> {
+    match std::env::var(P2P_LISTEN_ENV_VAR) {
+        Ok(addr) => Ok(addr.to_socket_addrs()?.next()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Addresses advertised to other nodes: comma separated `NET_P2P_PUBLIC_ADDR`,
+/// or the listen address unless it is unspecified (e.g. `0.0.0.0`).
+pub(crate) fn advertised_endpoints(listen: SocketAddr) -> Vec<String> {
+    match std::env::var(P2P_PUBLIC_ADDR_ENV_VAR) {
+        Ok(addrs) => addrs
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) if listen.ip().is_unspecified() => Vec::new(),
+        Err(_) => vec![listen.to_string()],
+    }
+}
+
+enum Link<H: CallRequestHandler + 'static> {
+    Connecting,
+    Direct(ConnectionRef<TcpTransport, H>),
+    Relayed(Instant),
+}
+
+pub(crate) struct DirectLinks<H: CallRequestHandler + 'static> {
+    default_id: NodeId,
+    handler: H,
+    links: Rc<RefCell<HashMap<NodeId, Link<H>>>>,
+    inbound: Rc<RefCell<Vec<ConnectionRef<TcpTransport, H>>>>,
+    lan_peers: Option<lan::Peers>,
+}
+
+impl<H: CallRequestHandler + Clone + 'static> Clone for DirectLinks<H> {
+    fn clone(&self) -> Self {
+        DirectLinks {
+            default_id: self.default_id,
+            handler: self.handler.clone(),
+            links: self.links.clone(),
+            inbound: self.inbound.clone(),
+            lan_peers: self.lan_peers.clone(),
+        }
+    }
+}
+
+impl<H: CallRequestHandler + Clone + Unpin + 'static> DirectLinks<H> {
+    /// `handler` serves calls coming over direct links, `default_id` asks other nodes
+    /// for their endpoints.
+    pub fn new(default_id: NodeId, handler: H) -> Self {
+        DirectLinks {
+            default_id,
+            handler,
+            links: Default::default(),
+            inbound: Default::default(),
+            lan_peers: None,
+        }
+    }
+
+    /// Looks up endpoints among nodes discovered on the local network
+    /// instead of asking through the hub.
+    pub fn with_lan_peers(mut self, peers: lan::Peers) -> Self {
+        self.lan_peers = Some(peers);
+        self
+    }
+
+    /// Accepts direct links on `addr` and advertises them to other nodes.
+    /// Returns the address actually bound.
+    pub async fn listen(&self, addr: SocketAddr) -> std::io::Result<SocketAddr> {
+        let mut listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let endpoints = advertised_endpoints(local_addr);
+        log::info!(
+            "Accepting direct connections on {}, advertised as {:?}",
+            addr,
+            endpoints
+        );
+
+        let _ = bus::bind(
+            public_net::BUS_ID,
+            move |_: public_net::GetDirectEndpoints| future::ok(endpoints.clone()),
+        );
+
+        let me = self.clone();
+        Arbiter::spawn(async move {
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept direct connection: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("Direct connection from {:?}", stream.peer_addr());
+                let _ = stream.set_nodelay(true);
+                let transport = Framed::new(stream, GsbMessageCodec::default());
+                let link = connection::connect_with_handler(transport, me.handler.clone());
+                let mut inbound = me.inbound.borrow_mut();
+                inbound.retain(|link| link.connected());
+                inbound.push(link);
+            }
+        });
+        Ok(local_addr)
+    }
+
+    /// Calls `call.addr` under `/net/<node>` over a direct link to that node if there is one.
+    /// Otherwise, and when the link turns out to be broken, the call goes to `relay`.
+    pub fn call<F, Fut>(
+        &self,
+        call: RpcRawCall,
+        relay: F,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>>
+    where
+        F: FnOnce(RpcRawCall) -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, Error>>,
+    {
+        let link = dst_node(&call.addr).and_then(|node| self.link(node).map(|link| (node, link)));
+        let links = self.links.clone();
+        async move {
+            if let Some((node, link)) = link {
+                match link.raw_call(call.clone()).await {
+                    Err(Error::Closed) => {
+                        log::info!("Direct link to {} lost, relaying through hub", node);
+                        links
+                            .borrow_mut()
+                            .insert(node, Link::Relayed(Instant::now()));
+                    }
+                    result => return result,
+                }
+            }
+            relay(call).await
+        }
+    }
+
+    /// Calls `call.addr` under `/net/<node>` over a direct link only, waiting for the link
+    /// to be set up if there is none yet. Used when there is no hub to relay through.
+    pub fn call_direct(&self, call: RpcRawCall) -> impl Future<Output = Result<Vec<u8>, Error>> {
+        let me = self.clone();
+        async move {
+            let node = dst_node(&call.addr).ok_or_else(|| {
+                Error::GsbBadRequest(format!("not a node address: {}", call.addr))
+            })?;
+            let link = match me.connected(node) {
+                Some(link) => link,
+                None => {
+                    let transport = me.try_connect(node).await.map_err(|e| {
+                        Error::GsbFailure(format!("no direct link to {}: {}", node, e))
+                    })?;
+                    log::info!("Direct link to {} established", node);
+                    let link = connection::connect_with_handler(transport, me.handler.clone());
+                    me.links
+                        .borrow_mut()
+                        .insert(node, Link::Direct(link.clone()));
+                    link
+                }
+            };
+            link.raw_call(call).await
+        }
+    }
+
+    fn connected(&self, node: NodeId) -> Option<ConnectionRef<TcpTransport, H>> {
+        match self.links.borrow().get(&node) {
+            Some(Link::Direct(link)) if link.connected() => Some(link.clone()),
+            _ => None,
+        }
+    }
+
+    /// Link to `node` if it is up, starting to set one up in the background if due.
+    fn link(&self, node: NodeId) -> Option<ConnectionRef<TcpTransport, H>> {
+        let mut links = self.links.borrow_mut();
+        let due = match links.get(&node) {
+            Some(Link::Direct(link)) if link.connected() => return Some(link.clone()),
+            Some(Link::Connecting) => false,
+            Some(Link::Relayed(since)) => since.elapsed() >= RETRY_DIRECT_AFTER,
+            // never tried, or the peer closed the link
+            Some(Link::Direct(_)) | None => true,
+        };
+        if due {
+            links.insert(node, Link::Connecting);
+            Arbiter::spawn(self.clone().connect(node));
+        }
+        None
+    }
+
+    async fn connect(self, node: NodeId) {
+        let link = match self.try_connect(node).await {
+            Ok(transport) => {
+                log::info!("Direct link to {} established", node);
+                Link::Direct(connection::connect_with_handler(
+                    transport,
+                    self.handler.clone(),
+                ))
+            }
+            Err(e) => {
+                log::debug!("No direct link to {}, relaying through hub: {}", node, e);
+                Link::Relayed(Instant::now())
+            }
+        };
+        self.links.borrow_mut().insert(node, link);
+    }
+
+    async fn try_connect(&self, node: NodeId) -> anyhow::Result<TcpTransport> {
+        let endpoints = match &self.lan_peers {
+            Some(peers) => peers
+                .endpoints(node)
+                .ok_or_else(|| anyhow::anyhow!("node not seen on the local network"))?,
+            // asked through the hub, since there is no direct link yet
+            None => net::from(self.default_id)
+                .to(node)
+                .service(public_net::BUS_ID)
+                .send(public_net::GetDirectEndpoints::default())
+                .await?
+                .map_err(anyhow::Error::msg)?,
+        };
+
+        for endpoint in endpoints {
+            let addr = match endpoint.to_socket_addrs().ok().and_then(|mut a| a.next()) {
+                Some(addr) => addr,
+                None => continue,
+            };
+            match tokio::time::timeout(CONNECT_TIMEOUT, connection::tcp(addr)).await {
+                Ok(Ok(transport)) => return Ok(transport),
+                Ok(Err(e)) => log::debug!("Direct connection to {} failed: {}", addr, e),
+                Err(_) => log::debug!("Direct connection to {} timed out", addr),
+            }
+        }
+        anyhow::bail!("none of the advertised endpoints is reachable")
+    }
+}
+
+/// Destination node of a `/net/<node>/...` address.
+pub(crate) fn dst_node(addr: &str) -> Option<NodeId> {
+    addr.strip_prefix(net::BUS_ID)?
+        .split('/')
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dst_node_of_net_address() {
+        let node: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        assert_eq!(dst_node(&format!("/net/{}/gftp/x", node)), Some(node));
+        assert_eq!(dst_node(&format!("/net/{}", node)), Some(node));
+        assert_eq!(dst_node("/net/lato/gftp"), None);
+        assert_eq!(dst_node(&format!("/public/{}", node)), None);
+    }
+}
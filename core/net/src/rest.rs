@@ -0,0 +1,24 @@
+use actix_web::web::get;
+use actix_web::{HttpResponse, Scope};
+
+use ya_client_model::ErrorMessage;
+use ya_core_model::net::local as local_net;
+use ya_service_api_web::middleware::Identity;
+use ya_service_bus::{typed as bus, RpcEndpoint};
+
+pub const NET_API_PATH: &str = "/net-api/v1";
+
+pub fn web_scope() -> Scope {
+    Scope::new(NET_API_PATH).route("/status", get().to(get_status))
+}
+
+async fn get_status(_id: Identity) -> HttpResponse {
+    match bus::service(local_net::BUS_ID)
+        .send(local_net::GetStatus::default())
+        .await
+    {
+        Ok(Ok(status)) => HttpResponse::Ok().json(status),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(ErrorMessage::new(e)),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorMessage::new(e.to_string())),
+    }
+}
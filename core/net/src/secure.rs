@@ -0,0 +1,685 @@
+// End-to-end encryption of calls between nodes
+//
+// Before the first call from one node to another, the caller sends a `Handshake` with an
+// ephemeral X25519 key signed by its identity key, and the callee replies the same way.
+// Both sides derive a pair of ChaCha20-Poly1305 keys, one per direction. Calls then go to
+// `/net/<callee>/net/Sealed` carrying the encrypted destination address and payload, so the
+// hub and any relay only see which nodes talk to each other. The callee serves sealed calls
+// as coming from the node authenticated during the handshake, whatever `caller` claims.
+//
+// Broadcasts are one-to-many and stay in plaintext.
+
+use chacha20poly1305::aead::{generic_array::GenericArray, Aead, NewAead};
+use chacha20poly1305::ChaCha20Poly1305;
+use futures::future::{LocalBoxFuture, Shared};
+use futures::prelude::*;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+use ya_client_model::NodeId;
+use ya_core_model::identity;
+use ya_core_model::net::{self, public as public_net, RemoteEndpoint};
+use ya_service_bus::{typed as bus, untyped as local_bus, Error, RpcEndpoint, RpcRawCall};
+
+use crate::p2p::dst_node;
+
+pub const ALLOW_PLAINTEXT_ENV_VAR: &str = "NET_ALLOW_PLAINTEXT";
+
+/// Exported part of the address receiving sealed calls.
+const SEALED_SERVICE: &str = "/net/Sealed";
+/// Exported part of the `Handshake` service address, the only one reachable in plaintext.
+const HANDSHAKE_SERVICE: &str = "/net/Handshake";
+/// First byte of replies to sealed calls: an encrypted reply follows.
+const SEALED_REPLY: u8 = 0;
+/// First byte of replies to sealed calls: the callee has no such session, renew it.
+const UNKNOWN_SESSION_REPLY: u8 = 1;
+
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+const SESSION_TTL: Duration = Duration::from_secs(3600);
+/// Initiators renew a bit earlier, so that responders never drop a session still in use.
+const RENEW_AFTER: Duration = Duration::from_secs(3000);
+const REPLAY_WINDOW: u64 = 64;
+
+const SESSION_ID_LEN: usize = 16;
+const HEADER_LEN: usize = SESSION_ID_LEN + 8;
+
+type SessionId = [u8; SESSION_ID_LEN];
+type Key = [u8; 32];
+
+#[derive(Clone, Copy)]
+enum Role {
+    Initiator = 1,
+    Responder = 2,
+}
+
+/// Whether `NET_ALLOW_PLAINTEXT` lets this node exchange plaintext calls with nodes
+/// not supporting encryption.
+pub fn allow_plaintext() -> bool {
+    std::env::var(ALLOW_PLAINTEXT_ENV_VAR)
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+struct OutSession {
+    id: SessionId,
+    seal_key: Key,
+    open_key: Key,
+    counter: Cell<u64>,
+    created: Instant,
+}
+
+struct InSession {
+    initiator: NodeId,
+    responder: NodeId,
+    seal_key: Key,
+    open_key: Key,
+    window: ReplayWindow,
+    created: Instant,
+}
+
+/// Accepts each counter once, tolerating reordering of up to `REPLAY_WINDOW` calls.
+#[derive(Default)]
+struct ReplayWindow {
+    max: u64,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn accept(&mut self, counter: u64) -> bool {
+        if counter > self.max {
+            let shift = counter - self.max;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            } | 1;
+            self.max = counter;
+            return true;
+        }
+        let age = self.max - counter;
+        if age >= REPLAY_WINDOW || self.seen & (1 << age) != 0 {
+            return false;
+        }
+        self.seen |= 1 << age;
+        true
+    }
+}
+
+type PendingSession = Shared<LocalBoxFuture<'static, Result<Rc<OutSession>, String>>>;
+
+#[derive(Default)]
+struct Sessions {
+    outgoing: HashMap<(NodeId, NodeId), Rc<OutSession>>,
+    pending: HashMap<(NodeId, NodeId), PendingSession>,
+    incoming: HashMap<SessionId, InSession>,
+}
+
+#[derive(Clone)]
+pub(crate) struct SecureNet {
+    own_nodes: Rc<Vec<NodeId>>,
+    allow_plaintext: bool,
+    sessions: Rc<RefCell<Sessions>>,
+}
+
+impl SecureNet {
+    pub fn new(own_nodes: Vec<NodeId>, allow_plaintext: bool) -> Self {
+        SecureNet {
+            own_nodes: Rc::new(own_nodes),
+            allow_plaintext,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Shares the sessions of this one, but never accepts nor makes plaintext calls.
+    pub fn sealed_only(&self) -> Self {
+        SecureNet {
+            allow_plaintext: false,
+            ..self.clone()
+        }
+    }
+
+    /// Serves handshakes of nodes calling this one.
+    pub fn bind(&self) {
+        let me = self.clone();
+        let _ = bus::bind(
+            public_net::BUS_ID,
+            move |handshake: public_net::Handshake| {
+                let me = me.clone();
+                async move { me.accept_handshake(handshake).await }
+            },
+        );
+    }
+
+    /// Calls `call.addr` under `/net/<node>` as `call.caller`, encrypted with a session
+    /// between the two. `transport` delivers the call, and is called again once if the callee
+    /// lost the session. The payload codec stays in plaintext, for the callee to decode with.
+    pub fn call<F, Fut>(
+        &self,
+        call: RpcRawCall,
+        transport: F,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>>
+    where
+        F: Fn(RpcRawCall) -> Fut + 'static,
+        Fut: Future<Output = Result<Vec<u8>, Error>> + 'static,
+    {
+        let me = self.clone();
+        async move {
+            let dst = match dst_node(&call.addr) {
+                Some(dst) => dst,
+                None => return transport(call).await,
+            };
+            let service = &call.addr[net::net_service(&dst).len()..];
+            if service == HANDSHAKE_SERVICE {
+                return transport(call).await;
+            }
+            let src: NodeId = call
+                .caller
+                .parse()
+                .map_err(|_| Error::GsbBadRequest(format!("invalid caller: {}", call.caller)))?;
+            let sealed_addr = format!("{}{}", net::net_service(&dst), SEALED_SERVICE);
+
+            let mut retried = false;
+            loop {
+                let session = match me.session(src, dst).await {
+                    Ok(session) => session,
+                    // e.g. a node not supporting encryption yet
+                    Err(e) if me.allow_plaintext => {
+                        log::warn!("Calling {} in plaintext, e2e handshake failed: {}", dst, e);
+                        return transport(call).await;
+                    }
+                    Err(e) => {
+                        return Err(Error::GsbFailure(format!(
+                            "e2e handshake with {} failed: {}",
+                            dst, e
+                        )))
+                    }
+                };
+                let (counter, sealed) = session.seal(service, &call.body)?;
+                let reply = transport(RpcRawCall {
+                    caller: call.caller.clone(),
+                    addr: sealed_addr.clone(),
+                    body: sealed,
+                    deadline: call.deadline,
+                    codec: call.codec,
+                })
+                .await?;
+                match reply.split_first() {
+                    Some((&SEALED_REPLY, reply)) => return session.open_reply(counter, reply),
+                    Some((&UNKNOWN_SESSION_REPLY, _)) if !retried => {
+                        log::debug!("e2e session with {} expired on its side, renewing", dst);
+                        me.sessions.borrow_mut().outgoing.remove(&(src, dst));
+                        retried = true;
+                    }
+                    Some((&UNKNOWN_SESSION_REPLY, _)) => {
+                        return Err(Error::GsbFailure(format!(
+                            "{} does not accept renewed e2e session",
+                            dst
+                        )))
+                    }
+                    _ => return Err(Error::GsbBadRequest("malformed sealed reply".into())),
+                }
+            }
+        }
+    }
+
+    /// Handles `call` from another node to `/public` + `service` on `own_node`.
+    /// The call is served with the payload codec set by the caller.
+    pub fn serve(
+        &self,
+        own_node: NodeId,
+        service: &str,
+        call: RpcRawCall,
+    ) -> LocalBoxFuture<'static, Result<Vec<u8>, Error>> {
+        if service == SEALED_SERVICE {
+            return match self.open(own_node, &call.body) {
+                Ok(Some((initiator, local_addr, body, reply_seal))) => {
+                    log::debug!(
+                        "Incoming e2e call from {} to {}, claimed caller: {}",
+                        initiator,
+                        local_addr,
+                        call.caller
+                    );
+                    let call = RpcRawCall {
+                        caller: initiator.to_string(),
+                        addr: local_addr,
+                        body,
+                        ..call
+                    };
+                    async move {
+                        let reply = local_bus::send_raw(call).await?;
+                        reply_seal.seal(&reply)
+                    }
+                    .boxed_local()
+                }
+                Ok(None) => future::ok(vec![UNKNOWN_SESSION_REPLY]).boxed_local(),
+                Err(e) => future::err(e).boxed_local(),
+            };
+        }
+        if service != HANDSHAKE_SERVICE && !self.allow_plaintext {
+            return future::err(Error::GsbBadRequest(format!(
+                "plaintext call to {} from {} rejected, end-to-end encryption required",
+                service, call.caller
+            )))
+            .boxed_local();
+        }
+        let call = RpcRawCall {
+            addr: format!("{}{}", net::PUBLIC_PREFIX, service),
+            ..call
+        };
+        local_bus::send_raw(call).boxed_local()
+    }
+
+    fn session(
+        &self,
+        src: NodeId,
+        dst: NodeId,
+    ) -> impl Future<Output = Result<Rc<OutSession>, String>> {
+        let mut sessions = self.sessions.borrow_mut();
+        if let Some(session) = sessions.outgoing.get(&(src, dst)) {
+            if session.created.elapsed() < RENEW_AFTER {
+                return future::ok(session.clone()).left_future();
+            }
+        }
+        if let Some(pending) = sessions.pending.get(&(src, dst)) {
+            return pending.clone().right_future();
+        }
+
+        let me = self.clone();
+        let pending = async move {
+            let result = handshake(src, dst).await.map(Rc::new);
+            let mut sessions = me.sessions.borrow_mut();
+            sessions.pending.remove(&(src, dst));
+            match &result {
+                Ok(session) => {
+                    log::info!("e2e session with {} established", dst);
+                    sessions.outgoing.insert((src, dst), session.clone());
+                }
+                Err(e) => log::warn!("e2e handshake with {} failed: {}", dst, e),
+            }
+            result
+        }
+        .boxed_local()
+        .shared();
+        sessions.pending.insert((src, dst), pending.clone());
+        pending.right_future()
+    }
+
+    async fn accept_handshake(
+        &self,
+        handshake: public_net::Handshake,
+    ) -> Result<public_net::Handshake, String> {
+        let (initiator, responder) = (handshake.initiator, handshake.responder);
+        if !self.own_nodes.contains(&responder) {
+            return Err(format!("{} is not served here", responder));
+        }
+        check_timestamp(handshake.timestamp)?;
+        let initiator_key = public_key(&handshake.ephemeral_key)?;
+        let digest = handshake_digest(
+            Role::Initiator,
+            initiator,
+            responder,
+            initiator_key.as_bytes(),
+            &[],
+            handshake.timestamp,
+        );
+        verify(initiator, digest, handshake.signature).await?;
+
+        let secret = EphemeralSecret::new(rand::rngs::OsRng);
+        let responder_key = PublicKey::from(&secret);
+        let timestamp = now_secs();
+        let digest = handshake_digest(
+            Role::Responder,
+            initiator,
+            responder,
+            initiator_key.as_bytes(),
+            responder_key.as_bytes(),
+            timestamp,
+        );
+        let signature = sign(responder, digest).await?;
+
+        let shared = secret.diffie_hellman(&initiator_key);
+        let (id, i2r, r2i) = derive_keys(
+            shared.as_bytes(),
+            initiator_key.as_bytes(),
+            responder_key.as_bytes(),
+        );
+        {
+            let mut sessions = self.sessions.borrow_mut();
+            sessions
+                .incoming
+                .retain(|_, session| session.created.elapsed() < SESSION_TTL);
+            sessions.incoming.insert(
+                id,
+                InSession {
+                    initiator,
+                    responder,
+                    seal_key: r2i,
+                    open_key: i2r,
+                    window: Default::default(),
+                    created: Instant::now(),
+                },
+            );
+        }
+        log::debug!("Accepted e2e session from {} to {}", initiator, responder);
+
+        Ok(public_net::Handshake {
+            initiator,
+            responder,
+            ephemeral_key: responder_key.as_bytes().to_vec(),
+            timestamp,
+            signature,
+        })
+    }
+
+    /// Decrypts a sealed call to `own_node`, returning the authenticated caller,
+    /// the local destination, the payload and the key for the reply.
+    /// `None` means the session is unknown or expired.
+    fn open(
+        &self,
+        own_node: NodeId,
+        data: &[u8],
+    ) -> Result<Option<(NodeId, String, Vec<u8>, ReplySeal)>, Error> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::GsbBadRequest("malformed sealed call".into()));
+        }
+        let id: SessionId = data[..SESSION_ID_LEN].try_into().unwrap();
+        let counter = u64::from_be_bytes(data[SESSION_ID_LEN..HEADER_LEN].try_into().unwrap());
+
+        let mut sessions = self.sessions.borrow_mut();
+        let session = match sessions.incoming.get_mut(&id) {
+            Some(session) if session.created.elapsed() < SESSION_TTL => session,
+            _ => return Ok(None),
+        };
+        if session.responder != own_node {
+            return Err(Error::GsbBadRequest("sealed call for another node".into()));
+        }
+        let plain = decrypt(&session.open_key, counter, &data[HEADER_LEN..])?;
+        if !session.window.accept(counter) {
+            return Err(Error::GsbBadRequest("replayed sealed call".into()));
+        }
+        let (service, body) = decode_inner(&plain)?;
+        let local_addr = format!("{}{}", net::PUBLIC_PREFIX, service);
+        let reply_seal = ReplySeal {
+            key: session.seal_key,
+            counter,
+        };
+        Ok(Some((
+            session.initiator,
+            local_addr,
+            body.to_vec(),
+            reply_seal,
+        )))
+    }
+}
+
+impl OutSession {
+    fn seal(&self, service: &str, body: &[u8]) -> Result<(u64, Vec<u8>), Error> {
+        let counter = self.counter.get() + 1;
+        self.counter.set(counter);
+
+        let plain = encode_inner(service, body);
+        let mut sealed = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+        sealed.extend_from_slice(&self.id);
+        sealed.extend_from_slice(&counter.to_be_bytes());
+        sealed.extend(encrypt(&self.seal_key, counter, &plain)?);
+        Ok((counter, sealed))
+    }
+
+    fn open_reply(&self, counter: u64, reply: &[u8]) -> Result<Vec<u8>, Error> {
+        decrypt(&self.open_key, counter, reply)
+    }
+}
+
+struct ReplySeal {
+    key: Key,
+    counter: u64,
+}
+
+impl ReplySeal {
+    fn seal(&self, reply: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut sealed = vec![SEALED_REPLY];
+        sealed.extend(encrypt(&self.key, self.counter, reply)?);
+        Ok(sealed)
+    }
+}
+
+async fn handshake(src: NodeId, dst: NodeId) -> Result<OutSession, String> {
+    let secret = EphemeralSecret::new(rand::rngs::OsRng);
+    let initiator_key = PublicKey::from(&secret);
+    let timestamp = now_secs();
+    let digest = handshake_digest(
+        Role::Initiator,
+        src,
+        dst,
+        initiator_key.as_bytes(),
+        &[],
+        timestamp,
+    );
+    let signature = sign(src, digest).await?;
+
+    let reply = net::from(src)
+        .to(dst)
+        .service(public_net::BUS_ID)
+        .send(public_net::Handshake {
+            initiator: src,
+            responder: dst,
+            ephemeral_key: initiator_key.as_bytes().to_vec(),
+            timestamp,
+            signature,
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+    if reply.initiator != src || reply.responder != dst {
+        return Err("handshake reply for another session".into());
+    }
+    check_timestamp(reply.timestamp)?;
+    let responder_key = public_key(&reply.ephemeral_key)?;
+    let digest = handshake_digest(
+        Role::Responder,
+        src,
+        dst,
+        initiator_key.as_bytes(),
+        responder_key.as_bytes(),
+        reply.timestamp,
+    );
+    verify(dst, digest, reply.signature).await?;
+
+    let shared = secret.diffie_hellman(&responder_key);
+    let (id, i2r, r2i) = derive_keys(
+        shared.as_bytes(),
+        initiator_key.as_bytes(),
+        responder_key.as_bytes(),
+    );
+    Ok(OutSession {
+        id,
+        seal_key: i2r,
+        open_key: r2i,
+        counter: Cell::new(0),
+        created: Instant::now(),
+    })
+}
+
+fn handshake_digest(
+    role: Role,
+    initiator: NodeId,
+    responder: NodeId,
+    initiator_key: &[u8],
+    responder_key: &[u8],
+    timestamp: i64,
+) -> Vec<u8> {
+    Sha256::new()
+        .chain(b"yagna-net-handshake")
+        .chain(&[role as u8])
+        .chain(initiator.to_string().as_bytes())
+        .chain(responder.to_string().as_bytes())
+        .chain(initiator_key)
+        .chain(responder_key)
+        .chain(&timestamp.to_be_bytes())
+        .finalize()
+        .to_vec()
+}
+
+/// Session id and the initiator-to-responder and responder-to-initiator keys.
+fn derive_keys(shared: &[u8], initiator_key: &[u8], responder_key: &[u8]) -> (SessionId, Key, Key) {
+    let derive = |label: &[u8]| -> [u8; 32] {
+        let digest = Sha256::new()
+            .chain(label)
+            .chain(shared)
+            .chain(initiator_key)
+            .chain(responder_key)
+            .finalize();
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&digest);
+        key
+    };
+    let mut id = SessionId::default();
+    id.copy_from_slice(&derive(b"id")[..SESSION_ID_LEN]);
+    (id, derive(b"i2r"), derive(b"r2i"))
+}
+
+async fn sign(node_id: NodeId, digest: Vec<u8>) -> Result<Vec<u8>, String> {
+    bus::service(identity::BUS_ID)
+        .send(identity::Sign {
+            node_id,
+            payload: digest,
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Checks that `signature` of `digest` was made with the identity key of `node_id`.
+async fn verify(node_id: NodeId, digest: Vec<u8>, signature: Vec<u8>) -> Result<(), String> {
+    let valid = bus::service(identity::BUS_ID)
+        .send(identity::Verify {
+            node_id,
+            data: digest,
+            signature,
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    if !valid {
+        return Err(format!("handshake not signed by {}", node_id));
+    }
+    Ok(())
+}
+
+fn public_key(bytes: &[u8]) -> Result<PublicKey, String> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "malformed ephemeral key".to_string())?;
+    Ok(PublicKey::from(bytes))
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn check_timestamp(timestamp: i64) -> Result<(), String> {
+    if (now_secs() - timestamp).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err("handshake timestamp out of range".into());
+    }
+    Ok(())
+}
+
+fn nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+fn encrypt(key: &Key, counter: u64, plain: &[u8]) -> Result<Vec<u8>, Error> {
+    ChaCha20Poly1305::new(GenericArray::from_slice(key))
+        .encrypt(GenericArray::from_slice(&nonce(counter)), plain)
+        .map_err(|_| Error::EncodingProblem("e2e encryption failed".into()))
+}
+
+fn decrypt(key: &Key, counter: u64, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    ChaCha20Poly1305::new(GenericArray::from_slice(key))
+        .decrypt(GenericArray::from_slice(&nonce(counter)), sealed)
+        .map_err(|_| Error::GsbBadRequest("e2e decryption failed".into()))
+}
+
+fn encode_inner(service: &str, body: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::with_capacity(2 + service.len() + body.len());
+    plain.extend_from_slice(&(service.len() as u16).to_be_bytes());
+    plain.extend_from_slice(service.as_bytes());
+    plain.extend_from_slice(body);
+    plain
+}
+
+fn decode_inner(plain: &[u8]) -> Result<(&str, &[u8]), Error> {
+    let malformed = || Error::GsbBadRequest("malformed sealed call".into());
+    if plain.len() < 2 {
+        return Err(malformed());
+    }
+    let len = u16::from_be_bytes([plain[0], plain[1]]) as usize;
+    let service = plain
+        .get(2..2 + len)
+        .and_then(|s| std::str::from_utf8(s).ok())
+        .filter(|s| s.starts_with('/'))
+        .ok_or_else(malformed)?;
+    Ok((service, &plain[2 + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_window_accepts_each_counter_once() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(1));
+        assert!(window.accept(3));
+        assert!(window.accept(2));
+        assert!(!window.accept(2));
+        assert!(!window.accept(3));
+        assert!(window.accept(100));
+        assert!(!window.accept(30));
+        assert!(window.accept(99));
+    }
+
+    #[test]
+    fn sealed_call_round_trip() {
+        let key = [7u8; 32];
+        let plain = encode_inner("/gftp/x", b"chunk");
+        let sealed = encrypt(&key, 5, &plain).unwrap();
+        assert!(decrypt(&key, 6, &sealed).is_err());
+        let opened = decrypt(&key, 5, &sealed).unwrap();
+        let (service, body) = decode_inner(&opened).unwrap();
+        assert_eq!(service, "/gftp/x");
+        assert_eq!(body, b"chunk");
+    }
+
+    #[test]
+    fn unknown_session_is_reported_apart_from_errors() {
+        let node: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let net = SecureNet::new(vec![node], false);
+        assert!(net.open(node, &[0u8; HEADER_LEN + 16]).unwrap().is_none());
+        assert!(net.open(node, &[0u8; HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn sealed_reply_is_tagged() {
+        let reply_seal = ReplySeal {
+            key: [7u8; 32],
+            counter: 5,
+        };
+        let sealed = reply_seal.seal(b"reply").unwrap();
+        let (tag, reply) = sealed.split_first().unwrap();
+        assert_eq!(*tag, SEALED_REPLY);
+        assert_eq!(decrypt(&[7u8; 32], 5, reply).unwrap(), b"reply");
+    }
+}
@@ -1,118 +1,272 @@
 use actix_rt::Arbiter;
 use anyhow::{anyhow, Context};
+use futures::future::LocalBoxFuture;
 use futures::prelude::*;
+use futures::stream::LocalBoxStream;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
 
 use ya_client_model::NodeId;
 use ya_core_model::identity::{self, IdentityInfo};
-use ya_core_model::net::{self, local as local_net, local::SendBroadcastMessage};
+use ya_core_model::net::{self, local as local_net, local::SendBroadcastMessage, PUBLIC_PREFIX};
+use ya_service_api_interfaces::Service;
 use ya_service_bus::{
-    connection, typed as bus, untyped as local_bus, Error, ResponseChunk, RpcEndpoint, RpcMessage,
+    connection::{self, CallRequestHandler},
+    reconnect::{self, ConnectionEvent, ReconnectConfig},
+    typed as bus, untyped as local_bus, Error, ResponseChunk, RpcEndpoint, RpcMessage, RpcRawCall,
 };
 
 use crate::api::{net_service, parse_from_addr};
+use crate::bcast::BCastService;
+use crate::cli::NetCommand;
+use crate::status::NetStatus;
+use crate::{lan, p2p, rest, secure};
 
+pub use crate::lan::LAN_MULTICAST_ENV_VAR;
+pub use crate::secure::ALLOW_PLAINTEXT_ENV_VAR;
+
+pub const NET_MODE_ENV_VAR: &str = "NET_MODE";
 pub const CENTRAL_ADDR_ENV_VAR: &str = "CENTRAL_NET_HOST";
 pub const DEFAULT_CENTRAL_ADDR: &str = "3.249.139.167:7464";
+pub const RECONNECT_MIN_DELAY_ENV_VAR: &str = "NET_RECONNECT_MIN_DELAY";
+pub const RECONNECT_MAX_DELAY_ENV_VAR: &str = "NET_RECONNECT_MAX_DELAY";
+
+/// Hub addresses from `CENTRAL_NET_HOST`, which may list several federated hubs separated
+/// with commas. They are tried in order when connecting.
+pub fn central_net_addrs() -> std::io::Result<Vec<SocketAddr>> {
+    let hosts = std::env::var(CENTRAL_ADDR_ENV_VAR).unwrap_or(DEFAULT_CENTRAL_ADDR.into());
+    let mut addrs = Vec::new();
+    for host in hosts
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+    {
+        addrs.extend(host.to_socket_addrs()?.next());
+    }
+    if addrs.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "central net hub addr needed",
+        ));
+    }
+    Ok(addrs)
+}
 
 pub fn central_net_addr() -> std::io::Result<SocketAddr> {
-    Ok(std::env::var(CENTRAL_ADDR_ENV_VAR)
-        .unwrap_or(DEFAULT_CENTRAL_ADDR.into())
-        .to_socket_addrs()?
-        .next()
-        .expect("central net hub addr needed"))
+    Ok(central_net_addrs()?.remove(0))
 }
 
-/// Initialize net module on a hub.
-pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io::Result<()> {
-    let hub_addr = central_net_addr()?;
-    let conn = connection::tcp(hub_addr).await?;
-    let bcast = super::bcast::BCastService::default();
-    let bcast_service_id = <SendBroadcastMessage<serde_json::Value> as RpcMessage>::ID;
+/// Backoff between hub reconnection attempts, in seconds from
+/// `NET_RECONNECT_MIN_DELAY` and `NET_RECONNECT_MAX_DELAY`.
+fn reconnect_config() -> ReconnectConfig {
+    let env_secs = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+    };
+    let default = ReconnectConfig::default();
+    ReconnectConfig {
+        min_delay: env_secs(RECONNECT_MIN_DELAY_ENV_VAR).unwrap_or(default.min_delay),
+        max_delay: env_secs(RECONNECT_MAX_DELAY_ENV_VAR).unwrap_or(default.max_delay),
+    }
+}
 
-    // connect to hub with forwarding handler
-    let my_net_node_id = net_service(&default_node_id);
-    let own_net_nodes: Vec<_> = nodes.iter().map(|id| net_service(id)).collect();
+async fn connect_hub(status: NetStatus) -> std::io::Result<connection::TcpTransport> {
+    let mut last_err = None;
+    for hub_addr in central_net_addrs()? {
+        match connection::tcp(hub_addr).await {
+            Ok(conn) => {
+                log::info!("Connected to net hub {}", hub_addr);
+                status.set_hub_addr(hub_addr.to_string());
+                return Ok(conn);
+            }
+            Err(e) => {
+                log::warn!("Failed to connect to net hub {}: {}", hub_addr, e);
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.unwrap())
+}
 
-    let forward_call = move |request_id: String, caller: String, addr: String, data: Vec<u8>| {
-        let prefix = own_net_nodes
+/// Delivers calls over a direct link to the destination node if there is one,
+/// through the hub otherwise.
+fn transport<H1, H2>(
+    direct: &p2p::DirectLinks<H1>,
+    central_bus: &reconnect::ReconnectingConnection<connection::TcpTransport, H2>,
+) -> impl Fn(RpcRawCall) -> LocalBoxFuture<'static, Result<Vec<u8>, Error>> + Clone
+where
+    H1: CallRequestHandler + Clone + Unpin + 'static,
+    H2: CallRequestHandler + Clone + Unpin + 'static,
+{
+    let direct = direct.clone();
+    let central_bus = central_bus.clone();
+    move |call| {
+        let central_bus = central_bus.clone();
+        direct
+            .call(call, move |call| central_bus.raw_call(call))
+            .boxed_local()
+    }
+}
+
+type ForwardReply = LocalBoxStream<'static, Result<ResponseChunk, Error>>;
+
+/// Serves calls from other nodes to `/net/<own node>/...` with the local bus.
+#[derive(Clone)]
+struct ForwardHandler {
+    own_net_nodes: Rc<Vec<(String, NodeId)>>,
+    secure: secure::SecureNet,
+    status: NetStatus,
+}
+
+fn forward_handler(
+    nodes: &[NodeId],
+    secure: &secure::SecureNet,
+    status: &NetStatus,
+) -> ForwardHandler {
+    ForwardHandler {
+        own_net_nodes: Rc::new(nodes.iter().map(|id| (net_service(id), *id)).collect()),
+        secure: secure.clone(),
+        status: status.clone(),
+    }
+}
+
+impl CallRequestHandler for ForwardHandler {
+    type Reply = ForwardReply;
+
+    fn do_call(
+        &mut self,
+        request_id: String,
+        caller: String,
+        address: String,
+        data: Vec<u8>,
+    ) -> Self::Reply {
+        self.do_raw_call(
+            request_id,
+            RpcRawCall {
+                caller,
+                addr: address,
+                body: data,
+                deadline: None,
+                codec: None,
+            },
+        )
+    }
+
+    fn do_raw_call(&mut self, request_id: String, call: RpcRawCall) -> Self::Reply {
+        let own = self
+            .own_net_nodes
             .iter()
-            .find(|&own_net_node_id| addr.starts_with(own_net_node_id));
-        if let Some(prefix) = prefix {
-            // replaces  /net/<dest_node_id>/test/1 --> /public/test/1
-            let local_addr: String = addr.replacen(prefix, net::PUBLIC_PREFIX, 1);
+            .find(|(own_net_node_id, _)| call.addr.starts_with(own_net_node_id.as_str()));
+        if let Some((prefix, node_id)) = own {
+            // /net/<dest_node_id>/test/1 is served by /public/test/1
+            let service = call.addr[prefix.len()..].to_string();
             log::debug!(
-                "Incoming msg from = {}, to = {}, fwd to local addr = {}, request_id: {}",
-                caller,
-                addr,
-                local_addr,
+                "Incoming msg from = {}, to = {}, request_id: {}",
+                call.caller,
+                call.addr,
                 request_id
             );
-            // actual forwarding to my local bus
+            // actual forwarding to my local bus, after checking who is calling
+            let status = self.status.clone();
             stream::once(
-                local_bus::send(&local_addr, &caller, &data)
-                    .and_then(|r| future::ok(ResponseChunk::Full(r))),
+                self.secure
+                    .serve(*node_id, &service, call)
+                    .inspect(move |result| status.call_received(result))
+                    .map_ok(ResponseChunk::Full),
             )
-            .right_stream()
+            .boxed_local()
         } else {
-            return stream::once(future::err(Error::GsbBadRequest(format!(
+            stream::once(future::err(Error::GsbBadRequest(format!(
                 "wrong routing: {}; I'll accept only addrs starting with: {:?}",
-                addr, own_net_nodes
+                call.addr,
+                self.own_net_nodes
+                    .iter()
+                    .map(|(prefix, _)| prefix)
+                    .collect::<Vec<_>>()
             ))))
-            .left_stream();
+            .boxed_local()
         }
-    };
-
-    let broadcast_handler = {
-        let bcast = bcast.clone();
+    }
+}
 
-        move |caller: String, topic: String, msg: Vec<u8>| {
-            let endpoints = bcast.resolve(&topic);
-            let msg: Rc<[u8]> = msg.into();
-            Arbiter::spawn(async move {
-                log::trace!("Received broadcast to topic {} from [{}].", &topic, &caller);
-                for endpoint in endpoints {
-                    let addr = format!("{}/{}", endpoint, bcast_service_id);
-                    let _ = local_bus::send(addr.as_ref(), &caller, msg.as_ref()).await;
-                }
-            })
+/// Delivers a broadcast to `topic` received from `caller` to local subscribers.
+fn deliver_broadcast(
+    bcast: &BCastService,
+    status: &NetStatus,
+    caller: String,
+    topic: String,
+    msg: Vec<u8>,
+) {
+    status.broadcast_received();
+    let bcast_service_id = <SendBroadcastMessage<serde_json::Value> as RpcMessage>::ID;
+    let endpoints = bcast.resolve(&topic);
+    let msg: Rc<[u8]> = msg.into();
+    Arbiter::spawn(async move {
+        log::trace!("Received broadcast to topic {} from [{}].", &topic, &caller);
+        for endpoint in endpoints {
+            let addr = format!("{}/{}", endpoint, bcast_service_id);
+            let _ = local_bus::send(addr.as_ref(), &caller, msg.as_ref()).await;
         }
-    };
-
-    let central_bus = connection::connect_with_handler(conn, (forward_call, broadcast_handler));
+    })
+}
 
-    // bind my local net service(s) on remote centralised bus under /net/<my_identity>
-    for node in &nodes {
-        let addr = net_service(node);
-        central_bus
-            .bind(addr.clone())
-            .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))?;
-        log::info!("network service bound at: {} under: {}", hub_addr, addr);
-    }
+/// Binds `/net`, `/from` and broadcasts on my local bus. Calls to other nodes are
+/// delivered by `transport`, broadcasts are sent with `broadcast` and topics
+/// subscribed for the first time are passed to `subscribe_topic`.
+fn bind_local_bus<T, B, BFut, S, SFut>(
+    default_node_id: NodeId,
+    nodes: Vec<NodeId>,
+    secure: &secure::SecureNet,
+    bcast: &BCastService,
+    status: &NetStatus,
+    transport: T,
+    broadcast: B,
+    subscribe_topic: S,
+) where
+    T: Fn(RpcRawCall) -> LocalBoxFuture<'static, Result<Vec<u8>, Error>> + Clone + 'static,
+    B: Fn(String, String, Vec<u8>) -> BFut + 'static,
+    BFut: Future<Output = Result<(), Error>> + 'static,
+    S: Fn(String) -> SFut + 'static,
+    SFut: Future<Output = Result<(), Error>> + 'static,
+{
+    status.bind(bcast);
 
-    // bind /net on my local bus and forward all calls to remote bus under /net
+    // bind /net on my local bus and forward all calls to remote nodes under /net
     {
-        let central_bus = central_bus.clone();
+        let secure = secure.clone();
+        let status = status.clone();
+        let transport = transport.clone();
+        let my_net_node_id = net_service(&default_node_id);
         let default_caller = default_node_id.to_string();
-        local_bus::subscribe(net::BUS_ID, move |_caller: &str, addr: &str, msg: &[u8]| {
+        local_bus::subscribe_raw(net::BUS_ID, move |call: RpcRawCall| {
             log::debug!(
-                "Sending message to hub. Called by: {}, addr: {}.",
-                my_net_node_id,
-                addr
+                "Sending message to {}. Called by: {}.",
+                call.addr,
+                my_net_node_id
             );
-            // `_caller` here is usually "local", so we replace it with our default node id
-            central_bus.call(default_caller.clone(), addr.to_string(), Vec::from(msg))
+            // caller here is usually "local", so we replace it with our default node id
+            let call = RpcRawCall {
+                caller: default_caller.clone(),
+                ..call
+            };
+            let status = status.clone();
+            secure
+                .call(call, transport.clone())
+                .inspect(move |result| status.call_sent(result))
         });
     }
 
-    // bind /from/<caller>/to/<addr> on my local bus and forward all calls to remote bus under /net
+    // bind /from/<caller>/to/<addr> on my local bus and forward all calls to remote nodes under /net
     {
-        let central_bus = central_bus.clone();
+        let secure = secure.clone();
+        let status = status.clone();
 
-        local_bus::subscribe("/from", move |_caller: &str, addr: &str, msg: &[u8]| {
-            let (from_node, to_addr) = match parse_from_addr(addr) {
+        local_bus::subscribe_raw("/from", move |call: RpcRawCall| {
+            let (from_node, to_addr) = match parse_from_addr(&call.addr) {
                 Ok(v) => v,
                 Err(e) => return future::err(Error::GsbBadRequest(e.to_string())).left_future(),
             };
@@ -125,24 +279,31 @@ pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io
                 .left_future();
             }
 
-            central_bus
-                .call(from_node.to_string(), to_addr, Vec::from(msg))
+            let call = RpcRawCall {
+                caller: from_node.to_string(),
+                addr: to_addr,
+                ..call
+            };
+            let status = status.clone();
+            secure
+                .call(call, transport.clone())
+                .inspect(move |result| status.call_sent(result))
                 .right_future()
         });
     }
 
     {
         let bcast = bcast.clone();
-        let central_bus = central_bus.clone();
+        let subscribe_topic = Rc::new(subscribe_topic);
 
         let _ = bus::bind(local_net::BUS_ID, move |subscribe: local_net::Subscribe| {
             let topic = subscribe.topic().to_owned();
             let (is_new, id) = bcast.add(subscribe);
-            let central_bus = central_bus.clone();
+            let subscribe_topic = subscribe_topic.clone();
             async move {
-                log::debug!("Subscribe topic {} on central bus.", topic);
+                log::debug!("Subscribe topic {}.", topic);
                 if is_new {
-                    if let Err(e) = central_bus.subscribe(topic.clone()).await {
+                    if let Err(e) = subscribe_topic(topic.clone()).await {
                         log::error!("fail to subscribe to: {}, {}", topic, e);
                     }
                     log::debug!("Created new topic: {}", topic);
@@ -153,9 +314,10 @@ pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io
     }
 
     {
-        let central_bus = central_bus.clone();
+        let bcast_service_id = <SendBroadcastMessage<serde_json::Value> as RpcMessage>::ID;
         let addr = format!("{}/{}", local_net::BUS_ID, bcast_service_id);
         let resp: Rc<[u8]> = serde_json::to_vec(&Ok::<(), ()>(())).unwrap().into();
+        let status = status.clone();
         let _ = local_bus::subscribe(&addr, move |caller: &str, _addr: &str, msg: &[u8]| {
             // TODO: remove unwrap here.
             let ent: SendBroadcastMessage<serde_json::Value> = serde_json::from_slice(msg).unwrap();
@@ -166,7 +328,8 @@ pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io
                 &caller
             );
 
-            let fut = central_bus.broadcast(caller.to_owned(), ent.topic().to_owned(), msg.into());
+            status.broadcast_sent();
+            let fut = broadcast(caller.to_owned(), ent.topic().to_owned(), msg.into());
             let resp = resp.clone();
             async move {
                 if let Err(e) = fut.await {
@@ -177,12 +340,244 @@ pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io
             }
         });
     }
+}
+
+/// Initialize net module on a hub.
+pub async fn bind_remote(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io::Result<()> {
+    let bcast = BCastService::default();
+    let status = NetStatus::new("hub", nodes.clone());
+
+    let secure = secure::SecureNet::new(nodes.clone(), secure::allow_plaintext());
+    secure.bind();
+
+    // connect to hub with forwarding handler
+    let forward_call = forward_handler(&nodes, &secure, &status);
+    let broadcast_handler = {
+        let bcast = bcast.clone();
+        let status = status.clone();
+        move |caller: String, topic: String, msg: Vec<u8>| {
+            deliver_broadcast(&bcast, &status, caller, topic, msg)
+        }
+    };
+
+    // calls from other nodes are served the same way, whether relayed by the hub or not,
+    // except that direct links carry no plaintext calls
+    let direct = p2p::DirectLinks::new(
+        default_node_id,
+        forward_handler(&nodes, &secure.sealed_only(), &status),
+    );
+    if let Some(listen_addr) = p2p::listen_addr()? {
+        direct.listen(listen_addr).await?;
+    }
+
+    let connect = {
+        let status = status.clone();
+        move || connect_hub(status.clone())
+    };
+    let central_bus = reconnect::connect_with_handler(
+        reconnect_config(),
+        connect,
+        (forward_call, broadcast_handler),
+    )
+    .await?;
+    status.set_connected(true);
+
+    // bindings and topic subscriptions are replayed by `central_bus` itself
+    let events_status = status.clone();
+    Arbiter::spawn(central_bus.events().for_each(move |event| {
+        let status = events_status.clone();
+        async move {
+            let event = match event {
+                ConnectionEvent::Disconnected => {
+                    status.set_connected(false);
+                    local_net::Event::HubDisconnected
+                }
+                ConnectionEvent::Reconnected { attempts } => {
+                    status.set_connected(true);
+                    local_net::Event::HubReconnected { attempts }
+                }
+                ConnectionEvent::ReconnectFailed { attempts, retry_in } => {
+                    log::warn!(
+                        "Net hub reconnect attempt {} failed, retrying in {:?}",
+                        attempts,
+                        retry_in
+                    );
+                    return;
+                }
+            };
+            if let Err(e) = bus::publish(event).await {
+                log::error!("Failed to publish net event: {}", e);
+            }
+        }
+    }));
+
+    // bind my local net service(s) on remote centralised bus under /net/<my_identity>
+    for node in &nodes {
+        let addr = net_service(node);
+        central_bus
+            .bind(addr.clone())
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))?;
+        log::info!("network service bound on hub under: {}", addr);
+    }
+
+    let transport = transport(&direct, &central_bus);
+    let broadcast = {
+        let central_bus = central_bus.clone();
+        move |caller: String, topic: String, msg: Vec<u8>| central_bus.broadcast(caller, topic, msg)
+    };
+    let subscribe_topic = move |topic: String| central_bus.subscribe(topic);
+    bind_local_bus(
+        default_node_id,
+        nodes,
+        &secure,
+        &bcast,
+        &status,
+        transport,
+        broadcast,
+        subscribe_topic,
+    );
+
+    Ok(())
+}
+
+/// Initialize net module on the local network, without a hub.
+///
+/// Nodes are discovered through multicast announcements and all calls go over direct links.
+/// Broadcasts are sent to every node discovered, each filtering them by its own subscriptions.
+pub async fn bind_lan(default_node_id: NodeId, nodes: Vec<NodeId>) -> std::io::Result<()> {
+    let bcast = BCastService::default();
+    let status = NetStatus::new("lan", nodes.clone());
+
+    // every call goes over a direct link, where plaintext is never accepted
+    if secure::allow_plaintext() {
+        log::warn!("Plaintext calls are not supported on the local network");
+    }
+    let secure = secure::SecureNet::new(nodes.clone(), false);
+    secure.bind();
+
+    let peers = lan::Peers::default();
+    let forward_call = forward_handler(&nodes, &secure, &status);
+    let direct = p2p::DirectLinks::new(default_node_id, forward_call).with_lan_peers(peers.clone());
+    let listen_addr = p2p::listen_addr()?.unwrap_or_else(|| ([0, 0, 0, 0], 0).into());
+    let local_addr = direct.listen(listen_addr).await?;
+
+    let announced = std::iter::once(default_node_id)
+        .chain(nodes.iter().cloned().filter(|id| *id != default_node_id))
+        .collect();
+    lan::discover(
+        announced,
+        local_addr.port(),
+        p2p::advertised_endpoints(local_addr),
+        peers.clone(),
+    )?;
+    status.set_connected(true);
+
+    // broadcasts from other nodes, served under /net/<my_identity> like any other call
+    {
+        let bcast = bcast.clone();
+        let status = status.clone();
+        let resp: Rc<[u8]> = serde_json::to_vec(&Ok::<(), ()>(())).unwrap().into();
+        let _ = local_bus::subscribe(
+            &format!("{}{}", PUBLIC_PREFIX, lan::BROADCAST_SERVICE),
+            move |caller: &str, _addr: &str, msg: &[u8]| match serde_json::from_slice::<
+                SendBroadcastMessage<serde_json::Value>,
+            >(msg)
+            {
+                Ok(ent) => {
+                    let topic = ent.topic().to_owned();
+                    deliver_broadcast(&bcast, &status, caller.to_owned(), topic, msg.into());
+                    future::ok(Vec::from(resp.as_ref()))
+                }
+                Err(e) => future::err(Error::GsbBadRequest(format!("invalid broadcast: {}", e))),
+            },
+        );
+    }
+
+    let transport = move |call| direct.call_direct(call).boxed_local();
+    let broadcast = {
+        let secure = secure.clone();
+        let transport = transport.clone();
+        let nodes = nodes.clone();
+        move |caller: String, _topic: String, msg: Vec<u8>| {
+            // sent in the name of the local caller if it is one of my identities
+            let caller = match caller.parse::<NodeId>() {
+                Ok(node) if nodes.contains(&node) => caller,
+                _ => default_node_id.to_string(),
+            };
+            let sends: Vec<_> = peers
+                .nodes()
+                .into_iter()
+                .map(|peer| {
+                    let addr = format!("{}{}", net_service(&peer), lan::BROADCAST_SERVICE);
+                    let call = RpcRawCall {
+                        caller: caller.clone(),
+                        addr,
+                        body: msg.clone(),
+                        deadline: None,
+                        codec: None,
+                    };
+                    secure.call(call, transport.clone()).map(move |result| {
+                        if let Err(e) = result {
+                            log::debug!("Failed to send broadcast to {}: {}", peer, e);
+                        }
+                    })
+                })
+                .collect();
+            Arbiter::spawn(future::join_all(sends).map(|_| ()));
+            future::ok(())
+        }
+    };
+    bind_local_bus(
+        default_node_id,
+        nodes,
+        &secure,
+        &bcast,
+        &status,
+        transport,
+        broadcast,
+        |_| future::ok(()),
+    );
 
     Ok(())
 }
 
+/// How this node reaches other nodes, chosen with `NET_MODE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetMode {
+    /// Through a central hub, see `CENTRAL_NET_HOST`.
+    Hub,
+    /// Directly, with nodes discovered on the local network.
+    Lan,
+}
+
+impl NetMode {
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var(NET_MODE_ENV_VAR) {
+            Ok(mode) => mode.parse(),
+            Err(_) => Ok(NetMode::Hub),
+        }
+    }
+}
+
+impl FromStr for NetMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hub" => Ok(NetMode::Hub),
+            "lan" => Ok(NetMode::Lan),
+            other => Err(anyhow!("invalid net mode: {}, expected hub or lan", other)),
+        }
+    }
+}
+
 pub struct Net;
 
+impl Service for Net {
+    type Cli = NetCommand;
+}
+
 impl Net {
     pub async fn gsb<Context>(_: Context) -> anyhow::Result<()> {
         let ids: Vec<IdentityInfo> = bus::service(identity::BUS_ID)
@@ -198,8 +593,86 @@ impl Net {
         log::info!("using default identity as network id: {:?}", default_id);
         let ids = ids.into_iter().map(|id| id.node_id).collect();
 
-        bind_remote(default_id, ids)
-            .await
-            .context(format!("Error binding network service"))
+        match NetMode::from_env()? {
+            NetMode::Hub => bind_remote(default_id, ids).await,
+            NetMode::Lan => bind_lan(default_id, ids).await,
+        }
+        .context(format!("Error binding network service"))
+    }
+
+    pub fn rest<Context>(_: &Context) -> actix_web::Scope {
+        rest::web_scope()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use ya_service_bus::Codec;
+
+    #[derive(Serialize, Deserialize)]
+    struct Echo(String);
+
+    impl RpcMessage for Echo {
+        const ID: &'static str = "Echo";
+        type Item = String;
+        type Error = ();
+    }
+
+    #[actix_rt::test]
+    async fn forwarded_calls_keep_their_codec() {
+        let node: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let caller: NodeId = "0xcafe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let _ = bus::bind("/public/test", |echo: Echo| future::ok::<_, ()>(echo.0));
+        let secure = secure::SecureNet::new(vec![node], true);
+        let mut handler = forward_handler(&[node], &secure, &NetStatus::new("hub", vec![node]));
+
+        for codec in vec![Codec::Json, Codec::Msgpack] {
+            let call = RpcRawCall {
+                caller: caller.to_string(),
+                addr: format!("{}/test/Echo", net_service(&node)),
+                body: codec.to_vec(&Echo("hello".into())).unwrap(),
+                deadline: None,
+                codec: Some(codec),
+            };
+            let reply = match handler.do_raw_call("1".into(), call).next().await {
+                Some(Ok(ResponseChunk::Full(reply))) => reply,
+                other => panic!("unexpected reply: {:?}", other.map(|r| r.is_ok())),
+            };
+            let reply: Result<String, ()> = codec.from_read(reply.as_slice()).unwrap();
+            assert_eq!(reply, Ok("hello".to_string()), "{:?}", codec);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn direct_links_reject_plaintext_calls() {
+        let node: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let _ = bus::bind("/public/test", |echo: Echo| future::ok::<_, ()>(echo.0));
+        let secure = secure::SecureNet::new(vec![node], true);
+        let status = NetStatus::new("hub", vec![node]);
+        let call = || RpcRawCall {
+            caller: "0xcafe000000000000000000000000000000000000".into(),
+            addr: format!("{}/test/Echo", net_service(&node)),
+            body: Codec::Json.to_vec(&Echo("hello".into())).unwrap(),
+            deadline: None,
+            codec: Some(Codec::Json),
+        };
+
+        let mut relayed = forward_handler(&[node], &secure, &status);
+        let reply = relayed.do_raw_call("1".into(), call()).next().await;
+        assert!(reply.unwrap().is_ok());
+
+        let mut direct = forward_handler(&[node], &secure.sealed_only(), &status);
+        match direct.do_raw_call("2".into(), call()).next().await {
+            Some(Err(Error::GsbBadRequest(_))) => (),
+            other => panic!("plaintext call served: {:?}", other.map(|r| r.is_ok())),
+        }
     }
 }
@@ -0,0 +1,120 @@
+// Connection state and message counters, reported with `local::GetStatus`
+
+use futures::future;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ya_client_model::NodeId;
+use ya_core_model::net::local::{self as local_net, MessageCounters};
+use ya_service_bus::typed as bus;
+
+use crate::bcast::BCastService;
+
+#[derive(Clone)]
+pub(crate) struct NetStatus {
+    inner: Rc<RefCell<local_net::Status>>,
+}
+
+impl NetStatus {
+    pub fn new(mode: &str, node_ids: Vec<NodeId>) -> Self {
+        NetStatus {
+            inner: Rc::new(RefCell::new(local_net::Status {
+                mode: mode.to_string(),
+                hub_addr: None,
+                connected: false,
+                node_ids,
+                topics: Vec::new(),
+                outgoing: MessageCounters::default(),
+                incoming: MessageCounters::default(),
+            })),
+        }
+    }
+
+    pub fn set_hub_addr(&self, hub_addr: String) {
+        self.inner.borrow_mut().hub_addr = Some(hub_addr);
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.inner.borrow_mut().connected = connected;
+    }
+
+    pub fn call_sent<T, E>(&self, result: &Result<T, E>) {
+        count_call(&mut self.inner.borrow_mut().outgoing, result.is_ok());
+    }
+
+    pub fn call_received<T, E>(&self, result: &Result<T, E>) {
+        count_call(&mut self.inner.borrow_mut().incoming, result.is_ok());
+    }
+
+    pub fn broadcast_sent(&self) {
+        self.inner.borrow_mut().outgoing.broadcasts += 1;
+    }
+
+    pub fn broadcast_received(&self) {
+        self.inner.borrow_mut().incoming.broadcasts += 1;
+    }
+
+    /// Serves `GetStatus`, with topics taken from `bcast` at the time of the query.
+    pub fn bind(&self, bcast: &BCastService) {
+        let me = self.clone();
+        let bcast = bcast.clone();
+        let _ = bus::bind(local_net::BUS_ID, move |_: local_net::GetStatus| {
+            let mut status = me.inner.borrow().clone();
+            status.topics = bcast.topics();
+            future::ok(status)
+        });
+    }
+}
+
+fn count_call(counters: &mut MessageCounters, ok: bool) {
+    counters.calls += 1;
+    if !ok {
+        counters.failed_calls += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya_service_bus::RpcEndpoint;
+
+    #[actix_rt::test]
+    async fn get_status_reports_state_counters_and_topics() {
+        let node: NodeId = "0xbabe000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let bcast = BCastService::default();
+        bcast.add(local_net::Subscribe::with_filter(
+            "market/**",
+            "/local/market/bcast",
+        ));
+        let status = NetStatus::new("hub", vec![node]);
+        status.bind(&bcast);
+
+        status.set_hub_addr("127.0.0.1:7464".to_string());
+        status.set_connected(true);
+        status.call_sent(&Ok::<(), ()>(()));
+        status.call_sent(&Err::<(), ()>(()));
+        status.call_received(&Ok::<(), ()>(()));
+        status.broadcast_sent();
+        status.broadcast_received();
+        status.broadcast_received();
+
+        let reported = bus::service(local_net::BUS_ID)
+            .send(local_net::GetStatus {})
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reported.mode, "hub");
+        assert_eq!(reported.hub_addr.as_deref(), Some("127.0.0.1:7464"));
+        assert!(reported.connected);
+        assert_eq!(reported.node_ids, vec![node]);
+        assert_eq!(reported.topics, vec!["market/**"]);
+        assert_eq!(reported.outgoing.calls, 2);
+        assert_eq!(reported.outgoing.failed_calls, 1);
+        assert_eq!(reported.outgoing.broadcasts, 1);
+        assert_eq!(reported.incoming.calls, 1);
+        assert_eq!(reported.incoming.failed_calls, 0);
+        assert_eq!(reported.incoming.broadcasts, 2);
+    }
+}
@@ -221,11 +221,13 @@ async fn main() -> anyhow::Result<()> {
             identity: provider_id,
             name: "".to_string(),
             role: "".to_string(),
+            scope: None,
         };
         let requestor_identity = Identity {
             identity: requestor_id,
             name: "".to_string(),
             role: "".to_string(),
+            scope: None,
         };
 
         let provider_scope =
@@ -0,0 +1,17 @@
+CREATE TABLE pay_outbox(
+    id VARCHAR(50) NOT NULL PRIMARY KEY,
+    owner_id VARCHAR(50) NOT NULL,
+    peer_id VARCHAR(50) NOT NULL,
+    message_type VARCHAR(50) NOT NULL,
+    document_id VARCHAR(50) NOT NULL,
+    body TEXT NOT NULL,
+    status VARCHAR(50) NOT NULL CHECK (status in ('PENDING', 'DELIVERED', 'REJECTED', 'EXPIRED')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT NULL,
+    created_ts DATETIME NOT NULL DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    next_attempt_ts DATETIME NOT NULL,
+    expires_ts DATETIME NOT NULL,
+    UNIQUE(owner_id, message_type, document_id)
+);
+
+CREATE INDEX pay_outbox_next_attempt_idx ON pay_outbox(status, next_attempt_ts);
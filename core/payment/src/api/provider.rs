@@ -1,6 +1,7 @@
 use crate::api::*;
 use crate::dao::*;
 use crate::error::{DbError, Error};
+use crate::outbox::{self, Delivery};
 use crate::utils::provider::*;
 use crate::utils::*;
 use actix_web::web::{get, post, Data, Json, Path, Query};
@@ -9,7 +10,7 @@ use serde_json::value::Value::Null;
 use ya_client_model::payment::*;
 use ya_core_model::payment::local::{GetAccounts, BUS_ID as LOCAL_SERVICE};
 use ya_core_model::payment::public::{
-    CancelError, CancelInvoice, SendDebitNote, SendError, SendInvoice, BUS_ID as PUBLIC_SERVICE,
+    CancelError, CancelInvoice, SendDebitNote, SendInvoice, BUS_ID as PUBLIC_SERVICE,
 };
 use ya_core_model::payment::RpcMessageError;
 use ya_net::RemoteEndpoint;
@@ -147,21 +148,11 @@ async fn send_debit_note(
     }
 
     with_timeout(query.timeout, async move {
-        match async move {
-            ya_net::from(node_id)
-                .to(debit_note.recipient_id)
-                .service(PUBLIC_SERVICE)
-                .call(SendDebitNote(debit_note))
-                .await??;
-            dao.mark_received(debit_note_id, node_id).await?;
-            Ok(())
-        }
-        .await
-        {
-            Ok(_) => response::ok(Null),
-            Err(Error::Rpc(RpcMessageError::Send(SendError::BadRequest(e)))) => {
-                response::bad_request(&e)
-            }
+        let recipient_id = debit_note.recipient_id;
+        match outbox::send(&db, node_id, recipient_id, SendDebitNote(debit_note)).await {
+            Ok(Delivery::Delivered) => response::ok(Null),
+            Ok(Delivery::Rejected(e)) => response::bad_request(&e),
+            Ok(Delivery::Pending(_)) => response::accepted(Null),
             Err(e) => response::server_error(&e),
         }
     })
@@ -306,21 +297,11 @@ async fn send_invoice(
     }
 
     with_timeout(query.timeout, async move {
-        match async move {
-            ya_net::from(node_id)
-                .to(invoice.recipient_id)
-                .service(PUBLIC_SERVICE)
-                .call(SendInvoice(invoice))
-                .await??;
-            dao.mark_received(invoice_id, node_id).await?;
-            Ok(())
-        }
-        .await
-        {
-            Ok(_) => response::ok(Null),
-            Err(Error::Rpc(RpcMessageError::Send(SendError::BadRequest(e)))) => {
-                response::bad_request(&e)
-            }
+        let recipient_id = invoice.recipient_id;
+        match outbox::send(&db, node_id, recipient_id, SendInvoice(invoice)).await {
+            Ok(Delivery::Delivered) => response::ok(Null),
+            Ok(Delivery::Rejected(e)) => response::bad_request(&e),
+            Ok(Delivery::Pending(_)) => response::accepted(Null),
             Err(e) => response::server_error(&e),
         }
     })
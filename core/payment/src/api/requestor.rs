@@ -92,6 +92,9 @@ async fn accept_debit_note(
     let node_id = id.identity;
     let acceptance = body.into_inner();
     let allocation_id = acceptance.allocation_id.clone();
+    if !id.is_allocation_allowed(&allocation_id) {
+        return response::forbidden(&"Allocation out of application key scope");
+    }
 
     let dao: DebitNoteDao = db.as_dao();
     let debit_note: DebitNote = match dao.get(debit_note_id.clone(), node_id).await {
@@ -239,6 +242,9 @@ async fn accept_invoice(
     let node_id = id.identity;
     let acceptance = body.into_inner();
     let allocation_id = acceptance.allocation_id.clone();
+    if !id.is_allocation_allowed(&allocation_id) {
+        return response::forbidden(&"Allocation out of application key scope");
+    }
 
     let dao: InvoiceDao = db.as_dao();
     let invoice = match dao.get(invoice_id.clone(), node_id).await {
@@ -484,3 +490,73 @@ async fn get_accounts(id: Identity) -> HttpResponse {
         .collect();
     response::ok(recv_accounts)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Service as _;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::json;
+    use ya_core_model::appkey::Scope as AppKeyScope;
+    use ya_service_api_web::middleware::auth::dummy::DummyAuth;
+
+    fn identity(allocations: Option<Vec<&str>>) -> Identity {
+        Identity {
+            identity: "0xbabe000000000000000000000000000000000000"
+                .parse()
+                .unwrap(),
+            name: "app".to_string(),
+            role: "requestor".to_string(),
+            scope: allocations.map(|allocations| AppKeyScope {
+                allocations: allocations.into_iter().map(str::to_string).collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Status of accepting an unknown invoice and debit note with `allocation_id`.
+    async fn accept_status(id: Identity, allocation_id: &str) -> Vec<StatusCode> {
+        let dir = tempdir::TempDir::new("payment").unwrap();
+        let db = DbExecutor::from_data_dir(dir.path(), "payment").unwrap();
+        db.apply_migration(crate::migrations::run_with_output)
+            .unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .service(crate::api::requestor_scope().wrap(DummyAuth::new(id))),
+        )
+        .await;
+
+        let acceptance = json!({"totalAmountAccepted": "1", "allocationId": allocation_id});
+        let mut statuses = vec![];
+        for uri in &[
+            "/requestor/invoices/i1/accept",
+            "/requestor/debitNotes/d1/accept",
+        ] {
+            let req = test::TestRequest::post()
+                .uri(uri)
+                .set_json(&acceptance)
+                .to_request();
+            statuses.push(app.call(req).await.unwrap().status());
+        }
+        statuses
+    }
+
+    #[actix_rt::test]
+    async fn acceptance_is_limited_to_allocations_in_scope() {
+        let forbidden = vec![StatusCode::FORBIDDEN; 2];
+        let allowed = vec![StatusCode::NOT_FOUND; 2];
+
+        assert_eq!(accept_status(identity(None), "a1").await, allowed);
+        assert_eq!(
+            accept_status(identity(Some(vec!["a1"])), "a1").await,
+            allowed
+        );
+        assert_eq!(
+            accept_status(identity(Some(vec!["a1"])), "a2").await,
+            forbidden
+        );
+        assert_eq!(accept_status(identity(Some(vec![])), "a1").await, forbidden);
+    }
+}
@@ -23,6 +23,12 @@ pub enum PaymentCli {
         platform: Option<String>,
     },
     Accounts,
+    /// Lists payment messages sent to other nodes through the outbox
+    Outbox {
+        /// Only messages with given status (pending, delivered, rejected, expired)
+        #[structopt(long, short)]
+        status: Option<pay::OutboxStatus>,
+    },
 }
 
 impl PaymentCli {
@@ -83,6 +89,37 @@ impl PaymentCli {
                 }
                 .into())
             }
+            PaymentCli::Outbox { status } => {
+                let entries = bus::service(pay::BUS_ID)
+                    .call(pay::GetOutbox { status })
+                    .await??;
+                Ok(ResponseTable {
+                    columns: vec![
+                        "message".to_owned(),
+                        "document".to_owned(),
+                        "peer".to_owned(),
+                        "status".to_owned(),
+                        "attempts".to_owned(),
+                        "next attempt".to_owned(),
+                        "last error".to_owned(),
+                    ],
+                    values: entries
+                        .into_iter()
+                        .map(|entry| {
+                            serde_json::json! {[
+                                entry.message_type,
+                                entry.document_id,
+                                entry.peer_id,
+                                entry.status,
+                                entry.attempts,
+                                entry.next_attempt.to_rfc3339(),
+                                entry.last_error.unwrap_or_default()
+                            ]}
+                        })
+                        .collect(),
+                }
+                .into())
+            }
         }
     }
 }
@@ -6,6 +6,7 @@ mod debit_note_event;
 mod invoice;
 mod invoice_event;
 mod order;
+mod outbox;
 mod payment;
 
 pub use self::activity::ActivityDao;
@@ -16,4 +17,5 @@ pub use self::debit_note_event::DebitNoteEventDao;
 pub use self::invoice::InvoiceDao;
 pub use self::invoice_event::InvoiceEventDao;
 pub use self::order::OrderDao;
+pub use self::outbox::OutboxDao;
 pub use self::payment::PaymentDao;
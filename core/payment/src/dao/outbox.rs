@@ -0,0 +1,313 @@
+use crate::error::DbResult;
+use crate::models::outbox::{ReadObj, WriteObj};
+use crate::schema::pay_outbox::dsl;
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use std::convert::TryInto;
+use ya_core_model::payment::local::{OutboxEntry, OutboxStatus};
+use ya_persistence::executor::{do_with_transaction, readonly_transaction, AsDao, PoolType};
+
+pub struct OutboxDao<'c> {
+    pool: &'c PoolType,
+}
+
+impl<'c> AsDao<'c> for OutboxDao<'c> {
+    fn as_dao(pool: &'c PoolType) -> Self {
+        Self { pool }
+    }
+}
+
+impl<'c> OutboxDao<'c> {
+    /// Stores the message and returns id of its entry. A message already pending
+    /// keeps its schedule, a message delivered or given up before is sent again.
+    pub async fn enqueue(&self, entry: WriteObj) -> DbResult<String> {
+        do_with_transaction(self.pool, move |conn| {
+            let existing: Option<(String, String)> = dsl::pay_outbox
+                .filter(dsl::owner_id.eq(&entry.owner_id))
+                .filter(dsl::message_type.eq(&entry.message_type))
+                .filter(dsl::document_id.eq(&entry.document_id))
+                .select((dsl::id, dsl::status))
+                .first(conn)
+                .optional()?;
+            match existing {
+                Some((id, status)) if status == OutboxStatus::Pending.as_str() => Ok(id),
+                Some((id, _)) => {
+                    diesel::update(dsl::pay_outbox.find(&id))
+                        .set((
+                            dsl::body.eq(entry.body),
+                            dsl::status.eq(entry.status),
+                            dsl::attempts.eq(0),
+                            dsl::last_error.eq(None::<String>),
+                            dsl::next_attempt_ts.eq(entry.next_attempt_ts),
+                            dsl::expires_ts.eq(entry.expires_ts),
+                        ))
+                        .execute(conn)?;
+                    Ok(id)
+                }
+                None => {
+                    let id = entry.id.clone();
+                    diesel::insert_into(dsl::pay_outbox)
+                        .values(entry)
+                        .execute(conn)?;
+                    Ok(id)
+                }
+            }
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: String) -> DbResult<Option<ReadObj>> {
+        readonly_transaction(self.pool, move |conn| {
+            Ok(dsl::pay_outbox.find(id).first(conn).optional()?)
+        })
+        .await
+    }
+
+    /// Pending entries scheduled for delivery not later than `now`.
+    pub async fn get_due(&self, now: NaiveDateTime) -> DbResult<Vec<ReadObj>> {
+        readonly_transaction(self.pool, move |conn| {
+            Ok(dsl::pay_outbox
+                .filter(dsl::status.eq(OutboxStatus::Pending.as_str()))
+                .filter(dsl::next_attempt_ts.le(now))
+                .order_by(dsl::next_attempt_ts.asc())
+                .load(conn)?)
+        })
+        .await
+    }
+
+    /// Claims a pending entry due not later than `now` for one delivery attempt
+    /// by postponing its next attempt to `lease_until`. Returns `None` when the
+    /// entry is not due, e.g. because another attempt has claimed it already.
+    pub async fn claim(
+        &self,
+        id: String,
+        now: NaiveDateTime,
+        lease_until: NaiveDateTime,
+    ) -> DbResult<Option<ReadObj>> {
+        do_with_transaction(self.pool, move |conn| {
+            let claimed = diesel::update(
+                dsl::pay_outbox
+                    .filter(dsl::id.eq(&id))
+                    .filter(dsl::status.eq(OutboxStatus::Pending.as_str()))
+                    .filter(dsl::next_attempt_ts.le(now)),
+            )
+            .set(dsl::next_attempt_ts.eq(lease_until))
+            .execute(conn)?;
+            if claimed == 0 {
+                return Ok(None);
+            }
+            Ok(Some(dsl::pay_outbox.find(&id).first(conn)?))
+        })
+        .await
+    }
+
+    pub async fn mark_delivered(&self, id: String) -> DbResult<()> {
+        self.finish_attempt(id, OutboxStatus::Delivered, None, None)
+            .await
+    }
+
+    pub async fn mark_rejected(&self, id: String, error: String) -> DbResult<()> {
+        self.finish_attempt(id, OutboxStatus::Rejected, Some(error), None)
+            .await
+    }
+
+    pub async fn retry_later(
+        &self,
+        id: String,
+        error: String,
+        next_attempt_ts: NaiveDateTime,
+    ) -> DbResult<()> {
+        self.finish_attempt(
+            id,
+            OutboxStatus::Pending,
+            Some(error),
+            Some(next_attempt_ts),
+        )
+        .await
+    }
+
+    async fn finish_attempt(
+        &self,
+        id: String,
+        status: OutboxStatus,
+        error: Option<String>,
+        next_attempt_ts: Option<NaiveDateTime>,
+    ) -> DbResult<()> {
+        do_with_transaction(self.pool, move |conn| {
+            let query = diesel::update(dsl::pay_outbox.find(&id));
+            let values = (
+                dsl::status.eq(status.as_str()),
+                dsl::attempts.eq(dsl::attempts + 1),
+                dsl::last_error.eq(error),
+            );
+            match next_attempt_ts {
+                Some(ts) => query
+                    .set((values, dsl::next_attempt_ts.eq(ts)))
+                    .execute(conn)?,
+                None => query.set(values).execute(conn)?,
+            };
+            Ok(())
+        })
+        .await
+    }
+
+    /// Gives up on pending entries which expired before `now`.
+    pub async fn expire(&self, now: NaiveDateTime) -> DbResult<usize> {
+        do_with_transaction(self.pool, move |conn| {
+            Ok(diesel::update(
+                dsl::pay_outbox
+                    .filter(dsl::status.eq(OutboxStatus::Pending.as_str()))
+                    .filter(dsl::expires_ts.le(now)),
+            )
+            .set(dsl::status.eq(OutboxStatus::Expired.as_str()))
+            .execute(conn)?)
+        })
+        .await
+    }
+
+    pub async fn list(&self, status: Option<OutboxStatus>) -> DbResult<Vec<OutboxEntry>> {
+        readonly_transaction(self.pool, move |conn| {
+            let query = dsl::pay_outbox.order_by(dsl::created_ts.asc());
+            let entries: Vec<ReadObj> = match status {
+                Some(status) => query.filter(dsl::status.eq(status.as_str())).load(conn)?,
+                None => query.load(conn)?,
+            };
+            entries.into_iter().map(TryInto::try_into).collect()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use ya_persistence::executor::DbExecutor;
+
+    fn test_db(dir: &tempdir::TempDir) -> DbExecutor {
+        let db = DbExecutor::from_data_dir(dir.path(), "payment").unwrap();
+        db.apply_migration(crate::migrations::run_with_output)
+            .unwrap();
+        db
+    }
+
+    fn entry(document_id: &str, next_attempt_ts: NaiveDateTime) -> WriteObj {
+        WriteObj {
+            id: uuid::Uuid::new_v4().to_string(),
+            owner_id: "0x0000000000000000000000000000000000000001"
+                .parse()
+                .unwrap(),
+            peer_id: "0x0000000000000000000000000000000000000002"
+                .parse()
+                .unwrap(),
+            message_type: "SendInvoice".to_string(),
+            document_id: document_id.to_string(),
+            body: "{}".to_string(),
+            status: OutboxStatus::Pending.as_str().to_string(),
+            next_attempt_ts,
+            expires_ts: next_attempt_ts + Duration::days(7),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn enqueue_keeps_pending_entry_and_resends_finished_one() {
+        let dir = tempdir::TempDir::new("outbox").unwrap();
+        let db = test_db(&dir);
+        let dao: OutboxDao = db.as_dao();
+        let now = Utc::now().naive_utc();
+
+        let id = dao.enqueue(entry("invoice-1", now)).await.unwrap();
+        let later = now + Duration::minutes(1);
+        assert_eq!(dao.enqueue(entry("invoice-1", later)).await.unwrap(), id);
+        assert_eq!(
+            dao.get(id.clone()).await.unwrap().unwrap().next_attempt_ts,
+            now
+        );
+
+        dao.mark_delivered(id.clone()).await.unwrap();
+        let delivered = dao.get(id.clone()).await.unwrap().unwrap();
+        assert_eq!(delivered.status, OutboxStatus::Delivered.as_str());
+        assert_eq!(delivered.attempts, 1);
+
+        assert_eq!(dao.enqueue(entry("invoice-1", later)).await.unwrap(), id);
+        let resent = dao.get(id).await.unwrap().unwrap();
+        assert_eq!(resent.status, OutboxStatus::Pending.as_str());
+        assert_eq!(resent.attempts, 0);
+        assert_eq!(resent.next_attempt_ts, later);
+    }
+
+    #[actix_rt::test]
+    async fn claimed_entry_is_attempted_once() {
+        let dir = tempdir::TempDir::new("outbox").unwrap();
+        let db = test_db(&dir);
+        let dao: OutboxDao = db.as_dao();
+        let now = Utc::now().naive_utc();
+        let lease_until = now + Duration::minutes(5);
+
+        let id = dao.enqueue(entry("invoice-1", now)).await.unwrap();
+        assert_eq!(dao.get_due(now).await.unwrap().len(), 1);
+        assert!(dao
+            .claim(id.clone(), now, lease_until)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(dao
+            .claim(id.clone(), now, lease_until)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(dao.get_due(now).await.unwrap().is_empty());
+
+        dao.retry_later(id.clone(), "unreachable".to_string(), now)
+            .await
+            .unwrap();
+        let pending = dao.get(id.clone()).await.unwrap().unwrap();
+        assert_eq!(pending.status, OutboxStatus::Pending.as_str());
+        assert_eq!(pending.attempts, 1);
+        assert_eq!(pending.last_error.as_deref(), Some("unreachable"));
+        assert!(dao
+            .claim(id.clone(), now, lease_until)
+            .await
+            .unwrap()
+            .is_some());
+
+        dao.mark_rejected(id.clone(), "bad request".to_string())
+            .await
+            .unwrap();
+        let rejected = dao.get(id.clone()).await.unwrap().unwrap();
+        assert_eq!(rejected.status, OutboxStatus::Rejected.as_str());
+        assert_eq!(rejected.attempts, 2);
+        assert!(dao
+            .claim(id, lease_until, lease_until)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[actix_rt::test]
+    async fn expired_entries_are_given_up() {
+        let dir = tempdir::TempDir::new("outbox").unwrap();
+        let db = test_db(&dir);
+        let dao: OutboxDao = db.as_dao();
+        let now = Utc::now().naive_utc();
+
+        let expired = dao
+            .enqueue(entry("invoice-1", now - Duration::days(8)))
+            .await
+            .unwrap();
+        let pending = dao.enqueue(entry("invoice-2", now)).await.unwrap();
+        assert_eq!(dao.expire(now).await.unwrap(), 1);
+
+        let entry = dao.get(expired.clone()).await.unwrap().unwrap();
+        assert_eq!(entry.status, OutboxStatus::Expired.as_str());
+        assert!(dao.claim(expired, now, now).await.unwrap().is_none());
+        let due: Vec<String> = dao
+            .get_due(now)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect();
+        assert_eq!(due, vec![pending]);
+    }
+}
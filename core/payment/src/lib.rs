@@ -13,6 +13,7 @@ mod cli;
 pub mod dao;
 pub mod error;
 pub mod models;
+pub mod outbox;
 pub mod processor;
 pub mod schema;
 pub mod service;
@@ -38,6 +39,7 @@ impl PaymentService {
         db.apply_migration(migrations::run_with_output)?;
         let processor = PaymentProcessor::new(db.clone());
         self::service::bind_service(&db, processor);
+        outbox::start(db.clone());
         Ok(())
     }
 
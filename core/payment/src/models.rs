@@ -6,4 +6,5 @@ pub mod debit_note_event;
 pub mod invoice;
 pub mod invoice_event;
 pub mod order;
+pub mod outbox;
 pub mod payment;
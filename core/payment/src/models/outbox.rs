@@ -0,0 +1,86 @@
+use crate::error::{DbError, DbResult};
+use crate::schema::pay_outbox;
+use crate::utils::json_to_string;
+use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
+use std::convert::TryFrom;
+use uuid::Uuid;
+use ya_client_model::NodeId;
+use ya_core_model::payment::local::{OutboxEntry, OutboxStatus};
+use ya_core_model::payment::public::DurableMessage;
+
+#[derive(Debug, Insertable)]
+#[table_name = "pay_outbox"]
+pub struct WriteObj {
+    pub id: String,
+    pub owner_id: NodeId,
+    pub peer_id: NodeId,
+    pub message_type: String,
+    pub document_id: String,
+    pub body: String,
+    pub status: String,
+    pub next_attempt_ts: NaiveDateTime,
+    pub expires_ts: NaiveDateTime,
+}
+
+impl WriteObj {
+    pub fn new<M: DurableMessage>(
+        owner_id: NodeId,
+        peer_id: NodeId,
+        msg: &M,
+        first_attempt_delay: Duration,
+        ttl: Duration,
+    ) -> DbResult<Self> {
+        let now = Utc::now().naive_utc();
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            owner_id,
+            peer_id,
+            message_type: M::ID.to_owned(),
+            document_id: msg.document_id().to_owned(),
+            body: json_to_string(msg)?,
+            status: OutboxStatus::Pending.as_str().to_owned(),
+            next_attempt_ts: now + first_attempt_delay,
+            expires_ts: now + ttl,
+        })
+    }
+}
+
+#[derive(Queryable, Debug, Identifiable)]
+#[table_name = "pay_outbox"]
+pub struct ReadObj {
+    pub id: String,
+    pub owner_id: NodeId,
+    pub peer_id: NodeId,
+    pub message_type: String,
+    pub document_id: String,
+    pub body: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_ts: NaiveDateTime,
+    pub next_attempt_ts: NaiveDateTime,
+    pub expires_ts: NaiveDateTime,
+}
+
+impl TryFrom<ReadObj> for OutboxEntry {
+    type Error = DbError;
+
+    fn try_from(entry: ReadObj) -> DbResult<Self> {
+        Ok(Self {
+            status: entry
+                .status
+                .parse()
+                .map_err(|e| DbError::Integrity(format!("{}", e)))?,
+            id: entry.id,
+            owner_id: entry.owner_id,
+            peer_id: entry.peer_id,
+            message_type: entry.message_type,
+            document_id: entry.document_id,
+            attempts: entry.attempts,
+            last_error: entry.last_error,
+            created: Utc.from_utc_datetime(&entry.created_ts),
+            next_attempt: Utc.from_utc_datetime(&entry.next_attempt_ts),
+            expires: Utc.from_utc_datetime(&entry.expires_ts),
+        })
+    }
+}
@@ -0,0 +1,189 @@
+//! Durable delivery of payment messages to other nodes.
+//!
+//! [`DurableMessage`]s sent through the outbox are stored before the first
+//! delivery attempt and redelivered with exponential backoff until the
+//! recipient acknowledges or refuses them, or until they expire.
+use crate::dao::{DebitNoteDao, InvoiceDao, OutboxDao};
+use crate::error::DbError;
+use crate::models::outbox::{ReadObj, WriteObj};
+use crate::utils::json_from_str;
+use actix_rt::Arbiter;
+use chrono::{Duration, Utc};
+use ya_client_model::NodeId;
+use ya_core_model::payment::public::{
+    DurableMessage, SendDebitNote, SendError, SendInvoice, SendPayment, BUS_ID,
+};
+use ya_net::RemoteEndpoint;
+use ya_persistence::executor::DbExecutor;
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const MIN_RETRY_DELAY_SECS: i64 = 5;
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+const MESSAGE_TTL_DAYS: i64 = 7;
+/// Longer than any delivery attempt, so that a claimed entry is attempted again
+/// only if the process stopped in the middle of the attempt.
+const ATTEMPT_LEASE_SECS: i64 = 300;
+
+/// Outcome of a delivery attempt.
+#[derive(Clone, Debug)]
+pub enum Delivery {
+    Delivered,
+    /// Refused by the recipient, will not be retried.
+    Rejected(String),
+    /// Recipient could not be reached, delivery will be retried.
+    Pending(String),
+}
+
+/// Stores the message and makes the first delivery attempt right away.
+pub async fn send<M: DurableMessage>(
+    db: &DbExecutor,
+    owner_id: NodeId,
+    peer_id: NodeId,
+    msg: M,
+) -> Result<Delivery, DbError> {
+    let entry = WriteObj::new(
+        owner_id,
+        peer_id,
+        &msg,
+        Duration::zero(),
+        Duration::days(MESSAGE_TTL_DAYS),
+    )?;
+    let dao: OutboxDao = db.as_dao();
+    let id = dao.enqueue(entry).await?;
+    if let Some(entry) = claim(&dao, id.clone()).await? {
+        return deliver(db, entry).await;
+    }
+    // Already being attempted or scheduled for a retry
+    match dao.get(id.clone()).await? {
+        Some(entry) => Ok(Delivery::Pending(format!(
+            "Delivery scheduled for {}",
+            entry.next_attempt_ts
+        ))),
+        None => Err(DbError::Integrity(format!("Outbox entry {} not found", id))),
+    }
+}
+
+/// Redelivers pending messages in the background.
+pub fn start(db: DbExecutor) {
+    Arbiter::spawn(async move {
+        loop {
+            if let Err(e) = process(&db).await {
+                log::error!("Payment outbox error: {}", e);
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+}
+
+async fn process(db: &DbExecutor) -> Result<(), DbError> {
+    let now = Utc::now().naive_utc();
+    let dao: OutboxDao = db.as_dao();
+    let expired = dao.expire(now).await?;
+    if expired > 0 {
+        log::warn!("{} outgoing payment messages expired undelivered", expired);
+    }
+    for entry in dao.get_due(now).await? {
+        let entry = match claim(&dao, entry.id).await? {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let description = format!(
+            "{} {} to {}",
+            entry.message_type, entry.document_id, entry.peer_id
+        );
+        match deliver(db, entry).await? {
+            Delivery::Delivered => log::info!("Delivered {}", description),
+            Delivery::Rejected(e) => log::warn!("Recipient refused {}: {}", description, e),
+            Delivery::Pending(e) => log::debug!("Failed to deliver {}: {}", description, e),
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure a single delivery attempt at a time is made for the entry.
+async fn claim(dao: &OutboxDao<'_>, id: String) -> Result<Option<ReadObj>, DbError> {
+    let now = Utc::now().naive_utc();
+    dao.claim(id, now, now + Duration::seconds(ATTEMPT_LEASE_SECS))
+        .await
+}
+
+async fn deliver(db: &DbExecutor, entry: ReadObj) -> Result<Delivery, DbError> {
+    let owner_id = entry.owner_id;
+    let document_id = entry.document_id.clone();
+    match entry.message_type.as_str() {
+        t if t == SendInvoice::ID => {
+            let delivery = attempt::<SendInvoice>(db, entry).await?;
+            if let Delivery::Delivered = delivery {
+                let dao: InvoiceDao = db.as_dao();
+                dao.mark_received(document_id, owner_id).await?;
+            }
+            Ok(delivery)
+        }
+        t if t == SendDebitNote::ID => {
+            let delivery = attempt::<SendDebitNote>(db, entry).await?;
+            if let Delivery::Delivered = delivery {
+                let dao: DebitNoteDao = db.as_dao();
+                dao.mark_received(document_id, owner_id).await?;
+            }
+            Ok(delivery)
+        }
+        t if t == SendPayment::ID => attempt::<SendPayment>(db, entry).await,
+        t => {
+            let e = format!("Unsupported message type: {}", t);
+            db.as_dao::<OutboxDao>()
+                .mark_rejected(entry.id, e.clone())
+                .await?;
+            Ok(Delivery::Rejected(e))
+        }
+    }
+}
+
+async fn attempt<M: DurableMessage>(db: &DbExecutor, entry: ReadObj) -> Result<Delivery, DbError> {
+    let msg: M = json_from_str(&entry.body)?;
+    let result = ya_net::from(entry.owner_id)
+        .to(entry.peer_id)
+        .service(BUS_ID)
+        .call(msg)
+        .await;
+
+    let dao: OutboxDao = db.as_dao();
+    let error = match result {
+        Ok(Ok(_)) => {
+            dao.mark_delivered(entry.id).await?;
+            return Ok(Delivery::Delivered);
+        }
+        Ok(Err(SendError::BadRequest(e))) => {
+            dao.mark_rejected(entry.id, e.clone()).await?;
+            return Ok(Delivery::Rejected(e));
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    let next_attempt_ts = Utc::now().naive_utc() + retry_delay(entry.attempts);
+    dao.retry_later(entry.id, error.clone(), next_attempt_ts)
+        .await?;
+    Ok(Delivery::Pending(error))
+}
+
+fn retry_delay(attempts: i32) -> Duration {
+    let delay = MIN_RETRY_DELAY_SECS
+        .checked_shl(attempts.max(0) as u32)
+        .filter(|delay| *delay > 0)
+        .unwrap_or(MAX_RETRY_DELAY_SECS);
+    Duration::seconds(delay.min(MAX_RETRY_DELAY_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_limit() {
+        assert_eq!(retry_delay(0), Duration::seconds(MIN_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(1), Duration::seconds(2 * MIN_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(3), Duration::seconds(8 * MIN_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(-1), Duration::seconds(MIN_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(20), Duration::seconds(MAX_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(100), Duration::seconds(MAX_RETRY_DELAY_SECS));
+    }
+}
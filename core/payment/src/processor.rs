@@ -4,6 +4,7 @@ use crate::error::processor::{
     OrderValidationError, SchedulePaymentError, VerifyPaymentError,
 };
 use crate::models::order::ReadObj as DbOrder;
+use crate::outbox::{self, Delivery};
 use bigdecimal::{BigDecimal, Zero};
 use futures::lock::Mutex;
 use std::collections::hash_map::Entry;
@@ -17,8 +18,7 @@ use ya_core_model::payment::local::{
     Account, NotifyPayment, RegisterAccount, RegisterAccountError, SchedulePayment,
     UnregisterAccount, UnregisterAccountError,
 };
-use ya_core_model::payment::public::{SendPayment, BUS_ID};
-use ya_net::RemoteEndpoint;
+use ya_core_model::payment::public::{SendError, SendPayment};
 use ya_persistence::executor::DbExecutor;
 use ya_service_bus::typed::Endpoint;
 use ya_service_bus::{typed as bus, RpcEndpoint};
@@ -237,15 +237,14 @@ impl PaymentProcessor {
         }
 
         let msg = SendPayment(payment);
-        ya_net::from(payer_id)
-            .to(payee_id)
-            .service(BUS_ID)
-            .call(msg)
-            .await??;
-
-        // TODO: Implement re-sending mechanism in case SendPayment fails
-
-        Ok(())
+        match outbox::send(&self.db_executor, payer_id, payee_id, msg).await? {
+            Delivery::Rejected(e) => Err(SendError::BadRequest(e).into()),
+            Delivery::Pending(e) => {
+                log::warn!("Payment to {} not delivered yet: {}", payee_id, e);
+                Ok(())
+            }
+            Delivery::Delivered => Ok(()),
+        }
     }
 
     pub async fn schedule_payment(&self, msg: SchedulePayment) -> Result<(), SchedulePaymentError> {
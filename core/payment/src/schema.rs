@@ -146,6 +146,23 @@ table! {
     }
 }
 
+table! {
+    pay_outbox (id) {
+        id -> Text,
+        owner_id -> Text,
+        peer_id -> Text,
+        message_type -> Text,
+        document_id -> Text,
+        body -> Text,
+        status -> Text,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        created_ts -> Timestamp,
+        next_attempt_ts -> Timestamp,
+        expires_ts -> Timestamp,
+    }
+}
+
 table! {
     pay_payment (id, owner_id) {
         id -> Text,
@@ -183,5 +200,6 @@ allow_tables_to_appear_in_same_query!(
     pay_invoice_event,
     pay_invoice_x_activity,
     pay_order,
+    pay_outbox,
     pay_payment,
 );
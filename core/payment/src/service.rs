@@ -26,7 +26,8 @@ mod local {
             .bind_with_processor(unregister_account)
             .bind_with_processor(notify_payment)
             .bind_with_processor(get_status)
-            .bind_with_processor(get_accounts);
+            .bind_with_processor(get_accounts)
+            .bind_with_processor(get_outbox);
         log::debug!("Successfully bound payment local service to service bus");
     }
 
@@ -67,6 +68,16 @@ mod local {
         Ok(processor.get_accounts().await)
     }
 
+    async fn get_outbox(
+        db: DbExecutor,
+        processor: PaymentProcessor,
+        sender: String,
+        msg: GetOutbox,
+    ) -> Result<Vec<OutboxEntry>, GenericError> {
+        let dao: OutboxDao = db.as_dao();
+        dao.list(msg.status).await.map_err(GenericError::new)
+    }
+
     async fn notify_payment(
         db: DbExecutor,
         processor: PaymentProcessor,
@@ -192,6 +203,21 @@ mod public {
             .unwrap()
             .parse()
             .unwrap();
+        let stored = db
+            .as_dao::<DebitNoteDao>()
+            .get(debit_note_id.clone(), debit_note.recipient_id)
+            .await;
+        match stored {
+            Ok(Some(stored)) if is_same_debit_note(&stored, &debit_note) => return Ok(Ack {}),
+            Ok(Some(_)) => {
+                return Err(SendError::BadRequest(format!(
+                    "Debit note {} already exists",
+                    debit_note_id
+                )))
+            }
+            Ok(None) => (),
+            Err(e) => return Err(SendError::ServiceError(e.to_string())),
+        }
         match async move {
             db.as_dao::<AgreementDao>()
                 .create_if_not_exists(agreement, node_id, Role::Requestor)
@@ -332,6 +358,21 @@ mod public {
             .unwrap()
             .parse()
             .unwrap();
+        let stored = db
+            .as_dao::<InvoiceDao>()
+            .get(invoice_id.clone(), invoice.recipient_id)
+            .await;
+        match stored {
+            Ok(Some(stored)) if is_same_invoice(&stored, &invoice) => return Ok(Ack {}),
+            Ok(Some(_)) => {
+                return Err(SendError::BadRequest(format!(
+                    "Invoice {} already exists",
+                    invoice_id
+                )))
+            }
+            Ok(None) => (),
+            Err(e) => return Err(SendError::ServiceError(e.to_string())),
+        }
         match async move {
             db.as_dao::<AgreementDao>()
                 .create_if_not_exists(agreement, node_id, Role::Requestor)
@@ -460,6 +501,22 @@ mod public {
             return Err(SendError::BadRequest("Invalid payer ID".to_owned()));
         }
 
+        let stored = db
+            .as_dao::<PaymentDao>()
+            .get(payment.payment_id.clone(), payment.payee_id)
+            .await;
+        match stored {
+            Ok(Some(stored)) if is_same_payment(&stored, &payment) => return Ok(Ack {}),
+            Ok(Some(_)) => {
+                return Err(SendError::BadRequest(format!(
+                    "Payment {} already exists",
+                    payment.payment_id
+                )))
+            }
+            Ok(None) => (),
+            Err(e) => return Err(SendError::ServiceError(e.to_string())),
+        }
+
         match processor.verify_payment(payment).await {
             Ok(_) => Ok(Ack {}),
             Err(e) => match e {
@@ -471,4 +528,124 @@ mod public {
             },
         }
     }
+    // Documents are redelivered from the sender's outbox whenever their acknowledgement
+    // is lost, so receiving one stored before is not an error.
+
+    fn is_same_debit_note(stored: &DebitNote, received: &DebitNote) -> bool {
+        stored.issuer_id == received.issuer_id
+            && stored.activity_id == received.activity_id
+            && stored.total_amount_due == received.total_amount_due
+            && stored.payment_due_date == received.payment_due_date
+    }
+
+    fn is_same_invoice(stored: &Invoice, received: &Invoice) -> bool {
+        let sorted = |ids: &Vec<String>| {
+            let mut ids = ids.clone();
+            ids.sort();
+            ids
+        };
+        stored.issuer_id == received.issuer_id
+            && stored.agreement_id == received.agreement_id
+            && sorted(&stored.activity_ids) == sorted(&received.activity_ids)
+            && stored.amount == received.amount
+            && stored.payment_due_date == received.payment_due_date
+    }
+
+    fn is_same_payment(stored: &Payment, received: &Payment) -> bool {
+        stored.payer_id == received.payer_id
+            && stored.payment_platform == received.payment_platform
+            && stored.amount == received.amount
+            && stored.details == received.details
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::outbox::{self, Delivery};
+        use chrono::Utc;
+        use ya_client_model::market;
+        use ya_service_bus::typed as bus;
+
+        #[actix_rt::test]
+        async fn redelivered_invoice_is_acknowledged() {
+            let dir = tempdir::TempDir::new("payment").unwrap();
+            let db = DbExecutor::from_data_dir(dir.path(), "payment").unwrap();
+            db.apply_migration(crate::migrations::run_with_output)
+                .unwrap();
+            let provider_id: NodeId = "0x0000000000000000000000000000000000000001"
+                .parse()
+                .unwrap();
+            let requestor_id: NodeId = "0x0000000000000000000000000000000000000002"
+                .parse()
+                .unwrap();
+
+            let agreement = market::Agreement {
+                agreement_id: "agreement".to_string(),
+                demand: market::Demand {
+                    properties: Default::default(),
+                    constraints: "".to_string(),
+                    demand_id: None,
+                    requestor_id: Some(requestor_id.to_string()),
+                },
+                offer: market::Offer {
+                    properties: Default::default(),
+                    constraints: "".to_string(),
+                    offer_id: None,
+                    provider_id: Some(provider_id.to_string()),
+                },
+                valid_to: Utc::now(),
+                approved_date: None,
+                state: market::agreement::State::Proposal,
+                proposed_signature: None,
+                approved_signature: None,
+                committed_signature: None,
+            };
+            fake_get_agreement(agreement.agreement_id.clone(), agreement.clone());
+            db.as_dao::<AgreementDao>()
+                .create_if_not_exists(agreement, provider_id, Role::Provider)
+                .await
+                .unwrap();
+            let dao: InvoiceDao = db.as_dao();
+            let invoice_id = dao
+                .create_new(
+                    NewInvoice {
+                        agreement_id: "agreement".to_string(),
+                        activity_ids: None,
+                        amount: bigdecimal::BigDecimal::from(1u64),
+                        payment_due_date: Utc::now(),
+                    },
+                    provider_id,
+                )
+                .await
+                .unwrap();
+            let invoice = dao
+                .get(invoice_id.clone(), provider_id)
+                .await
+                .unwrap()
+                .unwrap();
+
+            // stands in for the requestor's service, reached over the network
+            let addr = ya_net::from(provider_id)
+                .to(requestor_id)
+                .service(BUS_ID)
+                .addr()
+                .to_string();
+            let requestor_db = db.clone();
+            let _ = bus::bind(&addr, move |msg: SendInvoice| {
+                send_invoice(requestor_db.clone(), provider_id.to_string(), msg)
+            });
+
+            // as if the acknowledgement of the first delivery was lost
+            for _ in 0..2 {
+                let msg = SendInvoice(invoice.clone());
+                match outbox::send(&db, provider_id, requestor_id, msg).await {
+                    Ok(Delivery::Delivered) => (),
+                    other => panic!("unexpected delivery: {:?}", other),
+                }
+            }
+            let received = dao.get(invoice_id, requestor_id).await.unwrap().unwrap();
+            assert_eq!(received.status, DocumentStatus::Received);
+            assert_eq!(received.amount, invoice.amount);
+        }
+    }
 }
@@ -178,6 +178,10 @@ pub mod response {
         HttpResponse::Created().json(t)
     }
 
+    pub fn accepted<T: Serialize>(t: T) -> HttpResponse {
+        HttpResponse::Accepted().json(t)
+    }
+
     pub fn not_implemented() -> HttpResponse {
         HttpResponse::NotImplemented().json(ErrorMessage { message: None })
     }
@@ -190,6 +194,10 @@ pub mod response {
         HttpResponse::Unauthorized().json(ErrorMessage { message: None })
     }
 
+    pub fn forbidden(e: &impl ToString) -> HttpResponse {
+        HttpResponse::Forbidden().json(ErrorMessage::new(e.to_string()))
+    }
+
     pub fn timeout() -> HttpResponse {
         HttpResponse::GatewayTimeout().json(ErrorMessage { message: None })
     }
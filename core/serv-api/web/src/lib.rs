@@ -1,5 +1,6 @@
 pub mod middleware;
 pub mod scope;
+pub mod tls;
 
 pub use ya_client::web::{rest_api_url, DEFAULT_YAGNA_API_URL, YAGNA_API_URL_ENV_VAR};
 
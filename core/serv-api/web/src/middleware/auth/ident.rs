@@ -8,13 +8,33 @@ use std::cell::Ref;
 use std::convert::TryFrom;
 use std::pin::Pin;
 use ya_client::model::NodeId;
-use ya_core_model::appkey::AppKey;
+use ya_core_model::appkey::{AppKey, Scope};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Identity {
+    /// Identity acted on behalf of, the app key's own one unless another is selected
+    /// with the `X-Yagna-Identity` header.
     pub identity: NodeId,
     pub name: String,
     pub role: String,
+    /// Resources the app key is restricted to, if any.
+    pub scope: Option<Scope>,
+}
+
+impl Identity {
+    pub fn is_agreement_allowed(&self, agreement_id: &str) -> bool {
+        match &self.scope {
+            Some(scope) => scope.agreements.iter().any(|id| id == agreement_id),
+            None => true,
+        }
+    }
+
+    pub fn is_allocation_allowed(&self, allocation_id: &str) -> bool {
+        match &self.scope {
+            Some(scope) => scope.allocations.iter().any(|id| id == allocation_id),
+            None => true,
+        }
+    }
 }
 
 impl From<AppKey> for Identity {
@@ -23,6 +43,7 @@ impl From<AppKey> for Identity {
             identity: app_key.identity,
             name: app_key.name,
             role: app_key.role,
+            scope: app_key.scope,
         }
     }
 }
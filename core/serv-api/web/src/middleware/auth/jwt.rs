@@ -0,0 +1,365 @@
+//! Bearer tokens issued by an external OAuth2 / OpenID Connect identity provider.
+//!
+//! Tokens are JWTs signed with one of the provider's RSA keys, published as a JSON Web
+//! Key Set in a file or at a URL. Keys are loaded on startup and reloaded when a token
+//! names an unknown key id. Besides `iss`, `aud` and `exp`, tokens may carry:
+//!
+//! * `yagna_identity` - node id of a local identity to act as, the default one if not given,
+//! * `yagna_role` - app key role to act with, `read-only` if not given.
+
+use actix_web::error::{Error, ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized};
+use futures::lock::Mutex;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ya_client::model::NodeId;
+use ya_core_model::appkey::READ_ONLY_ROLE;
+use ya_core_model::identity as idm;
+use ya_service_api_cache::ValueResolver;
+use ya_service_bus::actix_rpc;
+
+use crate::middleware::auth::resolver::IdentityResolver;
+use crate::middleware::auth::Identity;
+
+pub const JWT_ISSUER_ENV_VAR: &str = "YAGNA_JWT_ISSUER";
+pub const JWT_AUDIENCE_ENV_VAR: &str = "YAGNA_JWT_AUDIENCE";
+pub const JWT_JWKS_ENV_VAR: &str = "YAGNA_JWT_JWKS";
+
+/// Keys are not reloaded more often than this, whatever the tokens name.
+const JWKS_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+const RSA_ALGORITHMS: [Algorithm; 6] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    yagna_identity: Option<NodeId>,
+    #[serde(default)]
+    yagna_role: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Jwk {
+    #[serde(default)]
+    kid: Option<String>,
+    kty: String,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+enum JwksSource {
+    File(PathBuf),
+    Url(String),
+}
+
+impl JwksSource {
+    fn new(jwks: &str) -> Self {
+        if jwks.starts_with("https://") || jwks.starts_with("http://") {
+            JwksSource::Url(jwks.to_string())
+        } else {
+            JwksSource::File(PathBuf::from(jwks))
+        }
+    }
+
+    async fn load(&self) -> anyhow::Result<Vec<Jwk>> {
+        let set: JwkSet = match self {
+            JwksSource::File(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+            JwksSource::Url(url) => awc::Client::default()
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("fetching {}: {}", url, e))?
+                .json()
+                .await
+                .map_err(|e| anyhow::anyhow!("reading {}: {}", url, e))?,
+        };
+        Ok(set
+            .keys
+            .into_iter()
+            .filter(|key| key.kty == "RSA" && key.n.is_some() && key.e.is_some())
+            .collect())
+    }
+}
+
+struct Keys {
+    keys: Vec<Jwk>,
+    loaded: Instant,
+}
+
+pub struct JwtAuth {
+    issuer: String,
+    audience: String,
+    source: JwksSource,
+    keys: Mutex<Keys>,
+}
+
+impl JwtAuth {
+    /// Token authentication configured with `YAGNA_JWT_ISSUER`, `YAGNA_JWT_AUDIENCE`
+    /// and `YAGNA_JWT_JWKS`, if the latter is set.
+    pub async fn from_env() -> anyhow::Result<Option<Self>> {
+        let jwks = match std::env::var(JWT_JWKS_ENV_VAR) {
+            Ok(jwks) => jwks,
+            Err(_) => return Ok(None),
+        };
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| anyhow::anyhow!("{} is required with JWKS", name))
+        };
+        let auth = Self::new(var(JWT_ISSUER_ENV_VAR)?, var(JWT_AUDIENCE_ENV_VAR)?, &jwks).await?;
+        Ok(Some(auth))
+    }
+
+    /// Accepts tokens for `audience` from `issuer`, signed with keys from `jwks`,
+    /// a file path or URL.
+    pub async fn new(issuer: String, audience: String, jwks: &str) -> anyhow::Result<Self> {
+        let source = JwksSource::new(jwks);
+        let keys = source.load().await?;
+        log::info!("Loaded {} JWT signing keys from {}", keys.len(), jwks);
+        Ok(JwtAuth {
+            issuer,
+            audience,
+            source,
+            keys: Mutex::new(Keys {
+                keys,
+                loaded: Instant::now(),
+            }),
+        })
+    }
+
+    async fn key(&self, kid: Option<&str>) -> Option<Jwk> {
+        let mut keys = self.keys.lock().await;
+        let find = |keys: &Keys| {
+            keys.keys
+                .iter()
+                .find(|key| kid.is_none() || key.kid.as_deref() == kid)
+                .cloned()
+        };
+        if let Some(key) = find(&*keys) {
+            return Some(key);
+        }
+        if keys.loaded.elapsed() < JWKS_RELOAD_INTERVAL {
+            return None;
+        }
+        keys.loaded = Instant::now();
+        match self.source.load().await {
+            Ok(loaded) => keys.keys = loaded,
+            Err(e) => log::warn!("Failed to reload JWT signing keys: {}", e),
+        }
+        find(&*keys)
+    }
+
+    /// Validates `token`, giving the identity and role it grants.
+    pub async fn authenticate(&self, token: &str) -> Result<Identity, Error> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            log::debug!("Invalid token: {}", e);
+            ErrorUnauthorized(format!("Invalid token: {}", e))
+        };
+        let header = decode_header(token).map_err(invalid)?;
+        if !RSA_ALGORITHMS.contains(&header.alg) {
+            return Err(ErrorUnauthorized(format!(
+                "Unsupported token algorithm: {:?}",
+                header.alg
+            )));
+        }
+        let key = self
+            .key(header.kid.as_deref())
+            .await
+            .ok_or_else(|| ErrorUnauthorized("Unknown token signing key"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = RSA_ALGORITHMS.to_vec();
+        validation.iss = Some(self.issuer.clone());
+        validation.set_audience(&[&self.audience]);
+        let key = DecodingKey::from_rsa_components(
+            key.n.as_deref().unwrap_or_default(),
+            key.e.as_deref().unwrap_or_default(),
+        );
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+
+        let identity = match claims.yagna_identity {
+            Some(node_id) => local_identity(node_id).await?,
+            None => default_identity().await?,
+        };
+        Ok(Identity {
+            identity,
+            name: claims.sub,
+            role: claims
+                .yagna_role
+                .unwrap_or_else(|| READ_ONLY_ROLE.to_string()),
+            scope: None,
+        })
+    }
+}
+
+/// Tells apart JWTs from app keys.
+pub fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+async fn local_identity(node_id: NodeId) -> Result<NodeId, Error> {
+    IdentityResolver
+        .resolve(&node_id.to_string())
+        .await?
+        .ok_or_else(|| {
+            log::debug!("Token for unknown identity: {}", node_id);
+            ErrorForbidden(format!("Unknown identity: {}", node_id))
+        })
+}
+
+async fn default_identity() -> Result<NodeId, Error> {
+    actix_rpc::service(idm::BUS_ID)
+        .send(idm::Get::ByDefault)
+        .await
+        .map_err(|e| ErrorInternalServerError(format!("{}", e)))?
+        .map_err(|e| ErrorInternalServerError(format!("{}", e)))?
+        .map(|info| info.node_id)
+        .ok_or_else(|| ErrorInternalServerError("No default identity"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::tests::{bind_services, NODE_ID, OTHER_NODE_ID};
+    use actix_web::http::StatusCode;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use openssl::rsa::Rsa;
+    use serde_json::{json, Value};
+
+    const EXPIRES: u64 = 4102444800;
+
+    fn base64_url(bytes: &[u8]) -> String {
+        openssl::base64::encode_block(bytes)
+            .trim_end_matches('=')
+            .replace('+', "-")
+            .replace('/', "_")
+    }
+
+    /// Authentication with a new RSA key published as `kid`, and the key to sign with.
+    async fn test_auth(kid: &str) -> (JwtAuth, EncodingKey) {
+        let rsa = Rsa::generate(2048).unwrap();
+        let set = json!({"keys": [{
+            "kid": kid,
+            "kty": "RSA",
+            "n": base64_url(&rsa.n().to_vec()),
+            "e": base64_url(&rsa.e().to_vec()),
+        }]});
+        let jwks = std::env::temp_dir().join(format!("jwks-{}-{}.json", kid, std::process::id()));
+        std::fs::write(&jwks, set.to_string()).unwrap();
+        let auth = JwtAuth::new("idp".into(), "yagna".into(), jwks.to_str().unwrap())
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&jwks);
+
+        let key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+        (auth, key)
+    }
+
+    fn sign(kid: &str, key: &EncodingKey, claims: &Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.into());
+        encode(&header, claims, key).unwrap()
+    }
+
+    fn status(result: Result<Identity, Error>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn claims_give_identity_and_role() {
+        bind_services();
+        let (auth, key) = test_auth("claims").await;
+
+        let claims = json!({
+            "sub": "user", "iss": "idp", "aud": "yagna", "exp": EXPIRES,
+            "yagna_identity": OTHER_NODE_ID, "yagna_role": "requestor"
+        });
+        let identity = auth
+            .authenticate(&sign("claims", &key, &claims))
+            .await
+            .unwrap();
+        assert_eq!(identity.identity.to_string(), OTHER_NODE_ID);
+        assert_eq!(identity.name, "user");
+        assert_eq!(identity.role, "requestor");
+        assert!(identity.scope.is_none());
+
+        let claims = json!({"sub": "user", "iss": "idp", "aud": "yagna", "exp": EXPIRES});
+        let identity = auth
+            .authenticate(&sign("claims", &key, &claims))
+            .await
+            .unwrap();
+        assert_eq!(identity.identity.to_string(), NODE_ID);
+        assert_eq!(identity.role, READ_ONLY_ROLE);
+
+        let claims = json!({
+            "sub": "user", "iss": "idp", "aud": "yagna", "exp": EXPIRES,
+            "yagna_identity": "0x1111000000000000000000000000000000000000"
+        });
+        let result = auth.authenticate(&sign("claims", &key, &claims)).await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn rejects_expired_tokens_and_tokens_for_others() {
+        bind_services();
+        let (auth, key) = test_auth("rejected").await;
+
+        let claims = json!({"sub": "user", "iss": "idp", "aud": "yagna", "exp": EXPIRES});
+        let result = auth.authenticate(&sign("rejected", &key, &claims)).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        for claims in &[
+            json!({"sub": "user", "iss": "idp", "aud": "yagna", "exp": 946684800u64}),
+            json!({"sub": "user", "iss": "idp", "aud": "other", "exp": EXPIRES}),
+            json!({"sub": "user", "iss": "other", "aud": "yagna", "exp": EXPIRES}),
+        ] {
+            let result = auth.authenticate(&sign("rejected", &key, claims)).await;
+            assert_eq!(status(result), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn rejects_tokens_not_signed_with_jwks_keys() {
+        let jwks = std::env::temp_dir().join(format!("jwks-{}.json", std::process::id()));
+        // the symmetric key is not to be used, whatever the token says
+        let set = json!({"keys": [
+            {"kid": "k1", "kty": "RSA", "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri", "e": "AQAB"},
+            {"kid": "k2", "kty": "oct", "k": "c2VjcmV0"}
+        ]});
+        std::fs::write(&jwks, set.to_string()).unwrap();
+        let auth = JwtAuth::new("idp".into(), "yagna".into(), jwks.to_str().unwrap())
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&jwks);
+
+        let claims = json!({"sub": "user", "iss": "idp", "aud": "yagna", "exp": 4102444800u64});
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".into());
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+
+        assert!(is_jwt(&token));
+        assert!(!is_jwt("0123456789abcdef0123456789abcdef"));
+        assert!(auth.authenticate(&token).await.is_err());
+        assert!(auth.authenticate("a.b.c").await.is_err());
+    }
+}
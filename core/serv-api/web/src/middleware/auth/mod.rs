@@ -1,13 +1,20 @@
 pub mod dummy;
 pub mod ident;
+pub mod jwt;
 pub mod resolver;
+pub mod role;
+pub mod scope;
 
 pub use crate::middleware::auth::ident::Identity;
 
-use crate::middleware::auth::resolver::AppKeyResolver;
+use crate::middleware::auth::jwt::JwtAuth;
+use crate::middleware::auth::resolver::{
+    AppKeyNameResolver, AppKeyResolver, IdentityResolver, UsageRecorder,
+};
+use crate::tls::ClientCertificate;
 use actix_service::{Service, Transform};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::error::{Error, ErrorUnauthorized};
+use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden, ErrorUnauthorized};
 use actix_web::{http::header::Header, HttpMessage};
 use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
 use futures::future::{ok, Future, Ready};
@@ -17,18 +24,42 @@ use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use ya_service_api_cache::AutoResolveCache;
+use ya_core_model::appkey::AppKey;
+use ya_service_api_cache::{AutoResolveCache, ValueResolver};
+
+/// Header selecting another local identity, by node id or alias, to act on behalf of.
+pub const IDENTITY_HEADER: &str = "X-Yagna-Identity";
 
 pub type Cache = AutoResolveCache<AppKeyResolver>;
+pub type IdentityCache = AutoResolveCache<IdentityResolver>;
+pub type NameCache = AutoResolveCache<AppKeyNameResolver>;
 
 pub struct Auth {
     cache: Arc<Mutex<Cache>>,
+    names: Arc<Mutex<NameCache>>,
+    identities: Arc<Mutex<IdentityCache>>,
+    jwt: Option<Arc<JwtAuth>>,
 }
 
 impl Default for Auth {
     fn default() -> Self {
         let cache = Arc::new(Mutex::new(Cache::default()));
-        Auth { cache }
+        let names = Arc::new(Mutex::new(NameCache::default()));
+        let identities = Arc::new(Mutex::new(IdentityCache::default()));
+        Auth {
+            cache,
+            names,
+            identities,
+            jwt: None,
+        }
+    }
+}
+
+impl Auth {
+    /// Also accepts JWT bearer tokens validated by `jwt`, besides app keys.
+    pub fn with_jwt(mut self, jwt: Option<Arc<JwtAuth>>) -> Self {
+        self.jwt = jwt;
+        self
     }
 }
 
@@ -46,9 +77,15 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
+        let usage = Rc::new(RefCell::new(UsageRecorder::default()));
+        resolver::spawn_usage_flush(Rc::downgrade(&usage));
         ok(AuthMiddleware {
             service: Rc::new(RefCell::new(service)),
             cache: self.cache.clone(),
+            names: self.names.clone(),
+            identities: self.identities.clone(),
+            jwt: self.jwt.clone(),
+            usage,
         })
     }
 }
@@ -56,6 +93,10 @@ where
 pub struct AuthMiddleware<S> {
     service: Rc<RefCell<S>>,
     cache: Arc<Mutex<Cache>>,
+    names: Arc<Mutex<NameCache>>,
+    identities: Arc<Mutex<IdentityCache>>,
+    jwt: Option<Arc<JwtAuth>>,
+    usage: Rc<RefCell<UsageRecorder>>,
 }
 
 impl<S, B> Service for AuthMiddleware<S>
@@ -76,41 +117,317 @@ where
         let header = Authorization::<Bearer>::parse(&req)
             .ok()
             .map(|a| a.into_scheme().token().to_string());
+        let cert_name = req
+            .extensions()
+            .get::<ClientCertificate>()
+            .and_then(|cert| cert.common_name.clone());
+        let on_behalf_of = req
+            .headers()
+            .get(IDENTITY_HEADER)
+            .map(|value| value.to_str().map(str::to_string));
 
         let cache = self.cache.clone();
+        let names = self.names.clone();
+        let identities = self.identities.clone();
+        let jwt = self.jwt.clone();
+        let usage = self.usage.clone();
         let service = self.service.clone();
 
         Box::pin(async move {
-            match header {
-                Some(key) => {
-                    let cached = cache.lock().await.get(&key);
-                    let resolved = match cached {
-                        Some(opt) => opt,
-                        None => cache.lock().await.resolve(&key).await,
-                    };
-
-                    match resolved {
-                        Some(app_key) => {
-                            req.extensions_mut().insert(Identity::from(app_key));
-                            let fut = { service.borrow_mut().call(req) };
-                            Ok(fut.await?)
-                        }
-                        None => {
-                            log::debug!(
-                                "{} {} Invalid application key: {}",
-                                req.method(),
-                                req.path(),
-                                key
-                            );
-                            Err(ErrorUnauthorized("Invalid application key"))
-                        }
+            let (mut identity, by_app_key) = match (header, cert_name) {
+                (Some(token), _) => match jwt {
+                    Some(jwt) if jwt::is_jwt(&token) => (jwt.authenticate(&token).await?, false),
+                    _ => {
+                        let app_key = resolve_app_key(&req, &token, &cache).await?;
+                        (Identity::from(app_key), true)
                     }
+                },
+                (None, Some(name)) => {
+                    let app_key = resolve_app_key(&req, &name, &names).await?;
+                    (Identity::from(app_key), true)
                 }
-                None => {
+                (None, None) => {
                     log::debug!("Missing application key");
-                    Err(ErrorUnauthorized("Missing application key"))
+                    return Err(ErrorUnauthorized("Missing application key"));
+                }
+            };
+
+            if !role::is_permitted(&identity.role, req.method(), req.path()) {
+                log::debug!(
+                    "{} {} Not permitted for role: {}",
+                    req.method(),
+                    req.path(),
+                    identity.role
+                );
+                return Err(ErrorForbidden(format!(
+                    "Not permitted for role: {}",
+                    identity.role
+                )));
+            }
+            if let Some(scope) = &identity.scope {
+                if !scope::is_in_scope(scope, req.path()) {
+                    log::debug!(
+                        "{} {} Out of application key scope",
+                        req.method(),
+                        req.path()
+                    );
+                    return Err(ErrorForbidden("Out of application key scope"));
                 }
             }
+            if let Some(on_behalf_of) = on_behalf_of {
+                identity.identity = resolve_identity(&identity, on_behalf_of, &identities).await?;
+            }
+            if by_app_key {
+                usage.borrow_mut().record(&identity.name);
+            }
+
+            req.extensions_mut().insert(identity);
+            let fut = { service.borrow_mut().call(req) };
+            Ok(fut.await?)
         })
     }
 }
+
+/// Resolves an app key by its value or, with `NameCache`, by its name.
+async fn resolve_app_key<R>(
+    req: &ServiceRequest,
+    key: &String,
+    cache: &Mutex<AutoResolveCache<R>>,
+) -> Result<AppKey, Error>
+where
+    R: ValueResolver<Key = String, Value = AppKey>,
+    R::Error: std::fmt::Debug,
+{
+    let cached = cache.lock().await.get(key);
+    let resolved = match cached {
+        Some(opt) => opt,
+        None => cache.lock().await.resolve(key).await,
+    };
+
+    match resolved {
+        Some(app_key) if app_key.is_expired() => {
+            log::debug!(
+                "{} {} Expired application key: {}",
+                req.method(),
+                req.path(),
+                key
+            );
+            Err(ErrorUnauthorized("Application key expired"))
+        }
+        Some(app_key) => Ok(app_key),
+        None => {
+            log::debug!(
+                "{} {} Invalid application key: {}",
+                req.method(),
+                req.path(),
+                key
+            );
+            Err(ErrorUnauthorized("Invalid application key"))
+        }
+    }
+}
+
+async fn resolve_identity(
+    identity: &Identity,
+    on_behalf_of: Result<String, actix_web::http::header::ToStrError>,
+    identities: &Mutex<IdentityCache>,
+) -> Result<ya_client::model::NodeId, Error> {
+    let on_behalf_of =
+        on_behalf_of.map_err(|_| ErrorBadRequest(format!("Invalid {} header", IDENTITY_HEADER)))?;
+    if !role::allows_other_identities(&identity.role) || identity.scope.is_some() {
+        log::debug!(
+            "Application key {} may not act on behalf of {}",
+            identity.name,
+            on_behalf_of
+        );
+        return Err(ErrorForbidden(format!(
+            "Application key may not act on behalf of {}",
+            on_behalf_of
+        )));
+    }
+
+    let cached = identities.lock().await.get(&on_behalf_of);
+    let resolved = match cached {
+        Some(opt) => opt,
+        None => identities.lock().await.resolve(&on_behalf_of).await,
+    };
+    resolved.ok_or_else(|| ErrorBadRequest(format!("Unknown identity: {}", on_behalf_of)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Service as _;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App, HttpResponse};
+    use chrono::{Duration, Utc};
+    use ya_core_model::appkey as model;
+    use ya_core_model::identity as idm;
+    use ya_service_bus::typed as bus;
+
+    pub(super) const NODE_ID: &str = "0xbabe000000000000000000000000000000000000";
+    pub(super) const OTHER_NODE_ID: &str = "0xdead000000000000000000000000000000000000";
+
+    fn app_key(key: &str, role: &str) -> AppKey {
+        AppKey {
+            name: format!("{}-app", key),
+            key: key.to_string(),
+            role: role.to_string(),
+            identity: NODE_ID.parse().unwrap(),
+            created_date: Utc::now().naive_utc(),
+            expires_at: None,
+            last_used: None,
+            request_count: 0,
+            scope: None,
+        }
+    }
+
+    fn find_app_key(key: &str) -> Option<AppKey> {
+        match key {
+            "manager" => Some(app_key(key, model::DEFAULT_ROLE)),
+            "read-only" => Some(app_key(key, model::READ_ONLY_ROLE)),
+            "scoped" => Some(AppKey {
+                scope: Some(model::Scope::default()),
+                ..app_key(key, model::DEFAULT_ROLE)
+            }),
+            "expired" => Some(AppKey {
+                expires_at: Some(Utc::now().naive_utc() - Duration::hours(1)),
+                ..app_key(key, model::DEFAULT_ROLE)
+            }),
+            _ => None,
+        }
+    }
+
+    fn find_identity(get: idm::Get) -> Option<idm::IdentityInfo> {
+        let identity = |node_id: &str, alias: Option<&str>| idm::IdentityInfo {
+            alias: alias.map(str::to_string),
+            node_id: node_id.parse().unwrap(),
+            is_locked: false,
+            is_default: node_id == NODE_ID,
+        };
+        let identities = vec![
+            identity(NODE_ID, None),
+            identity(OTHER_NODE_ID, Some("other")),
+        ];
+        identities.into_iter().find(|info| match &get {
+            idm::Get::ByNodeId(node_id) => &info.node_id == node_id,
+            idm::Get::ByAlias(alias) => info.alias.as_ref() == Some(alias),
+            idm::Get::ByDefault => info.is_default,
+        })
+    }
+
+    /// Stands in for the app key and identity services, knowing the keys found by
+    /// `find_app_key` and the default identity `NODE_ID` besides `OTHER_NODE_ID`
+    /// aliased `other`.
+    pub(super) fn bind_services() {
+        let _ = bus::bind(model::BUS_ID, |get: model::Get| async move {
+            find_app_key(&get.key).ok_or_else(|| model::Error {
+                code: 404,
+                message: "Not found".to_string(),
+            })
+        });
+        let _ = bus::bind(model::BUS_ID, |get: model::GetByName| async move {
+            let key = get.name.trim_end_matches("-app");
+            find_app_key(key)
+                .filter(|app_key| app_key.name == get.name)
+                .ok_or_else(|| model::Error {
+                    code: 404,
+                    message: "Not found".to_string(),
+                })
+        });
+        let _ = bus::bind(idm::BUS_ID, |get: idm::Get| async move {
+            Ok::<_, idm::Error>(find_identity(get))
+        });
+    }
+
+    /// Status of the response to `req` and either the identity acted on behalf of
+    /// or the error message.
+    async fn status_of(req: test::TestRequest) -> (StatusCode, String) {
+        respond(req.uri("/me").to_request()).await
+    }
+
+    async fn respond(req: actix_http::Request) -> (StatusCode, String) {
+        bind_services();
+        let mut app =
+            test::init_service(App::new().wrap(Auth::default()).route(
+                "/me",
+                web::get().to(|id: Identity| async move {
+                    HttpResponse::Ok().body(id.identity.to_string())
+                }),
+            ))
+            .await;
+
+        match app.call(req).await {
+            Ok(resp) => {
+                let status = resp.status();
+                let body = test::read_body(resp).await;
+                (status, String::from_utf8(body.to_vec()).unwrap())
+            }
+            Err(e) => (e.as_response_error().status_code(), e.to_string()),
+        }
+    }
+
+    fn with_key(key: &str) -> test::TestRequest {
+        test::TestRequest::get().header("Authorization", format!("Bearer {}", key))
+    }
+
+    #[actix_rt::test]
+    async fn expired_app_key_is_rejected() {
+        let (status, _) = status_of(with_key("manager")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, message) = status_of(with_key("expired")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(message, "Application key expired");
+
+        let (status, message) = status_of(with_key("unknown")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(message, "Invalid application key");
+    }
+
+    #[actix_rt::test]
+    async fn other_identity_is_selected_by_manager_keys_only() {
+        let on_behalf_of =
+            |key: &str, identity: &str| with_key(key).header(IDENTITY_HEADER, identity);
+
+        let (status, identity) = status_of(with_key("manager")).await;
+        assert_eq!((status, identity.as_str()), (StatusCode::OK, NODE_ID));
+        for other in &["other", OTHER_NODE_ID] {
+            let (status, identity) = status_of(on_behalf_of("manager", other)).await;
+            assert_eq!((status, identity.as_str()), (StatusCode::OK, OTHER_NODE_ID));
+        }
+
+        let (status, _) = status_of(on_behalf_of("manager", "unknown")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        for key in &["read-only", "scoped"] {
+            let (status, message) = status_of(on_behalf_of(key, "other")).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(message, "Application key may not act on behalf of other");
+        }
+    }
+
+    fn with_certificate(common_name: &str) -> actix_http::Request {
+        let req = test::TestRequest::get().uri("/me").to_request();
+        req.extensions_mut().insert(ClientCertificate {
+            common_name: Some(common_name.to_string()),
+        });
+        req
+    }
+
+    #[actix_rt::test]
+    async fn client_certificate_names_app_key() {
+        let (status, identity) = respond(with_certificate("manager-app")).await;
+        assert_eq!((status, identity.as_str()), (StatusCode::OK, NODE_ID));
+
+        let (status, message) = respond(with_certificate("expired-app")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(message, "Application key expired");
+
+        let (status, message) = respond(with_certificate("manager")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(message, "Invalid application key");
+
+        let (status, _) = respond(test::TestRequest::get().uri("/me").to_request()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
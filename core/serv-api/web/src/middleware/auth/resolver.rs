@@ -1,8 +1,15 @@
 use actix_web::error::ErrorInternalServerError;
 use actix_web::Error;
+use chrono::Utc;
 use futures::{Future, TryFutureExt};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::pin::Pin;
-use ya_core_model::appkey::{self, AppKey, Get};
+use std::rc::Weak;
+use std::time::Duration;
+use ya_client::model::NodeId;
+use ya_core_model::appkey::{self, AppKey, Get, GetByName, KeyUsage, RecordUsage};
+use ya_core_model::identity as idm;
 use ya_service_api_cache::ValueResolver;
 use ya_service_bus::actix_rpc;
 
@@ -33,3 +40,133 @@ impl ValueResolver for AppKeyResolver {
         })
     }
 }
+
+/// Resolves app keys by their names, given as TLS client certificate subjects.
+#[derive(Default)]
+pub struct AppKeyNameResolver;
+
+impl ValueResolver for AppKeyNameResolver {
+    type Key = String;
+    type Value = AppKey;
+    type Error = Error;
+
+    fn resolve<'a>(
+        &self,
+        name: &Self::Key,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Self::Value>, Self::Error>> + 'a>> {
+        let name = name.clone();
+        Box::pin(async move {
+            let resp = actix_rpc::service(appkey::BUS_ID)
+                .send(GetByName { name })
+                .map_err(|e| ErrorInternalServerError(format!("{}", e)))
+                .await?;
+            Ok(resp.ok())
+        })
+    }
+}
+
+/// Resolves node ids or aliases of local identities given in `X-Yagna-Identity`.
+#[derive(Default)]
+pub struct IdentityResolver;
+
+impl ValueResolver for IdentityResolver {
+    type Key = String;
+    type Value = NodeId;
+    type Error = Error;
+
+    fn resolve<'a>(
+        &self,
+        key: &Self::Key,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Self::Value>, Self::Error>> + 'a>> {
+        let get = match key.parse() {
+            Ok(node_id) => idm::Get::ByNodeId(node_id),
+            Err(_) => idm::Get::ByAlias(key.clone()),
+        };
+        Box::pin(async move {
+            let resp = actix_rpc::service(idm::BUS_ID)
+                .send(get)
+                .map_err(|e| ErrorInternalServerError(format!("{}", e)))
+                .await?;
+            Ok(resp.ok().flatten().map(|info| info.node_id))
+        })
+    }
+}
+
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Requests authorized with each app key, kept in memory until flushed
+/// with `spawn_usage_flush`. Keys are told apart by name, which survives rotation.
+#[derive(Default)]
+pub struct UsageRecorder {
+    pending: HashMap<String, KeyUsage>,
+}
+
+impl UsageRecorder {
+    pub fn record(&mut self, name: &str) {
+        let now = Utc::now().naive_utc();
+        let usage = self
+            .pending
+            .entry(name.to_string())
+            .or_insert_with(|| KeyUsage {
+                name: name.to_string(),
+                requests: 0,
+                last_used: now,
+            });
+        usage.requests += 1;
+        usage.last_used = now;
+    }
+
+    pub fn take(&mut self) -> Vec<KeyUsage> {
+        self.pending.drain().map(|(_, usage)| usage).collect()
+    }
+}
+
+/// Periodically sends usage noted by `recorder` to the app key service,
+/// until the recorder is dropped.
+pub(crate) fn spawn_usage_flush(recorder: Weak<RefCell<UsageRecorder>>) {
+    actix_rt::spawn(async move {
+        loop {
+            actix_rt::time::delay_for(USAGE_FLUSH_INTERVAL).await;
+            let usage = match recorder.upgrade() {
+                Some(recorder) => recorder.borrow_mut().take(),
+                None => break,
+            };
+            if !usage.is_empty() {
+                record_usage(usage).await;
+            }
+        }
+    })
+}
+
+/// Updates last used times and request counts of app keys.
+async fn record_usage(usage: Vec<KeyUsage>) {
+    match actix_rpc::service(appkey::BUS_ID)
+        .send(RecordUsage { usage })
+        .await
+    {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => log::warn!("Failed to record application key usage: {}", e),
+        Err(e) => log::warn!("Failed to record application key usage: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_accumulated_per_key() {
+        let mut recorder = UsageRecorder::default();
+        recorder.record("app-a");
+        recorder.record("app-b");
+        recorder.record("app-a");
+
+        let mut usage = recorder.take();
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(usage.len(), 2);
+        assert_eq!((usage[0].name.as_str(), usage[0].requests), ("app-a", 2));
+        assert_eq!((usage[1].name.as_str(), usage[1].requests), ("app-b", 1));
+        assert!(usage[0].last_used >= usage[1].last_used);
+        assert!(recorder.take().is_empty());
+    }
+}
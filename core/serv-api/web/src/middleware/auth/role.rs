@@ -0,0 +1,171 @@
+//! Permissions of app key roles on REST API scopes.
+//!
+//! Every request falls into one or more API scopes by its path and method. A role is
+//! allowed to read (`GET`, `HEAD`, `OPTIONS`) or also write to each scope. Paths outside
+//! the market, activity and payment APIs are readable by all roles and writable by
+//! the manager only. Roles not known here have no permissions at all.
+
+use actix_web::http::Method;
+
+use ya_client::model::activity::ACTIVITY_API_PATH;
+use ya_client::model::market::MARKET_API_PATH;
+use ya_client::model::payment::PAYMENT_API_PATH;
+use ya_core_model::appkey::{DEFAULT_ROLE, PROVIDER_ROLE, READ_ONLY_ROLE, REQUESTOR_ROLE, ROLES};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiScope {
+    MarketRequestor,
+    MarketProvider,
+    ActivityControl,
+    ActivityState,
+    PaymentRequestor,
+    PaymentProvider,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    None,
+    Read,
+    Write,
+}
+
+impl Access {
+    fn of(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Access::Read,
+            _ => Access::Write,
+        }
+    }
+}
+
+/// Access of `role` to `scope`.
+pub fn access(role: &str, scope: ApiScope) -> Access {
+    use ApiScope::*;
+
+    match role {
+        DEFAULT_ROLE => Access::Write,
+        REQUESTOR_ROLE => match scope {
+            MarketRequestor | ActivityControl | PaymentRequestor => Access::Write,
+            ActivityState => Access::Read,
+            MarketProvider | PaymentProvider => Access::None,
+        },
+        PROVIDER_ROLE => match scope {
+            MarketProvider | ActivityState | PaymentProvider => Access::Write,
+            MarketRequestor | ActivityControl | PaymentRequestor => Access::None,
+        },
+        READ_ONLY_ROLE => Access::Read,
+        _ => Access::None,
+    }
+}
+
+/// Scopes a request belongs to; it is enough for a role to be permitted on any of them.
+/// `None` for paths outside the scoped APIs.
+pub fn api_scopes(method: &Method, path: &str) -> Option<&'static [ApiScope]> {
+    use ApiScope::*;
+
+    if let Some(path) = strip_api_path(path, MARKET_API_PATH) {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        return Some(match segments.as_slice() {
+            ["demands", ..] => &[MarketRequestor],
+            ["offers", ..] => &[MarketProvider],
+            ["agreements"] => &[MarketRequestor],
+            ["agreements", _, "confirm"] | ["agreements", _, "wait"] => &[MarketRequestor],
+            ["agreements", _] if *method == Method::DELETE => &[MarketRequestor],
+            ["agreements", _, "approve"] | ["agreements", _, "reject"] => &[MarketProvider],
+            _ => &[MarketRequestor, MarketProvider],
+        });
+    }
+    if let Some(path) = strip_api_path(path, ACTIVITY_API_PATH) {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        return Some(match segments.as_slice() {
+            ["activity", _, "state"] | ["activity", _, "usage"] | ["activity", _, "command"] => {
+                &[ActivityState]
+            }
+            ["events"] => &[ActivityState],
+            _ => &[ActivityControl],
+        });
+    }
+    if let Some(path) = strip_api_path(path, PAYMENT_API_PATH) {
+        return Some(if path.starts_with("/provider") {
+            &[PaymentProvider]
+        } else {
+            &[PaymentRequestor]
+        });
+    }
+    None
+}
+
+pub(crate) fn strip_api_path<'a>(path: &'a str, api_path: &str) -> Option<&'a str> {
+    let api_path = api_path.trim_end_matches('/');
+    if !path.starts_with(api_path) {
+        return None;
+    }
+    let rest = &path[api_path.len()..];
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Tells if keys of `role` may act on behalf of other local identities than their own.
+pub fn allows_other_identities(role: &str) -> bool {
+    role == DEFAULT_ROLE
+}
+
+/// Tells if `role` may call `method` on `path`.
+pub fn is_permitted(role: &str, method: &Method, path: &str) -> bool {
+    let required = Access::of(method);
+    match api_scopes(method, path) {
+        Some(scopes) => scopes.iter().any(|scope| access(role, *scope) >= required),
+        None => role == DEFAULT_ROLE || (required == Access::Read && ROLES.contains(&role)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permitted(role: &str, method: Method, api_path: &str, path: &str) -> bool {
+        let path = format!("{}{}", api_path.trim_end_matches('/'), path);
+        is_permitted(role, &method, &path)
+    }
+
+    #[test]
+    fn roles_are_limited_to_their_scopes() {
+        let (market, activity, payment) = (MARKET_API_PATH, ACTIVITY_API_PATH, PAYMENT_API_PATH);
+
+        for role in &[DEFAULT_ROLE, REQUESTOR_ROLE] {
+            assert!(permitted(role, Method::POST, market, "/demands"));
+            assert!(permitted(role, Method::POST, activity, "/activity"));
+        }
+        assert!(!permitted(PROVIDER_ROLE, Method::POST, market, "/demands"));
+        assert!(permitted(PROVIDER_ROLE, Method::POST, market, "/offers"));
+        let approve = "/agreements/a/approve";
+        assert!(!permitted(REQUESTOR_ROLE, Method::POST, market, approve));
+        let terminate = "/agreements/a/terminate";
+        assert!(permitted(PROVIDER_ROLE, Method::POST, market, terminate));
+        let state = "/activity/a/state";
+        assert!(permitted(REQUESTOR_ROLE, Method::GET, activity, state));
+        assert!(!permitted(REQUESTOR_ROLE, Method::PUT, activity, state));
+        assert!(permitted(PROVIDER_ROLE, Method::PUT, activity, state));
+        let allocations = "/requestor/allocations";
+        assert!(!permitted(
+            PROVIDER_ROLE,
+            Method::POST,
+            payment,
+            allocations
+        ));
+
+        let invoices = "/provider/invoices";
+        assert!(permitted(READ_ONLY_ROLE, Method::GET, payment, invoices));
+        assert!(!permitted(READ_ONLY_ROLE, Method::POST, payment, invoices));
+        assert!(permitted(
+            READ_ONLY_ROLE,
+            Method::GET,
+            "/net-api/v1",
+            "/status"
+        ));
+        assert!(!permitted("unknown", Method::GET, market, "/offers"));
+    }
+}
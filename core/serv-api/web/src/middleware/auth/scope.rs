@@ -0,0 +1,84 @@
+//! Restrictions of scoped app keys, see `ya_core_model::appkey::Scope`.
+//!
+//! Market subscriptions and agreements, and payment allocations are checked here by
+//! their ids in request paths. Activities and invoice or debit note acceptances name
+//! their agreement or allocation elsewhere, so they are let through to be checked by
+//! the handlers with `Identity::is_agreement_allowed` and `is_allocation_allowed`.
+//! Other requests to these APIs, like listing all events or creating subscriptions,
+//! are rejected.
+
+use ya_client::model::activity::ACTIVITY_API_PATH;
+use ya_client::model::market::MARKET_API_PATH;
+use ya_client::model::payment::PAYMENT_API_PATH;
+use ya_core_model::appkey::Scope;
+
+use super::role::strip_api_path;
+
+/// Tells if a key restricted to `scope` may request `path`.
+pub fn is_in_scope(scope: &Scope, path: &str) -> bool {
+    let listed = |ids: &[String], id: &str| ids.iter().any(|listed| listed == id);
+
+    if let Some(path) = strip_api_path(path, MARKET_API_PATH) {
+        return match segments(path).as_slice() {
+            ["demands", id, ..] | ["offers", id, ..] => listed(&scope.subscriptions, id),
+            ["agreements", id, ..] => listed(&scope.agreements, id),
+            _ => false,
+        };
+    }
+    if let Some(path) = strip_api_path(path, ACTIVITY_API_PATH) {
+        return match segments(path).as_slice() {
+            ["activity", ..] => true,
+            _ => false,
+        };
+    }
+    if let Some(path) = strip_api_path(path, PAYMENT_API_PATH) {
+        return match segments(path).as_slice() {
+            ["requestor", "allocations", id] => listed(&scope.allocations, id),
+            ["requestor", "invoices", _, "accept"] | ["requestor", "debitNotes", _, "accept"] => {
+                true
+            }
+            _ => false,
+        };
+    }
+    true
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_key_reaches_listed_resources_only() {
+        let scope = Scope {
+            subscriptions: vec!["s1".into()],
+            agreements: vec!["a1".into()],
+            allocations: vec!["x1".into()],
+        };
+        let market = MARKET_API_PATH.trim_end_matches('/');
+        let payment = PAYMENT_API_PATH.trim_end_matches('/');
+
+        assert!(is_in_scope(
+            &scope,
+            &format!("{}/demands/s1/events", market)
+        ));
+        assert!(!is_in_scope(
+            &scope,
+            &format!("{}/demands/s2/events", market)
+        ));
+        assert!(!is_in_scope(&scope, &format!("{}/demands", market)));
+        assert!(is_in_scope(&scope, &format!("{}/agreements/a1", market)));
+        assert!(!is_in_scope(&scope, &format!("{}/agreements/a2", market)));
+        let allocation = format!("{}/requestor/allocations/x1", payment);
+        assert!(is_in_scope(&scope, &allocation));
+        assert!(!is_in_scope(
+            &scope,
+            &format!("{}/requestor/allocations", payment)
+        ));
+        let accept = format!("{}/requestor/invoices/i1/accept", payment);
+        assert!(is_in_scope(&scope, &accept));
+    }
+}
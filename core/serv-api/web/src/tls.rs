@@ -0,0 +1,94 @@
+//! REST API over HTTPS, optionally verifying client certificates.
+//!
+//! A client presenting a certificate issued by one of the configured CAs, with the name
+//! of an app key as its subject common name, is authenticated as that app key by
+//! `middleware::Auth` without a bearer token.
+
+use actix_http::body::MessageBody;
+use actix_http::{Error, HttpService, Request, Response};
+use actix_rt::net::TcpStream;
+use actix_service::{map_config, IntoServiceFactory, ServiceFactory};
+use actix_tls::openssl::SslStream;
+use actix_web::dev::AppConfig;
+use openssl::nid::Nid;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslRef, SslVerifyMode};
+use std::fmt::Debug;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::path::Path;
+use url::Url;
+
+use crate::rest_api_host_port;
+
+pub const TLS_CERT_ENV_VAR: &str = "YAGNA_TLS_CERT";
+pub const TLS_KEY_ENV_VAR: &str = "YAGNA_TLS_KEY";
+pub const TLS_CLIENT_CA_ENV_VAR: &str = "YAGNA_TLS_CLIENT_CA";
+
+/// Client certificate verified during the TLS handshake, in request extensions of
+/// servers started with `bind`.
+#[derive(Clone, Debug, Default)]
+pub struct ClientCertificate {
+    pub common_name: Option<String>,
+}
+
+impl ClientCertificate {
+    fn of(ssl: &SslRef) -> Self {
+        let common_name = ssl.peer_certificate().and_then(|cert| {
+            cert.subject_name()
+                .entries_by_nid(Nid::COMMONNAME)
+                .next()
+                .and_then(|entry| entry.data().as_utf8().ok())
+                .map(|name| name.to_string())
+        });
+        ClientCertificate { common_name }
+    }
+}
+
+/// Acceptor of connections with the PEM `cert` chain and `key`. With `client_ca`,
+/// certificates clients choose to present must be issued by one of the CAs in it.
+pub fn acceptor(cert: &Path, key: &Path, client_ca: Option<&Path>) -> anyhow::Result<SslAcceptor> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_certificate_chain_file(cert)?;
+    builder.set_private_key_file(key, SslFiletype::PEM)?;
+    builder.check_private_key()?;
+    if let Some(client_ca) = client_ca {
+        builder.set_ca_file(client_ca)?;
+        builder.set_verify(SslVerifyMode::PEER);
+    }
+    Ok(builder.build())
+}
+
+/// HTTPS counterpart of `HttpServer::new(factory).bind(addr)` for the REST API at `api_url`,
+/// exposing client certificates to the apps as `ClientCertificate`.
+pub fn bind<F, I, S, B>(
+    factory: F,
+    api_url: &Url,
+    acceptor: SslAcceptor,
+) -> std::io::Result<actix_server::Server>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S>,
+    S: ServiceFactory<Config = AppConfig, Request = Request>,
+    S::Error: Into<Error> + 'static,
+    S::InitError: Debug,
+    S::Response: Into<Response<B>> + 'static,
+    B: MessageBody + 'static,
+{
+    // like `HttpServer`, apps see the address each listener is bound to
+    let host = rest_api_host_port(api_url.clone());
+    let mut server = actix_server::Server::build();
+    for addr in host.to_socket_addrs()? {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let (factory, acceptor, host) = (factory.clone(), acceptor.clone(), host.clone());
+        server = server.listen("yagna-api", listener, move || {
+            let host = host.clone();
+            HttpService::build()
+                .on_connect(|io: &SslStream<TcpStream>| ClientCertificate::of(io.ssl()))
+                .finish(map_config(factory(), move |_| {
+                    AppConfig::new(true, local_addr, host.clone())
+                }))
+                .openssl(acceptor.clone())
+        })?;
+    }
+    Ok(server.run())
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use structopt::{clap, StructOpt};
+
+use ya_core_model::bus as model;
+use ya_service_api::{CliCtx, CommandOutput, ResponseTable};
+use ya_service_bus::{typed as bus, RpcEndpoint};
+
+#[derive(StructOpt, Debug)]
+#[structopt(setting = clap::AppSettings::DeriveDisplayOrder)]
+/// Service bus introspection
+pub enum BusCommand {
+    /// Lists registered endpoints with their owners
+    Endpoints,
+    /// Lists topic subscriptions
+    Subscriptions,
+    /// Lists calls awaiting reply
+    Calls,
+    /// Shows call counts and latency histograms per endpoint
+    Stats,
+    /// Logs calls to addresses with given prefixes on the router
+    Trace {
+        /// Address prefixes, e.g. /local/identity
+        prefixes: Vec<String>,
+        /// Disables tracing
+        #[structopt(long, conflicts_with = "prefixes")]
+        off: bool,
+    },
+}
+
+impl BusCommand {
+    pub async fn run_command(self, _ctx: &CliCtx) -> Result<CommandOutput> {
+        Ok(match self {
+            BusCommand::Endpoints => ResponseTable {
+                columns: vec!["service id".into(), "owner".into()],
+                values: get_status()
+                    .await?
+                    .endpoints
+                    .into_iter()
+                    .map(|e| serde_json::json! {[e.service_id, e.owner]})
+                    .collect(),
+            }
+            .into(),
+            BusCommand::Subscriptions => ResponseTable {
+                columns: vec!["topic".into(), "subscribers".into()],
+                values: get_status()
+                    .await?
+                    .subscriptions
+                    .into_iter()
+                    .map(|s| serde_json::json! {[s.topic, s.subscribers.join(", ")]})
+                    .collect(),
+            }
+            .into(),
+            BusCommand::Calls => ResponseTable {
+                columns: vec![
+                    "request id".into(),
+                    "caller".into(),
+                    "address".into(),
+                    "age [ms]".into(),
+                ],
+                values: get_status()
+                    .await?
+                    .pending_calls
+                    .into_iter()
+                    .map(|c| serde_json::json! {[c.request_id, c.caller, c.address, c.age_ms]})
+                    .collect(),
+            }
+            .into(),
+            BusCommand::Stats => {
+                let mut columns: Vec<String> = vec![
+                    "address".into(),
+                    "calls".into(),
+                    "failures".into(),
+                    "avg [ms]".into(),
+                ];
+                columns.extend(
+                    model::LATENCY_BUCKETS_MS
+                        .iter()
+                        .map(|bound| format!("<={}ms", bound)),
+                );
+                columns.extend(
+                    model::LATENCY_BUCKETS_MS
+                        .last()
+                        .map(|bound| format!(">{}ms", bound)),
+                );
+                ResponseTable {
+                    columns,
+                    values: get_status()
+                        .await?
+                        .call_stats
+                        .into_iter()
+                        .map(|s| {
+                            let mut row = vec![
+                                serde_json::json!(s.address),
+                                serde_json::json!(s.calls),
+                                serde_json::json!(s.failures),
+                                serde_json::json!(s.avg_ms()),
+                            ];
+                            row.extend(
+                                s.latency_histogram.into_iter().map(serde_json::Value::from),
+                            );
+                            serde_json::Value::Array(row)
+                        })
+                        .collect(),
+                }
+                .into()
+            }
+            BusCommand::Trace { prefixes, off } => {
+                if prefixes.is_empty() && !off {
+                    CommandOutput::object(get_status().await?.trace)?
+                } else {
+                    let prefixes = bus::service(model::BUS_ID)
+                        .send(model::SetTrace::with_prefixes(prefixes))
+                        .await
+                        .map_err(anyhow::Error::msg)??;
+                    CommandOutput::object(prefixes)?
+                }
+            }
+        })
+    }
+}
+
+async fn get_status() -> Result<model::BusStatus> {
+    Ok(bus::service(model::BUS_ID)
+        .send(model::GetStatus::default())
+        .await
+        .map_err(anyhow::Error::msg)??)
+}
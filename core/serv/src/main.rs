@@ -7,6 +7,7 @@ use std::{
     env,
     fmt::Debug,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 use structopt::{clap, StructOpt};
 use url::Url;
@@ -31,12 +32,14 @@ use ya_service_api::{CliCtx, CommandOutput};
 use ya_service_api_interfaces::Provider;
 use ya_service_api_web::{
     middleware::{auth, Identity},
-    rest_api_host_port, DEFAULT_YAGNA_API_URL, YAGNA_API_URL_ENV_VAR,
+    rest_api_host_port, tls, DEFAULT_YAGNA_API_URL, YAGNA_API_URL_ENV_VAR,
 };
 use ya_utils_path::data_dir::DataDir;
 
 mod autocomplete;
+mod bus;
 use autocomplete::CompleteCommand;
+use bus::BusCommand;
 
 lazy_static::lazy_static! {
     static ref DEFAULT_DATA_DIR: String = DataDir::new(clap::crate_name!()).to_string();
@@ -172,7 +175,7 @@ impl ServiceContext {
 enum Services {
     #[enable(gsb, cli(flatten))]
     Identity(IdentityService),
-    #[enable(gsb)]
+    #[enable(gsb, rest, cli)]
     Net(NetService),
     #[enable(gsb, rest)]
     Market(MarketService),
@@ -211,6 +214,8 @@ enum CliCommand {
     /// Core service usage
     #[structopt(setting = clap::AppSettings::DeriveDisplayOrder)]
     Service(ServiceCommand),
+
+    Bus(BusCommand),
 }
 
 impl CliCommand {
@@ -219,6 +224,7 @@ impl CliCommand {
             CliCommand::Commands(command) => command.run_command(ctx).await,
             CliCommand::Complete(complete) => complete.run_command(ctx),
             CliCommand::Service(service) => service.run_command(ctx).await,
+            CliCommand::Bus(bus) => bus.run_command(ctx).await,
         }
     }
 }
@@ -246,6 +252,19 @@ struct ServiceCommandOpts {
         hide_env_values = true,
     )]
     api_url: Url,
+
+    /// PEM certificate chain to serve the REST API over HTTPS with
+    #[structopt(long, env = tls::TLS_CERT_ENV_VAR, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the HTTPS certificate
+    #[structopt(long, env = tls::TLS_KEY_ENV_VAR, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// PEM CAs to verify client certificates with, which then authenticate
+    /// as the app keys named by their subject common names
+    #[structopt(long, env = tls::TLS_CLIENT_CA_ENV_VAR, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
 }
 
 impl ServiceCommand {
@@ -254,7 +273,12 @@ impl ServiceCommand {
             prompt_terms()?;
         }
         match self {
-            Self::Run(ServiceCommandOpts { api_url }) => {
+            Self::Run(ServiceCommandOpts {
+                api_url,
+                tls_cert,
+                tls_key,
+                tls_client_ca,
+            }) => {
                 let name = clap::crate_name!();
                 log::info!("Starting {} service!", name);
 
@@ -275,18 +299,39 @@ impl ServiceCommand {
                     .await
                     .unwrap_or_else(|e| log::error!("Initializing payment accounts failed: {}", e));
 
+                let jwt = auth::jwt::JwtAuth::from_env()
+                    .await
+                    .context("loading JWT signing keys")?
+                    .map(Arc::new);
+
                 let api_host_port = rest_api_host_port(api_url.clone());
-                HttpServer::new(move || {
+                let app_factory = move || {
                     let app = App::new()
                         .wrap(middleware::Logger::default())
-                        .wrap(auth::Auth::default())
+                        .wrap(auth::Auth::default().with_jwt(jwt.clone()))
                         .route("/me", web::get().to(me));
                     Services::rest(app, &context)
-                })
-                .bind(api_host_port.clone())
-                .context(format!("Failed to bind http server on {:?}", api_host_port))?
-                .run()
-                .await?;
+                };
+                match (tls_cert, tls_key) {
+                    (Some(cert), Some(key)) => {
+                        let acceptor = tls::acceptor(&cert, &key, tls_client_ca.as_deref())
+                            .context("configuring TLS")?;
+                        log::info!("Serving REST API over HTTPS");
+                        tls::bind(app_factory, &api_url, acceptor)
+                            .context(format!(
+                                "Failed to bind https server on {:?}",
+                                api_host_port
+                            ))?
+                            .await?;
+                    }
+                    _ => {
+                        HttpServer::new(app_factory)
+                            .bind(api_host_port.clone())
+                            .context(format!("Failed to bind http server on {:?}", api_host_port))?
+                            .run()
+                            .await?;
+                    }
+                }
 
                 log::info!("{} service finished!", name);
                 Ok(CommandOutput::object(format!(
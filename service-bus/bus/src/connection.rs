@@ -8,17 +8,22 @@ use std::{
     collections::{HashMap, VecDeque},
     convert::TryInto,
     pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, SystemTime},
 };
 
 use ya_sb_proto::codec::{GsbMessage, ProtocolError};
 use ya_sb_proto::{
-    BroadcastReplyCode, BroadcastRequest, CallReply, CallReplyCode, CallReplyType, CallRequest,
-    RegisterReplyCode, RegisterRequest, SubscribeReplyCode, SubscribeRequest, UnregisterReplyCode,
-    UnregisterRequest, UnsubscribeReplyCode, UnsubscribeRequest,
+    BroadcastReplyCode, BroadcastRequest, CallCredit, CallReply, CallReplyCode, CallReplyType,
+    CallRequest, CancelCall, HelloReplyCode, HelloRequest, RegisterReplyCode, RegisterRequest,
+    SubscribeReplyCode, SubscribeRequest, UnregisterReplyCode, UnregisterRequest,
+    UnsubscribeReplyCode, UnsubscribeRequest,
 };
 
 use crate::local_router::router;
-use crate::Error;
+use crate::serialization::NegotiatedCodec;
+use crate::{Codec, Error};
 use crate::{ResponseChunk, RpcRawCall, RpcRawStreamCall};
 
 fn gen_id() -> u64 {
@@ -29,6 +34,26 @@ fn gen_id() -> u64 {
     rng.gen::<u64>() & 0x1f_ff_ff__ff_ff_ff_ffu64
 }
 
+/// Deadlines travel as time left, so that clocks of the nodes do not need to agree.
+/// A deadline already passed is sent as the shortest timeout, not as none.
+fn deadline_to_timeout(deadline: Option<SystemTime>) -> u64 {
+    deadline
+        .map(|deadline| {
+            let left = deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            (left.as_millis() as u64).max(1)
+        })
+        .unwrap_or_default()
+}
+
+fn deadline_from_timeout(timeout_ms: u64) -> Option<SystemTime> {
+    match timeout_ms {
+        0 => None,
+        timeout_ms => Some(SystemTime::now() + Duration::from_millis(timeout_ms)),
+    }
+}
+
 pub trait CallRequestHandler {
     type Reply: Stream<Item = Result<ResponseChunk, Error>> + Unpin;
 
@@ -40,6 +65,12 @@ pub trait CallRequestHandler {
         data: Vec<u8>,
     ) -> Self::Reply;
 
+    /// Same as `do_call`, for handlers interested in the deadline and payload codec
+    /// set by the caller.
+    fn do_raw_call(&mut self, request_id: String, call: RpcRawCall) -> Self::Reply {
+        self.do_call(request_id, call.caller, call.addr, call.body)
+    }
+
     fn handle_event(&mut self, caller: String, topic: String, data: Vec<u8>) {
         log::warn!("unhandled gsb event from: {}, to: {}", caller, topic,);
         log::trace!(
@@ -75,17 +106,37 @@ impl CallRequestHandler for LocalRouterHandler {
 
     fn do_call(
         &mut self,
-        _request_id: String,
+        request_id: String,
         caller: String,
         address: String,
         data: Vec<u8>,
     ) -> Self::Reply {
+        self.do_raw_call(
+            request_id,
+            RpcRawCall {
+                caller,
+                addr: address,
+                body: data,
+                deadline: None,
+                codec: None,
+            },
+        )
+    }
+
+    fn do_raw_call(&mut self, _request_id: String, call: RpcRawCall) -> Self::Reply {
         router()
             .lock()
             .unwrap()
-            .forward_bytes_local(&address, &caller, data.as_ref())
+            .forward_raw_local(call)
             .boxed_local()
     }
+
+    fn handle_event(&mut self, caller: String, topic: String, data: Vec<u8>) {
+        router()
+            .lock()
+            .unwrap()
+            .dispatch_remote_event(&caller, &topic, data)
+    }
 }
 
 impl<
@@ -106,13 +157,8 @@ impl<
     }
 }
 
-impl<
-        R: futures::Stream<Item = Result<ResponseChunk, Error>> + Unpin,
-        F1: FnMut(String, String, String, Vec<u8>) -> R,
-        F2: FnMut(String, String, Vec<u8>),
-    > CallRequestHandler for (F1, F2)
-{
-    type Reply = R;
+impl<H: CallRequestHandler, F2: FnMut(String, String, Vec<u8>)> CallRequestHandler for (H, F2) {
+    type Reply = H::Reply;
 
     fn do_call(
         &mut self,
@@ -121,7 +167,11 @@ impl<
         address: String,
         data: Vec<u8>,
     ) -> Self::Reply {
-        (self.0)(request_id, caller, address, data)
+        self.0.do_call(request_id, caller, address, data)
+    }
+
+    fn do_raw_call(&mut self, request_id: String, call: RpcRawCall) -> Self::Reply {
+        self.0.do_raw_call(request_id, call)
     }
 
     fn handle_event(&mut self, caller: String, topic: String, data: Vec<u8>) {
@@ -130,7 +180,7 @@ impl<
 }
 
 type TransportWriter<W> = actix::io::SinkWrite<GsbMessage, futures::sink::Buffer<W, GsbMessage>>;
-type ReplyQueue = VecDeque<oneshot::Sender<Result<(), Error>>>;
+type ReplyQueue<T = ()> = VecDeque<oneshot::Sender<Result<T, Error>>>;
 
 struct Connection<W, H>
 where
@@ -138,12 +188,16 @@ where
     H: CallRequestHandler,
 {
     writer: TransportWriter<W>,
+    hello_reply: ReplyQueue<Option<Codec>>,
     register_reply: ReplyQueue,
     unregister_reply: ReplyQueue,
     subscribe_reply: ReplyQueue,
     unsubscribe_reply: ReplyQueue,
     call_reply: HashMap<String, mpsc::Sender<Result<ResponseChunk, Error>>>,
     broadcast_reply: ReplyQueue,
+    handled_calls: HashMap<String, SpawnHandle>,
+    call_credits: HashMap<String, mpsc::UnboundedSender<u32>>,
+    close_watchers: Vec<oneshot::Sender<()>>,
     handler: H,
 }
 
@@ -154,9 +208,9 @@ where
 {
 }
 
-fn handle_reply<Ctx: ActorContext, F: FnOnce() -> Result<(), Error>>(
+fn handle_reply<T, Ctx: ActorContext, F: FnOnce() -> Result<T, Error>>(
     cmd_type: &str,
-    queue: &mut ReplyQueue,
+    queue: &mut ReplyQueue<T>,
     ctx: &mut Ctx,
     reply_msg: F,
 ) {
@@ -176,16 +230,39 @@ where
     fn new(w: W, handler: H, ctx: &mut <Self as Actor>::Context) -> Self {
         Connection {
             writer: io::SinkWrite::new(w.buffer(256), ctx),
+            hello_reply: Default::default(),
             register_reply: Default::default(),
             unregister_reply: Default::default(),
             subscribe_reply: Default::default(),
             unsubscribe_reply: Default::default(),
             call_reply: Default::default(),
             broadcast_reply: Default::default(),
+            handled_calls: Default::default(),
+            call_credits: Default::default(),
+            close_watchers: Default::default(),
             handler,
         }
     }
 
+    fn handle_hello_reply(
+        &mut self,
+        code: HelloReplyCode,
+        msg: String,
+        codec: Option<Codec>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        handle_reply("hello", &mut self.hello_reply, ctx, || match code {
+            HelloReplyCode::HelloOk => {
+                log::debug!("gsb hello: {}, payload codec: {:?}", msg, codec);
+                Ok(codec)
+            }
+            HelloReplyCode::HelloUnauthorized => {
+                log::warn!("unauthorized: {}", msg);
+                Err(Error::GsbAccessDenied(msg))
+            }
+        })
+    }
+
     fn handle_unregister_reply(
         &mut self,
         code: UnregisterReplyCode,
@@ -213,6 +290,7 @@ where
         handle_reply("broadcast", &mut self.broadcast_reply, ctx, || match code {
             BroadcastReplyCode::BroadcastOk => Ok(()),
             BroadcastReplyCode::BroadcastBadRequest => Err(Error::GsbBadRequest(msg)),
+            BroadcastReplyCode::BroadcastForbidden => Err(Error::GsbAccessDenied(msg)),
         })
     }
 
@@ -228,6 +306,10 @@ where
                 log::warn!("bad request: {}", msg);
                 Err(Error::GsbBadRequest(msg))
             }
+            RegisterReplyCode::RegisterForbidden => {
+                log::warn!("forbidden: {}", msg);
+                Err(Error::GsbAccessDenied(msg))
+            }
             RegisterReplyCode::RegisterConflict => {
                 log::warn!("already registered: {}", msg);
                 Err(Error::GsbAlreadyRegistered(msg))
@@ -247,6 +329,10 @@ where
                 log::warn!("bad request: {}", msg);
                 Err(Error::GsbBadRequest(msg))
             }
+            SubscribeReplyCode::SubscribeForbidden => {
+                log::warn!("forbidden: {}", msg);
+                Err(Error::GsbAccessDenied(msg))
+            }
         })
     }
 
@@ -271,21 +357,32 @@ where
     fn handle_call_request(
         &mut self,
         request_id: String,
-        caller: String,
-        address: String,
-        data: Vec<u8>,
+        call: RpcRawCall,
+        credit: u32,
         ctx: &mut <Self as Actor>::Context,
     ) {
         log::debug!(
             "handling call from = {}, to = {}, request_id={}, ",
-            caller,
-            address,
+            call.caller,
+            call.addr,
             request_id
         );
         let eos_request_id = request_id.clone();
-        let do_call = self
-            .handler
-            .do_call(request_id.clone(), caller, address, data)
+        let handle_request_id = request_id.clone();
+        let reply = self.handler.do_raw_call(request_id.clone(), call);
+        let reply = if credit > 0 {
+            let (tx, rx) = mpsc::unbounded();
+            let _ = self.call_credits.insert(request_id.clone(), tx);
+            CreditGate {
+                inner: reply,
+                available: credit,
+                grants: rx,
+            }
+            .left_stream()
+        } else {
+            reply.right_stream()
+        };
+        let do_call = reply
             .into_actor(self)
             .fold(false, move |_got_eos, r, act: &mut Self, _ctx| {
                 let request_id = request_id.clone();
@@ -323,6 +420,8 @@ where
                 fut::ready(got_eos)
             })
             .then(|got_eos, act, _ctx| {
+                let _ = act.handled_calls.remove(&eos_request_id);
+                let _ = act.call_credits.remove(&eos_request_id);
                 if !got_eos {
                     let _ = act.writer.write(GsbMessage::CallReply(CallReply {
                         request_id: eos_request_id,
@@ -333,8 +432,24 @@ where
                 }
                 fut::ready(())
             });
-        //do_call.spawn(ctx);
-        ctx.spawn(do_call);
+        let handle = ctx.spawn(do_call);
+        let _ = self.handled_calls.insert(handle_request_id, handle);
+    }
+
+    /// Drops the handler's reply stream, so that the handler sees its reply channel closed.
+    fn handle_cancel(&mut self, request_id: String, ctx: &mut <Self as Actor>::Context) {
+        log::debug!("handling cancel of request_id={}", request_id);
+        let _ = self.call_credits.remove(&request_id);
+        if let Some(handle) = self.handled_calls.remove(&request_id) {
+            let _ = ctx.cancel_future(handle);
+        }
+    }
+
+    fn handle_credit(&mut self, request_id: String, credit: u32) {
+        log::trace!("got credit {} for request_id={}", credit, request_id);
+        if let Some(grants) = self.call_credits.get(&request_id) {
+            let _ = grants.unbounded_send(credit);
+        }
     }
 
     fn handle_reply(
@@ -369,6 +484,9 @@ where
                 CallReplyCode::CallReplyBadRequest => {
                     Err(Error::GsbBadRequest(String::from_utf8(chunk.into_bytes())?))
                 }
+                CallReplyCode::CallReplyForbidden => Err(Error::GsbAccessDenied(
+                    String::from_utf8(chunk.into_bytes())?,
+                )),
                 CallReplyCode::ServiceFailure => {
                     Err(Error::GsbFailure(String::from_utf8(chunk.into_bytes())?))
                 }
@@ -382,8 +500,10 @@ where
                 .into_actor(self),
             );
         } else {
-            log::error!("unmatched call reply");
-            ctx.stop()
+            log::debug!(
+                "reply to unknown or cancelled call request_id={}",
+                request_id
+            );
         }
 
         if is_full {
@@ -410,10 +530,19 @@ where
     }
 }
 
+fn hello_reply_code(code: i32) -> Option<HelloReplyCode> {
+    Some(match code {
+        0 => HelloReplyCode::HelloOk,
+        401 => HelloReplyCode::HelloUnauthorized,
+        _ => return None,
+    })
+}
+
 fn register_reply_code(code: i32) -> Option<RegisterReplyCode> {
     Some(match code {
         0 => RegisterReplyCode::RegisteredOk,
         400 => RegisterReplyCode::RegisterBadRequest,
+        403 => RegisterReplyCode::RegisterForbidden,
         409 => RegisterReplyCode::RegisterConflict,
         _ => return None,
     })
@@ -431,6 +560,7 @@ fn subscribe_reply_code(code: i32) -> Option<SubscribeReplyCode> {
     Some(match code {
         0 => SubscribeReplyCode::SubscribedOk,
         400 => SubscribeReplyCode::SubscribeBadRequest,
+        403 => SubscribeReplyCode::SubscribeForbidden,
         _ => return None,
     })
 }
@@ -447,6 +577,7 @@ fn broadcast_reply_code(code: i32) -> Option<BroadcastReplyCode> {
     Some(match code {
         0 => BroadcastReplyCode::BroadcastOk,
         400 => BroadcastReplyCode::BroadcastBadRequest,
+        403 => BroadcastReplyCode::BroadcastForbidden,
         _ => return None,
     })
 }
@@ -458,6 +589,14 @@ where
 {
     fn handle(&mut self, item: Result<GsbMessage, ProtocolError>, ctx: &mut Self::Context) {
         match item.unwrap() {
+            GsbMessage::HelloReply(r) => {
+                if let Some(code) = hello_reply_code(r.code) {
+                    self.handle_hello_reply(code, r.message, Codec::from_proto(r.codec), ctx)
+                } else {
+                    log::error!("invalid hello reply code {}", r.code);
+                    ctx.stop();
+                }
+            }
             GsbMessage::RegisterReply(r) => {
                 if let Some(code) = register_reply_code(r.code) {
                     self.handle_register_reply(code, r.message, ctx)
@@ -498,9 +637,20 @@ where
                     ctx.stop();
                 }
             }
-            GsbMessage::CallRequest(r) => {
-                self.handle_call_request(r.request_id, r.caller, r.address, r.data, ctx)
-            }
+            GsbMessage::CallRequest(r) => self.handle_call_request(
+                r.request_id,
+                RpcRawCall {
+                    caller: r.caller,
+                    addr: r.address,
+                    body: r.data,
+                    deadline: deadline_from_timeout(r.timeout),
+                    codec: Codec::from_proto(r.codec),
+                },
+                r.credit,
+                ctx,
+            ),
+            GsbMessage::CallCredit(r) => self.handle_credit(r.request_id, r.credit),
+            GsbMessage::CancelCall(r) => self.handle_cancel(r.request_id, ctx),
             GsbMessage::CallReply(r) => {
                 if let Err(e) = self.handle_reply(r.request_id, r.code, r.reply_type, r.data, ctx) {
                     log::error!("error on call reply processing: {}", e);
@@ -543,18 +693,48 @@ where
     type Result = ActorResponse<Self, Vec<u8>, Error>;
 
     fn handle(&mut self, msg: RpcRawCall, _ctx: &mut Self::Context) -> Self::Result {
+        self.send_call(format!("{}", gen_id()), msg)
+    }
+}
+
+impl<W, H> Handler<RpcRawStreamCall> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ActorResponse<Self, (), Error>;
+
+    fn handle(&mut self, msg: RpcRawStreamCall, _ctx: &mut Self::Context) -> Self::Result {
+        self.send_stream_call(format!("{}", gen_id()), msg, 0)
+    }
+}
+
+impl<W, H> Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    fn send_call(
+        &mut self,
+        request_id: String,
+        msg: RpcRawCall,
+    ) -> ActorResponse<Self, Vec<u8>, Error> {
         let (tx, mut rx) = mpsc::channel(1);
-        let request_id = format!("{}", gen_id());
         let _ = self.call_reply.insert(request_id.clone(), tx);
         let caller = msg.caller;
         let address = msg.addr;
         let data = msg.body;
+        let timeout = deadline_to_timeout(msg.deadline);
+        let codec = Codec::to_proto(msg.codec) as i32;
         log::debug!("handling caller: {}, addr:{}", caller, address);
         let _r = self.writer.write(GsbMessage::CallRequest(CallRequest {
             request_id,
             caller,
             address,
             data,
+            timeout,
+            credit: 0,
+            codec,
         }));
         let fetch_response = async move {
             match futures::StreamExt::next(&mut rx).await {
@@ -568,47 +748,256 @@ where
         };
         ActorResponse::r#async(fetch_response.into_actor(self))
     }
-}
 
-impl<W, H> Handler<RpcRawStreamCall> for Connection<W, H>
-where
-    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
-    H: CallRequestHandler + 'static,
-{
-    type Result = ActorResponse<Self, (), Error>;
-
-    fn handle(&mut self, msg: RpcRawStreamCall, _ctx: &mut Self::Context) -> Self::Result {
-        let request_id = format!("{}", gen_id());
+    fn send_stream_call(
+        &mut self,
+        request_id: String,
+        msg: RpcRawStreamCall,
+        credit: u32,
+    ) -> ActorResponse<Self, (), Error> {
         let rx = msg.reply;
         let _ = self.call_reply.insert(request_id.clone(), rx);
         let caller = msg.caller;
         let address = msg.addr;
         let data = msg.body;
+        let timeout = deadline_to_timeout(msg.deadline);
+        let codec = Codec::to_proto(msg.codec) as i32;
         log::debug!("handling caller: {}, addr:{}", caller, address);
         let _r = self.writer.write(GsbMessage::CallRequest(CallRequest {
             request_id,
             caller,
             address,
             data,
+            timeout,
+            credit,
+            codec,
         }));
         ActorResponse::reply(Ok(()))
     }
 }
 
-fn send_cmd_async<A: Actor, W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static>(
+struct Call<T> {
+    request_id: String,
+    call: T,
+}
+
+impl Message for Call<RpcRawCall> {
+    type Result = Result<Vec<u8>, Error>;
+}
+
+impl Message for Call<RpcRawStreamCall> {
+    type Result = Result<(), Error>;
+}
+
+impl<W, H> Handler<Call<RpcRawCall>> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ActorResponse<Self, Vec<u8>, Error>;
+
+    fn handle(&mut self, msg: Call<RpcRawCall>, _ctx: &mut Self::Context) -> Self::Result {
+        self.send_call(msg.request_id, msg.call)
+    }
+}
+
+impl<W, H> Handler<Call<RpcRawStreamCall>> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ActorResponse<Self, (), Error>;
+
+    fn handle(&mut self, msg: Call<RpcRawStreamCall>, _ctx: &mut Self::Context) -> Self::Result {
+        self.send_stream_call(msg.request_id, msg.call, crate::STREAM_CREDIT as u32)
+    }
+}
+
+struct Cancel {
+    request_id: String,
+}
+
+impl Message for Cancel {
+    type Result = ();
+}
+
+impl<W, H> Handler<Cancel> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: Cancel, _ctx: &mut Self::Context) -> Self::Result {
+        if self.call_reply.remove(&msg.request_id).is_some() {
+            log::debug!("cancelling request_id={}", msg.request_id);
+            let _ = self.writer.write(GsbMessage::CancelCall(CancelCall {
+                request_id: msg.request_id,
+            }));
+        }
+    }
+}
+
+struct Credit {
+    request_id: String,
+    credit: u32,
+}
+
+impl Message for Credit {
+    type Result = ();
+}
+
+impl<W, H> Handler<Credit> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: Credit, _ctx: &mut Self::Context) -> Self::Result {
+        if self.call_reply.contains_key(&msg.request_id) {
+            let _ = self.writer.write(GsbMessage::CallCredit(CallCredit {
+                request_id: msg.request_id,
+                credit: msg.credit,
+            }));
+        }
+    }
+}
+
+/// Holds back reply chunks until the caller grants credit for them.
+struct CreditGate<S> {
+    inner: S,
+    available: u32,
+    grants: mpsc::UnboundedReceiver<u32>,
+}
+
+impl<S> Stream for CreditGate<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        while let Poll::Ready(Some(credit)) = self.grants.poll_next_unpin(cx) {
+            self.available = self.available.saturating_add(credit);
+        }
+        if self.available == 0 {
+            return Poll::Pending;
+        }
+        let item = futures::ready!(self.inner.poll_next_unpin(cx));
+        if item.is_some() {
+            self.available -= 1;
+        }
+        Poll::Ready(item)
+    }
+}
+
+/// Cancels the call unless disarmed before being dropped.
+struct CancelGuard {
+    connection: Option<Recipient<Cancel>>,
+    request_id: String,
+}
+
+impl CancelGuard {
+    fn disarm(&mut self) {
+        self.connection = None;
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            let request_id = std::mem::replace(&mut self.request_id, String::new());
+            let _ = connection.do_send(Cancel { request_id });
+        }
+    }
+}
+
+/// Reply stream of a streaming call. Grants credit for consumed chunks.
+/// Dropping it before the last chunk cancels the call.
+struct CancellableStream<S> {
+    inner: S,
+    guard: CancelGuard,
+    credit: Recipient<Credit>,
+    consumed: u32,
+}
+
+impl<S> Stream for CancellableStream<S>
+where
+    S: Stream<Item = Result<ResponseChunk, Error>> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let item = futures::ready!(self.inner.poll_next_unpin(cx));
+        match &item {
+            Some(Ok(ResponseChunk::Part(_))) => {
+                self.consumed += 1;
+                if self.consumed >= crate::STREAM_CREDIT as u32 / 2 {
+                    let credit = std::mem::replace(&mut self.consumed, 0);
+                    let _ = self.credit.do_send(Credit {
+                        request_id: self.guard.request_id.clone(),
+                        credit,
+                    });
+                }
+            }
+            _ => self.guard.disarm(),
+        }
+        Poll::Ready(item)
+    }
+}
+
+fn send_cmd_async<
+    A: Actor,
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    T: 'static,
+>(
     writer: &mut TransportWriter<W>,
-    queue: &mut VecDeque<oneshot::Sender<Result<(), Error>>>,
+    queue: &mut ReplyQueue<T>,
     msg: GsbMessage,
-) -> ActorResponse<A, (), Error> {
+) -> ActorResponse<A, T, Error> {
     let (tx, rx) = oneshot::channel();
     queue.push_back(tx);
     if let Err(e) = writer.write(msg) {
         ActorResponse::reply(Err(Error::GsbFailure(e.to_string())))
     } else {
-        ActorResponse::r#async(fut::wrap_future(async move {
-            rx.await??;
-            Ok(())
-        }))
+        ActorResponse::r#async(fut::wrap_future(async move { rx.await? }))
+    }
+}
+
+struct Hello {
+    name: String,
+    token: String,
+    codecs: Vec<Codec>,
+}
+
+impl Message for Hello {
+    type Result = Result<Option<Codec>, Error>;
+}
+
+impl<W, H> Handler<Hello> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ActorResponse<Self, Option<Codec>, Error>;
+
+    fn handle(&mut self, msg: Hello, _ctx: &mut Self::Context) -> Self::Result {
+        send_cmd_async(
+            &mut self.writer,
+            &mut self.hello_reply,
+            GsbMessage::HelloRequest(HelloRequest {
+                name: msg.name,
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                token: msg.token,
+                codecs: msg
+                    .codecs
+                    .into_iter()
+                    .map(|codec| Codec::to_proto(Some(codec)) as i32)
+                    .collect(),
+                router_id: String::new(),
+            }),
+        )
     }
 }
 
@@ -662,6 +1051,26 @@ where
     }
 }
 
+/// Resolves (with `Canceled`) once the connection actor is gone.
+struct WatchClose(oneshot::Sender<()>);
+
+impl Message for WatchClose {
+    type Result = ();
+}
+
+impl<W, H> Handler<WatchClose> for Connection<W, H>
+where
+    W: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchClose, _ctx: &mut Self::Context) -> Self::Result {
+        self.close_watchers.retain(|tx| !tx.is_canceled());
+        self.close_watchers.push(msg.0);
+    }
+}
+
 struct Subscribe {
     topic: String,
 }
@@ -748,7 +1157,10 @@ where
 pub struct ConnectionRef<
     Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
     H: CallRequestHandler + 'static,
->(Addr<Connection<SplitSink<Transport, GsbMessage>, H>>);
+>(
+    Addr<Connection<SplitSink<Transport, GsbMessage>, H>>,
+    Arc<NegotiatedCodec>,
+);
 
 impl<
         Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
@@ -763,7 +1175,7 @@ impl<
     > Clone for ConnectionRef<Transport, H>
 {
     fn clone(&self) -> Self {
-        ConnectionRef(self.0.clone())
+        ConnectionRef(self.0.clone(), self.1.clone())
     }
 }
 
@@ -772,6 +1184,34 @@ impl<
         H: CallRequestHandler + Unpin + 'static,
     > ConnectionRef<Transport, H>
 {
+    /// Authenticates the connection and negotiates the codec of typed payloads,
+    /// offering `codecs` in order of preference. Should be sent before any other command.
+    ///
+    /// Resolves to the negotiated codec, `None` if the router left the choice to the client.
+    pub fn hello(
+        &self,
+        name: impl Into<String>,
+        token: impl Into<String>,
+        codecs: Vec<Codec>,
+    ) -> impl Future<Output = Result<Option<Codec>, Error>> + 'static {
+        let fut = self.0.send(Hello {
+            name: name.into(),
+            token: token.into(),
+            codecs,
+        });
+        let negotiated = self.1.clone();
+        async move {
+            let codec = fut.await??;
+            negotiated.set(codec);
+            Ok(codec)
+        }
+    }
+
+    /// Codec of typed payloads negotiated by `hello` on this connection.
+    pub fn codec(&self) -> Option<Codec> {
+        self.1.get()
+    }
+
     pub fn bind(
         &self,
         addr: impl Into<String>,
@@ -835,13 +1275,36 @@ impl<
         addr: impl Into<String>,
         body: impl Into<Vec<u8>>,
     ) -> impl Future<Output = Result<Vec<u8>, Error>> {
-        self.0
-            .send(RpcRawCall {
-                caller: caller.into(),
-                addr: addr.into(),
-                body: body.into(),
-            })
-            .then(|v| async { v? })
+        self.call_with_deadline(caller, addr, body, None)
+    }
+
+    /// Calls remote service. Router fails the call once `deadline` passes.
+    pub fn call_with_deadline(
+        &self,
+        caller: impl Into<String>,
+        addr: impl Into<String>,
+        body: impl Into<Vec<u8>>,
+        deadline: Option<SystemTime>,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> {
+        self.raw_call(RpcRawCall {
+            caller: caller.into(),
+            addr: addr.into(),
+            body: body.into(),
+            deadline,
+            codec: None,
+        })
+    }
+
+    /// Calls remote service, keeping the deadline and payload codec of `call`.
+    pub fn raw_call(&self, call: RpcRawCall) -> impl Future<Output = Result<Vec<u8>, Error>> {
+        let request_id = format!("{}", gen_id());
+        let mut guard = self.cancel_guard(&request_id);
+        let call = self.0.send(Call { request_id, call });
+        async move {
+            let result = call.await;
+            guard.disarm();
+            result?
+        }
     }
 
     pub fn call_streaming(
@@ -850,18 +1313,48 @@ impl<
         addr: impl Into<String>,
         body: impl Into<Vec<u8>>,
     ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
-        let (tx, rx) = futures::channel::mpsc::channel(16);
+        self.call_streaming_with_deadline(caller, addr, body, None)
+    }
 
-        let args = RpcRawStreamCall {
+    pub fn call_streaming_with_deadline(
+        &self,
+        caller: impl Into<String>,
+        addr: impl Into<String>,
+        body: impl Into<Vec<u8>>,
+        deadline: Option<SystemTime>,
+    ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
+        self.raw_call_streaming(RpcRawCall {
             caller: caller.into(),
             addr: addr.into(),
             body: body.into(),
-            reply: tx.clone(),
-        };
-        let connection = self.0.clone();
+            deadline,
+            codec: None,
+        })
+    }
+
+    /// Streaming variant of `raw_call`.
+    pub fn raw_call_streaming(
+        &self,
+        call: RpcRawCall,
+    ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
+        let (tx, rx) = futures::channel::mpsc::channel(16);
+
+        let request_id = format!("{}", gen_id());
+        let guard = self.cancel_guard(&request_id);
+        let call = self.0.send(Call {
+            request_id,
+            call: RpcRawStreamCall {
+                caller: call.caller,
+                addr: call.addr,
+                body: call.body,
+                reply: tx.clone(),
+                deadline: call.deadline,
+                codec: call.codec,
+            },
+        });
         let _ = Arbiter::spawn(async move {
             let mut tx = tx;
-            match connection.send(args).await {
+            match call.await {
                 Ok(Ok(())) => (),
                 Ok(Err(e)) => {
                     tx.send(Err(e))
@@ -875,12 +1368,35 @@ impl<
                 }
             }
         });
-        rx
+        CancellableStream {
+            inner: rx,
+            guard,
+            credit: self.0.clone().recipient(),
+            consumed: 0,
+        }
     }
 
     pub fn connected(&self) -> bool {
         self.0.connected()
     }
+
+    /// Resolves when the connection to the router is lost.
+    pub fn closed(&self) -> impl Future<Output = ()> + 'static {
+        let (tx, rx) = oneshot::channel();
+        let watch = self.0.send(WatchClose(tx));
+        async move {
+            if watch.await.is_ok() {
+                let _ = rx.await;
+            }
+        }
+    }
+
+    fn cancel_guard(&self, request_id: &str) -> CancelGuard {
+        CancelGuard {
+            connection: Some(self.0.clone().recipient()),
+            request_id: request_id.to_string(),
+        }
+    }
 }
 
 pub fn connect<Transport, H>(transport: Transport) -> ConnectionRef<Transport, H>
@@ -906,10 +1422,11 @@ where
     H: CallRequestHandler + 'static,
 {
     let (split_sink, split_stream) = transport.split();
-    ConnectionRef(Connection::create(move |ctx| {
+    let connection = Connection::create(move |ctx| {
         let _h = Connection::add_stream(split_stream, ctx);
         Connection::new(split_sink, handler, ctx)
-    }))
+    });
+    ConnectionRef(connection, Default::default())
 }
 
 pub type TcpTransport =
@@ -922,3 +1439,209 @@ pub async fn tcp(addr: std::net::SocketAddr) -> Result<TcpTransport, std::io::Er
         ya_sb_proto::codec::GsbMessageCodec::default(),
     ))
 }
+
+#[cfg(test)]
+pub(crate) mod test_transport {
+    use super::*;
+
+    /// Connection end of an in-memory transport, the other end being the test's router.
+    pub(crate) struct TestTransport {
+        tx: mpsc::UnboundedSender<GsbMessage>,
+        rx: mpsc::UnboundedReceiver<GsbMessage>,
+    }
+
+    impl Sink<GsbMessage> for TestTransport {
+        type Error = ProtocolError;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut TaskContext<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: GsbMessage) -> Result<(), Self::Error> {
+            self.tx
+                .unbounded_send(item)
+                .map_err(|_| ProtocolError::Io(std::io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut TaskContext<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut TaskContext<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.tx.close_channel();
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Stream for TestTransport {
+        type Item = Result<GsbMessage, ProtocolError>;
+
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut TaskContext<'_>,
+        ) -> Poll<Option<Self::Item>> {
+            self.rx.poll_next_unpin(cx).map(|msg| msg.map(Ok))
+        }
+    }
+
+    pub(crate) fn transport() -> (
+        TestTransport,
+        mpsc::UnboundedSender<GsbMessage>,
+        mpsc::UnboundedReceiver<GsbMessage>,
+    ) {
+        let (to_router, from_connection) = mpsc::unbounded();
+        let (to_connection, from_router) = mpsc::unbounded();
+        let transport = TestTransport {
+            tx: to_router,
+            rx: from_router,
+        };
+        (transport, to_connection, from_connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_transport::*;
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use ya_sb_proto::{HelloReply, PayloadCodec};
+
+    fn call_request(request_id: &str, credit: u32) -> GsbMessage {
+        GsbMessage::CallRequest(CallRequest {
+            request_id: request_id.to_string(),
+            caller: "caller".to_string(),
+            address: "/local/test".to_string(),
+            credit,
+            ..Default::default()
+        })
+    }
+
+    async fn settle() {
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+    }
+
+    #[actix_rt::test]
+    async fn cancel_call_drops_handler_reply() {
+        let replies: Rc<RefCell<Vec<mpsc::Sender<Result<ResponseChunk, Error>>>>> =
+            Default::default();
+        let handler_replies = replies.clone();
+        let handler = move |_: String, _: String, _: String, _: Vec<u8>| {
+            let (tx, rx) = mpsc::channel(1);
+            handler_replies.borrow_mut().push(tx);
+            rx
+        };
+        let (transport, router_tx, mut router_rx) = transport();
+        let _connection = connect_with_handler(transport, handler);
+
+        router_tx.unbounded_send(call_request("1", 0)).unwrap();
+        settle().await;
+        assert_eq!(replies.borrow().len(), 1);
+        assert!(!replies.borrow()[0].is_closed());
+
+        router_tx
+            .unbounded_send(GsbMessage::CancelCall(CancelCall {
+                request_id: "1".to_string(),
+            }))
+            .unwrap();
+        settle().await;
+        assert!(replies.borrow()[0].is_closed());
+        assert!(router_rx.try_next().is_err());
+    }
+
+    fn received_replies(rx: &mut mpsc::UnboundedReceiver<GsbMessage>) -> Vec<Vec<u8>> {
+        let mut replies = Vec::new();
+        while let Ok(Some(msg)) = rx.try_next() {
+            match msg {
+                GsbMessage::CallReply(reply) => replies.push(reply.data),
+                msg => panic!("unexpected message: {:?}", msg),
+            }
+        }
+        replies
+    }
+
+    #[actix_rt::test]
+    async fn replies_are_held_until_credit_is_granted() {
+        let handler = |_: String, _: String, _: String, _: Vec<u8>| {
+            stream::iter(vec![
+                Ok(ResponseChunk::Part(b"1".to_vec())),
+                Ok(ResponseChunk::Part(b"2".to_vec())),
+                Ok(ResponseChunk::Full(b"3".to_vec())),
+            ])
+        };
+        let (transport, router_tx, mut router_rx) = transport();
+        let _connection = connect_with_handler(transport, handler);
+
+        router_tx.unbounded_send(call_request("1", 1)).unwrap();
+        settle().await;
+        assert_eq!(received_replies(&mut router_rx), vec![b"1".to_vec()]);
+
+        router_tx
+            .unbounded_send(GsbMessage::CallCredit(CallCredit {
+                request_id: "1".to_string(),
+                credit: 2,
+            }))
+            .unwrap();
+        settle().await;
+        assert_eq!(
+            received_replies(&mut router_rx),
+            vec![b"2".to_vec(), b"3".to_vec()]
+        );
+    }
+
+    async fn hello_reply(
+        router_tx: &mpsc::UnboundedSender<GsbMessage>,
+        router_rx: &mut mpsc::UnboundedReceiver<GsbMessage>,
+        codec: PayloadCodec,
+    ) {
+        match router_rx.next().await {
+            Some(GsbMessage::HelloRequest(hello)) => assert_eq!(
+                hello.codecs,
+                vec![
+                    PayloadCodec::Msgpack as i32,
+                    PayloadCodec::Cbor as i32,
+                    PayloadCodec::Json as i32
+                ]
+            ),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+        let reply = HelloReply {
+            code: HelloReplyCode::HelloOk as i32,
+            codec: codec as i32,
+            ..Default::default()
+        };
+        router_tx
+            .unbounded_send(GsbMessage::HelloReply(reply))
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn codec_is_negotiated_per_connection() {
+        let handler = |_: String, _: String, _: String, _: Vec<u8>| {
+            stream::empty::<Result<ResponseChunk, Error>>()
+        };
+        let (transport1, router_tx1, mut router_rx1) = transport();
+        let (transport2, router_tx2, mut router_rx2) = transport();
+        let connection1 = connect_with_handler(transport1, handler);
+        let connection2 = connect_with_handler(transport2, handler);
+
+        let hello1 = connection1.hello("one", "", Codec::preferred());
+        let hello2 = connection2.hello("two", "", Codec::preferred());
+        hello_reply(&router_tx1, &mut router_rx1, PayloadCodec::Msgpack).await;
+        hello_reply(&router_tx2, &mut router_rx2, PayloadCodec::Cbor).await;
+        assert_eq!(hello1.await.unwrap(), Some(Codec::Msgpack));
+        assert_eq!(hello2.await.unwrap(), Some(Codec::Cbor));
+
+        assert_eq!(connection1.codec(), Some(Codec::Msgpack));
+        assert_eq!(connection2.codec(), Some(Codec::Cbor));
+    }
+}
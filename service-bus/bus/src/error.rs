@@ -22,6 +22,8 @@ pub enum Error {
     GsbBadRequest(String),
     #[error("already registered: {0}")]
     GsbAlreadyRegistered(String),
+    #[error("access denied: {0}")]
+    GsbAccessDenied(String),
     #[error("{0}")]
     GsbFailure(String),
 }
@@ -0,0 +1,63 @@
+//! Wire format of `BusEvent`s.
+//!
+//! GSB broadcasts do not carry a payload codec, so events are always encoded
+//! with JSON, which every client decodes. Each event is tagged with the id of
+//! the publishing process, which delivers network events to its own subscribers
+//! directly and skips the copy echoed back by the router.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{Codec, Error};
+
+const EVENT_CODEC: Codec = Codec::Json;
+
+lazy_static::lazy_static! {
+    static ref ORIGIN: String = format!("{:016x}", rand::random::<u64>());
+}
+
+#[derive(Serialize)]
+struct EventEnvelope<'a, E> {
+    origin: &'a str,
+    event: &'a E,
+}
+
+#[derive(Deserialize)]
+struct EventHeader {
+    origin: String,
+}
+
+#[derive(Deserialize)]
+struct EventBody<E> {
+    event: E,
+}
+
+/// Encoded event as delivered to subscribers.
+pub(crate) struct RpcRawEvent {
+    pub caller: String,
+    pub body: Arc<[u8]>,
+}
+
+impl actix::Message for RpcRawEvent {
+    type Result = Result<(), Error>;
+}
+
+pub(crate) fn encode<E: Serialize>(event: &E) -> Result<Vec<u8>, Error> {
+    let envelope = EventEnvelope {
+        origin: ORIGIN.as_str(),
+        event,
+    };
+    Ok(EVENT_CODEC.to_vec(&envelope)?)
+}
+
+pub(crate) fn decode<E: DeserializeOwned>(body: &[u8]) -> Result<E, Error> {
+    let body: EventBody<E> = EVENT_CODEC.from_read(body)?;
+    Ok(body.event)
+}
+
+/// Events published by this process and received back from the router.
+pub(crate) fn is_own(body: &[u8]) -> bool {
+    EVENT_CODEC
+        .from_read::<EventHeader, _>(body)
+        .map(|header| header.origin == *ORIGIN)
+        .unwrap_or(false)
+}
@@ -1,12 +1,18 @@
 use actix::Message;
 use futures::prelude::Stream;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{fmt::Debug, future::Future};
+use std::{
+    fmt::Debug,
+    future::Future,
+    time::{Duration, SystemTime},
+};
 
 pub mod actix_rpc;
 pub mod connection;
 pub mod error;
+mod event;
 mod local_router;
+pub mod reconnect;
 mod remote_router;
 mod serialization;
 pub mod timeout;
@@ -14,6 +20,11 @@ pub mod typed;
 pub mod untyped;
 
 pub use error::Error;
+pub use serialization::Codec;
+
+/// Number of partial replies of a streaming call sent ahead of the caller consuming them.
+/// Local handlers are limited by reply channel capacity, remote ones by call credit.
+const STREAM_CREDIT: usize = 16;
 
 pub trait RpcMessage: Serialize + DeserializeOwned + 'static + Sync + Send {
     const ID: &'static str;
@@ -30,6 +41,7 @@ pub trait RpcStreamMessage: Serialize + DeserializeOwned + 'static + Sync + Send
 pub struct RpcEnvelope<T> {
     caller: String,
     body: T,
+    deadline: Option<SystemTime>,
 }
 
 #[derive(Debug)]
@@ -75,25 +87,50 @@ pub struct RpcRawStreamCall {
     pub addr: String,
     pub body: Vec<u8>,
     pub reply: futures::channel::mpsc::Sender<Result<ResponseChunk, error::Error>>,
+    pub deadline: Option<SystemTime>,
+    /// Encoding of a typed `body` and of the reply chunks, `None` for raw bytes.
+    pub codec: Option<Codec>,
+}
+
+impl RpcRawStreamCall {
+    /// The caller dropped the reply stream or cancelled the call, so further chunks are not needed.
+    pub fn is_cancelled(&self) -> bool {
+        self.reply.is_closed()
+    }
 }
 
 impl Message for RpcRawStreamCall {
     type Result = Result<(), error::Error>;
 }
 
+#[derive(Clone)]
 pub struct RpcRawCall {
     pub caller: String,
     pub addr: String,
     pub body: Vec<u8>,
+    pub deadline: Option<SystemTime>,
+    /// Encoding of a typed `body` and of the reply, `None` for raw bytes.
+    pub codec: Option<Codec>,
 }
 
 impl RpcRawCall {
-    fn from_envelope_addr<T: Serialize>(envelope: RpcEnvelope<T>, addr: String) -> Self {
-        RpcRawCall {
+    fn from_envelope_addr<T: Serialize>(
+        envelope: RpcEnvelope<T>,
+        addr: String,
+        codec: Codec,
+    ) -> Result<Self, Error> {
+        Ok(RpcRawCall {
             caller: envelope.caller,
             addr,
-            body: crate::serialization::to_vec(&envelope.body).unwrap(),
-        }
+            body: codec.to_vec(&envelope.body)?,
+            deadline: envelope.deadline,
+            codec: Some(codec),
+        })
+    }
+
+    /// Codec of a typed `body`. Untagged payloads use the build-time default.
+    fn body_codec(&self) -> Codec {
+        self.codec.unwrap_or_default()
     }
 }
 
@@ -101,11 +138,26 @@ impl Message for RpcRawCall {
     type Result = Result<Vec<u8>, error::Error>;
 }
 
+impl<T: RpcStreamMessage> RpcStreamCall<T> {
+    /// The caller dropped the reply stream or cancelled the call, so further items are not needed.
+    pub fn is_cancelled(&self) -> bool {
+        self.reply.is_closed()
+    }
+}
+
 impl<T: RpcStreamMessage> Message for RpcStreamCall<T> {
     type Result = Result<(), error::Error>;
 }
 
 impl<T: RpcMessage> RpcEnvelope<T> {
+    fn from_raw_call(call: &RpcRawCall, body: T) -> Self {
+        RpcEnvelope {
+            caller: call.caller.clone(),
+            body,
+            deadline: call.deadline,
+        }
+    }
+
     pub fn into_inner(self) -> T {
         self.body
     }
@@ -114,6 +166,7 @@ impl<T: RpcMessage> RpcEnvelope<T> {
         RpcEnvelope {
             caller: caller.to_string(),
             body,
+            deadline: None,
         }
     }
 
@@ -121,12 +174,32 @@ impl<T: RpcMessage> RpcEnvelope<T> {
         RpcEnvelope {
             caller: "local".into(),
             body,
+            deadline: None,
         }
     }
 
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
     pub fn caller(&self) -> &str {
         self.caller.as_str()
     }
+
+    /// Point in time after which the caller no longer waits for the reply.
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline
+    }
+
+    /// Time left until the deadline. Zero once it has passed.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
 }
 
 impl<T: RpcMessage> Message for RpcEnvelope<T> {
@@ -179,6 +252,31 @@ pub trait RpcStreamHandler<T: RpcStreamMessage> {
     fn handle(&mut self, caller: &str, msg: T) -> Self::Result;
 }
 
+/// Processes that receive a published [`BusEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventScope {
+    /// Subscribers within the publishing process only.
+    Local,
+    /// Also subscribers in other processes connected to the router or to its peers.
+    Network,
+}
+
+/// Event published to every subscriber of its type.
+///
+/// Events are delivered as GSB broadcasts, so `TOPIC` must be a concrete
+/// topic: `/` separated segments of alphanumeric characters, `_` and `-`,
+/// see [`topic`](ya_sb_util::topic).
+pub trait BusEvent: Serialize + DeserializeOwned + Clone + 'static + Sync + Send {
+    const TOPIC: &'static str;
+    const SCOPE: EventScope = EventScope::Local;
+}
+
+pub trait EventHandler<E: BusEvent> {
+    type Result: Future<Output = ()> + 'static;
+
+    fn handle(&mut self, caller: String, event: E) -> Self::Result;
+}
+
 pub struct Handle {
     pub(crate) _inner: (),
 }
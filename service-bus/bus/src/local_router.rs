@@ -1,6 +1,7 @@
 use actix::{prelude::*, Actor, SystemService};
 use futures::{prelude::*, FutureExt, StreamExt};
 use std::any::Any;
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
@@ -9,9 +10,11 @@ use ya_sb_util::futures::IntoFlatten;
 use ya_sb_util::PrefixLookupBag;
 
 use crate::{
-    remote_router::{RemoteRouter, UpdateService},
-    Error, Handle, ResponseChunk, RpcEnvelope, RpcHandler, RpcMessage, RpcRawCall,
-    RpcRawStreamCall, RpcStreamCall, RpcStreamHandler, RpcStreamMessage,
+    event::{self, RpcRawEvent},
+    remote_router::{BroadcastEvent, GetConnection, RemoteRouter, SubscribeTopic, UpdateService},
+    BusEvent, Codec, Error, EventHandler, EventScope, Handle, ResponseChunk, RpcEnvelope,
+    RpcHandler, RpcMessage, RpcRawCall, RpcRawStreamCall, RpcStreamCall, RpcStreamHandler,
+    RpcStreamMessage,
 };
 
 mod into_actix;
@@ -30,15 +33,15 @@ trait RawEndpoint: Any {
 // Implementation for non-streaming service
 impl<T: RpcMessage> RawEndpoint for Recipient<RpcEnvelope<T>> {
     fn send(&self, msg: RpcRawCall) -> Pin<Box<dyn Future<Output = Result<Vec<u8>, Error>>>> {
-        let body: T =
-            match crate::serialization::from_read(msg.body.as_slice()).map_err(Error::from) {
-                Ok(v) => v,
-                Err(e) => return future::err(e).boxed_local(),
-            };
+        let codec = msg.body_codec();
+        let body: T = match codec.from_read(msg.body.as_slice()).map_err(Error::from) {
+            Ok(v) => v,
+            Err(e) => return future::err(e).boxed_local(),
+        };
         Box::pin(
-            Recipient::send(self, RpcEnvelope::with_caller(&msg.caller, body))
+            Recipient::send(self, RpcEnvelope::from_raw_call(&msg, body))
                 .map_err(|e| e.into())
-                .and_then(|r| async move { crate::serialization::to_vec(&r).map_err(Error::from) }),
+                .and_then(move |r| async move { codec.to_vec(&r).map_err(Error::from) }),
         )
     }
 
@@ -46,16 +49,16 @@ impl<T: RpcMessage> RawEndpoint for Recipient<RpcEnvelope<T>> {
         &self,
         msg: RpcRawCall,
     ) -> Pin<Box<dyn Stream<Item = Result<ResponseChunk, Error>>>> {
-        let body: T =
-            match crate::serialization::from_read(msg.body.as_slice()).map_err(Error::from) {
-                Ok(v) => v,
-                Err(e) => return Box::pin(stream::once(async { Err::<ResponseChunk, Error>(e) })),
-            };
+        let codec = msg.body_codec();
+        let body: T = match codec.from_read(msg.body.as_slice()).map_err(Error::from) {
+            Ok(v) => v,
+            Err(e) => return Box::pin(stream::once(async { Err::<ResponseChunk, Error>(e) })),
+        };
 
         Box::pin(
-            Recipient::send(self, RpcEnvelope::with_caller(&msg.caller, body))
+            Recipient::send(self, RpcEnvelope::from_raw_call(&msg, body))
                 .map_err(|e| e.into())
-                .and_then(|r| future::ready(crate::serialization::to_vec(&r).map_err(Error::from)))
+                .and_then(move |r| future::ready(codec.to_vec(&r).map_err(Error::from)))
                 .map_ok(|v| ResponseChunk::Full(v))
                 .into_stream(),
         )
@@ -77,8 +80,12 @@ impl<T: RpcStreamMessage> RawEndpoint for Recipient<RpcStreamCall<T>> {
         &self,
         msg: RpcRawCall,
     ) -> Pin<Box<dyn Stream<Item = Result<ResponseChunk, Error>>>> {
-        let body: T = crate::serialization::from_read(msg.body.as_slice()).unwrap();
-        let (tx, rx) = futures::channel::mpsc::channel(16);
+        let codec = msg.body_codec();
+        let body: T = match codec.from_read(msg.body.as_slice()).map_err(Error::from) {
+            Ok(v) => v,
+            Err(e) => return Box::pin(stream::once(async { Err::<ResponseChunk, Error>(e) })),
+        };
+        let (tx, rx) = futures::channel::mpsc::channel(crate::STREAM_CREDIT);
         let (txe, rxe) = futures::channel::oneshot::channel();
 
         let call = RpcStreamCall {
@@ -101,9 +108,10 @@ impl<T: RpcStreamMessage> RawEndpoint for Recipient<RpcStreamCall<T>> {
         });
 
         let recv_stream = rx
-            .then(|r| {
+            .then(move |r| {
                 future::ready(
-                    crate::serialization::to_vec(&r)
+                    codec
+                        .to_vec(&r)
                         .map_err(Error::from)
                         .and_then(|r| Ok(ResponseChunk::Part(r))),
                 )
@@ -155,6 +163,8 @@ impl RawEndpoint for Recipient<RpcRawStreamCall> {
                 addr: msg.addr,
                 body: msg.body,
                 reply: tx,
+                deadline: msg.deadline,
+                codec: msg.codec,
             })
             .flatten_fut()
             .map_err(|e| eprintln!("cell error={}", e))
@@ -178,7 +188,7 @@ impl RawEndpoint for Recipient<RpcRawStreamCall> {
         &self,
         msg: RpcRawCall,
     ) -> Pin<Box<dyn Stream<Item = Result<ResponseChunk, Error>>>> {
-        let (tx, rx) = futures::channel::mpsc::channel(16);
+        let (tx, rx) = futures::channel::mpsc::channel(crate::STREAM_CREDIT);
         // TODO: send error to caller
         Arbiter::spawn(
             self.send(RpcRawStreamCall {
@@ -186,6 +196,8 @@ impl RawEndpoint for Recipient<RpcRawStreamCall> {
                 addr: msg.addr,
                 body: msg.body,
                 reply: tx,
+                deadline: msg.deadline,
+                codec: msg.codec,
             })
             .flatten_fut()
             .map_err(|e| eprintln!("cell error={}", e))
@@ -280,7 +292,7 @@ impl Slot {
         body: T,
     ) -> impl Stream<Item = Result<Result<T::Item, T::Error>, Error>> {
         if let Some(h) = self.stream_recipient() {
-            let (reply, rx) = futures::channel::mpsc::channel(16);
+            let (reply, rx) = futures::channel::mpsc::channel(crate::STREAM_CREDIT);
             let call = RpcStreamCall {
                 caller,
                 addr,
@@ -297,22 +309,29 @@ impl Slot {
             rx.map(|v| Ok(v)).left_stream()
         } else {
             (move || {
-                let body = match crate::serialization::to_vec(&body) {
+                let codec = Codec::default();
+                let body = match codec.to_vec(&body) {
                     Ok(body) => body,
                     Err(e) => return stream::once(future::err(Error::from(e))).right_stream(),
                 };
-                self.send_streaming(RpcRawCall { caller, addr, body })
-                    .map(|chunk_result| {
-                        (move || -> Result<Result<T::Item, T::Error>, Error> {
-                            let chunk = match chunk_result {
-                                Ok(ResponseChunk::Part(chunk)) => chunk,
-                                Ok(ResponseChunk::Full(chunk)) => chunk,
-                                Err(e) => return Err(e),
-                            };
-                            Ok(crate::serialization::from_read(Cursor::new(chunk))?)
-                        })()
-                    })
-                    .left_stream()
+                self.send_streaming(RpcRawCall {
+                    caller,
+                    addr,
+                    body,
+                    deadline: None,
+                    codec: Some(codec),
+                })
+                .map(|chunk_result| {
+                    (move || -> Result<Result<T::Item, T::Error>, Error> {
+                        let chunk = match chunk_result {
+                            Ok(ResponseChunk::Part(chunk)) => chunk,
+                            Ok(ResponseChunk::Full(chunk)) => chunk,
+                            Err(e) => return Err(e),
+                        };
+                        Ok(codec.from_read(Cursor::new(chunk))?)
+                    })()
+                })
+                .left_stream()
             })()
             .right_stream()
         }
@@ -321,12 +340,14 @@ impl Slot {
 
 pub struct Router {
     handlers: PrefixLookupBag<Slot>,
+    event_subscribers: HashMap<String, Vec<Recipient<RpcRawEvent>>>,
 }
 
 impl Router {
     fn new() -> Self {
         Router {
             handlers: PrefixLookupBag::default(),
+            event_subscribers: HashMap::new(),
         }
     }
 
@@ -421,34 +442,32 @@ impl Router {
             (if let Some(h) = slot.recipient() {
                 h.send(msg).map_err(Error::from).left_future()
             } else {
-                slot.send(RpcRawCall::from_envelope_addr(msg, addr))
-                    .then(|b| {
-                        future::ready(match b {
-                            Ok(b) => crate::serialization::from_read(std::io::Cursor::new(&b))
-                                .map_err(From::from),
-                            Err(e) => Err(e),
-                        })
-                    })
-                    .right_future()
-            })
-            .left_future()
-        } else {
-            RemoteRouter::from_registry()
-                .send(RpcRawCall::from_envelope_addr(msg, addr))
-                .then(|v| {
-                    future::ready(match v {
-                        Ok(v) => v,
-                        Err(e) => Err(e.into()),
-                    })
+                let codec = Codec::default();
+                (match RpcRawCall::from_envelope_addr(msg, addr, codec) {
+                    Ok(call) => slot.send(call).left_future(),
+                    Err(e) => future::err(e).right_future(),
                 })
-                .then(|b| {
+                .then(move |b| {
                     future::ready(match b {
-                        Ok(b) => crate::serialization::from_read(std::io::Cursor::new(&b))
+                        Ok(b) => codec
+                            .from_read(std::io::Cursor::new(&b))
                             .map_err(From::from),
                         Err(e) => Err(e),
                     })
                 })
                 .right_future()
+            })
+            .left_future()
+        } else {
+            async move {
+                let connection = RemoteRouter::from_registry().send(GetConnection).await??;
+                let codec = connection.codec().unwrap_or_default();
+                let call = RpcRawCall::from_envelope_addr(msg, addr, codec)?;
+                let b = connection.raw_call(call).await?;
+                Ok::<Result<T::Item, T::Error>, Error>(codec.from_read(Cursor::new(&b))?)
+            }
+            .boxed_local()
+            .right_future()
         }
     }
 
@@ -461,32 +480,35 @@ impl Router {
         let caller = "local".to_string();
         let addr = format!("{}/{}", addr, T::ID);
         if let Some(slot) = self.handlers.get_mut(&addr) {
-            slot.streaming_forward(caller, addr, msg).left_stream()
-        } else {
-            //use futures::StreamExt;
-            log::debug!("call remote");
-            let body = crate::serialization::to_vec(&msg).unwrap();
-            let (reply, tx) = futures::channel::mpsc::channel(16);
-            let call = RpcRawStreamCall {
+            return slot.streaming_forward(caller, addr, msg).left_stream();
+        }
+        log::debug!("call remote");
+        let call = async move {
+            let connection = RemoteRouter::from_registry().send(GetConnection).await??;
+            let codec = connection.codec().unwrap_or_default();
+            let call = RpcRawCall {
                 caller,
                 addr,
-                body,
-                reply,
+                body: codec.to_vec(&msg)?,
+                deadline: None,
+                codec: Some(codec),
             };
-            let _ = Arbiter::spawn(async move {
-                let v = RemoteRouter::from_registry().send(call).await;
-                log::debug!("call result={:?}", v);
-            });
-
-            tx.filter(|s| future::ready(s.as_ref().map(|s| !s.is_eos()).unwrap_or(true)))
-                .map(|b| {
+            let replies = connection
+                .raw_call_streaming(call)
+                .filter(|s| future::ready(s.as_ref().map(|s| !s.is_eos()).unwrap_or(true)))
+                .map(move |b| -> Result<Result<T::Item, T::Error>, Error> {
                     let body = b?.into_bytes();
-                    Ok(crate::serialization::from_read(std::io::Cursor::new(
-                        &body,
-                    ))?)
-                })
-                .right_stream()
-        }
+                    Ok(codec.from_read(Cursor::new(&body))?)
+                });
+            Ok::<_, Error>(replies)
+        };
+        call.map(|replies| match replies {
+            Ok(replies) => replies.left_stream(),
+            Err(e) => stream::once(future::err(e)).right_stream(),
+        })
+        .flatten_stream()
+        .boxed_local()
+        .right_stream()
     }
 
     pub fn forward_bytes(
@@ -495,20 +517,25 @@ impl Router {
         caller: &str,
         msg: Vec<u8>,
     ) -> impl Future<Output = Result<Vec<u8>, Error>> + Unpin {
-        if let Some(slot) = self.handlers.get_mut(addr) {
-            slot.send(RpcRawCall {
-                caller: caller.into(),
-                addr: addr.into(),
-                body: msg,
-            })
-            .left_future()
+        self.forward_raw(RpcRawCall {
+            caller: caller.into(),
+            addr: addr.into(),
+            body: msg,
+            deadline: None,
+            codec: None,
+        })
+    }
+
+    /// Same as `forward_bytes`, keeping the deadline and payload codec of `call`.
+    pub fn forward_raw(
+        &mut self,
+        call: RpcRawCall,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Unpin {
+        if let Some(slot) = self.handlers.get_mut(&call.addr) {
+            slot.send(call).left_future()
         } else {
             RemoteRouter::from_registry()
-                .send(RpcRawCall {
-                    caller: caller.into(),
-                    addr: addr.into(),
-                    body: msg,
-                })
+                .send(call)
                 .then(|v| match v {
                     Ok(r) => future::ready(r),
                     Err(e) => future::err(e.into()),
@@ -517,21 +544,86 @@ impl Router {
         }
     }
 
-    pub fn forward_bytes_local(
+    pub fn subscribe<E: BusEvent>(&mut self, handler: impl EventHandler<E> + 'static) -> Handle {
+        let recipient = into_actix::EventHandlerWrapper::new(handler)
+            .start()
+            .recipient();
+        let subscribers = self
+            .event_subscribers
+            .entry(E::TOPIC.to_string())
+            .or_default();
+        log::debug!("subscribing to event {}", E::TOPIC);
+        if subscribers.is_empty() && E::SCOPE == EventScope::Network {
+            RemoteRouter::from_registry().do_send(SubscribeTopic(E::TOPIC.to_string()));
+        }
+        subscribers.push(recipient);
+        Handle { _inner: () }
+    }
+
+    /// Delivers the event to local subscribers right away. The returned future
+    /// broadcasts network events through the router.
+    pub fn publish<E: BusEvent>(
         &mut self,
-        addr: &str,
         caller: &str,
-        msg: &[u8],
-    ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
-        if let Some(slot) = self.handlers.get_mut(addr) {
-            slot.send_streaming(RpcRawCall {
-                caller: caller.into(),
-                addr: addr.into(),
-                body: msg.into(),
+        event: &E,
+    ) -> impl Future<Output = Result<(), Error>> + Unpin {
+        let body = match event::encode(event) {
+            Ok(body) => body,
+            Err(e) => return future::err(e).left_future(),
+        };
+        self.dispatch_event(caller, E::TOPIC, body.as_slice().into());
+        if E::SCOPE == EventScope::Local {
+            return future::ok(()).left_future();
+        }
+        RemoteRouter::from_registry()
+            .send(BroadcastEvent {
+                caller: caller.to_string(),
+                topic: E::TOPIC.to_string(),
+                body,
+            })
+            .then(|v| {
+                future::ready(match v {
+                    Ok(v) => v,
+                    Err(e) => Err(e.into()),
+                })
             })
-            .left_stream()
+            .right_future()
+    }
+
+    /// Dispatches an event broadcast by the router to local subscribers.
+    pub fn dispatch_remote_event(&mut self, caller: &str, topic: &str, body: Vec<u8>) {
+        if event::is_own(&body) {
+            log::trace!("skipping own event {} echoed by the router", topic);
+            return;
+        }
+        self.dispatch_event(caller, topic, body.into())
+    }
+
+    fn dispatch_event(&mut self, caller: &str, topic: &str, body: Arc<[u8]>) {
+        let subscribers = match self.event_subscribers.get_mut(topic) {
+            Some(subscribers) => subscribers,
+            None => return,
+        };
+        subscribers.retain(|recipient| {
+            recipient
+                .do_send(RpcRawEvent {
+                    caller: caller.to_string(),
+                    body: body.clone(),
+                })
+                .map_err(|e| log::warn!("event {} not delivered: {}", topic, e))
+                .is_ok()
+        });
+    }
+
+    /// Dispatches a call received from the router to a local endpoint.
+    pub fn forward_raw_local(
+        &mut self,
+        call: RpcRawCall,
+    ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
+        if let Some(slot) = self.handlers.get_mut(&call.addr) {
+            slot.send_streaming(call).left_stream()
         } else {
-            log::warn!("no endpoint: {}", addr);
+            log::warn!("no endpoint: {}", call.addr);
             futures::stream::once(async { Err(Error::NoEndpoint) }).right_stream()
         }
     }
@@ -544,3 +636,86 @@ static ref ROUTER: Arc<Mutex<Router>> = Arc::new(Mutex::new(Router::new()));
 pub fn router() -> Arc<Mutex<Router>> {
     (*ROUTER).clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Ping(u32);
+
+    impl BusEvent for Ping {
+        const TOPIC: &'static str = "test/ping";
+    }
+
+    fn subscribe_ping(router: &mut Router) -> mpsc::UnboundedReceiver<Ping> {
+        let (tx, rx) = mpsc::unbounded();
+        router.subscribe(move |ping: Ping| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.unbounded_send(ping);
+            }
+        });
+        rx
+    }
+
+    #[actix_rt::test]
+    async fn published_events_reach_every_local_subscriber() {
+        let mut router = Router::new();
+        let mut first = subscribe_ping(&mut router);
+        let mut second = subscribe_ping(&mut router);
+
+        router.publish("local", &Ping(1)).await.unwrap();
+        assert_eq!(first.next().await, Some(Ping(1)));
+        assert_eq!(second.next().await, Some(Ping(1)));
+    }
+
+    #[actix_rt::test]
+    async fn own_events_echoed_by_router_are_skipped() {
+        let mut router = Router::new();
+        let mut rx = subscribe_ping(&mut router);
+
+        let own = event::encode(&Ping(1)).unwrap();
+        router.dispatch_remote_event("remote", Ping::TOPIC, own);
+        let foreign = serde_json::to_vec(&serde_json::json!({
+            "origin": "other-process",
+            "event": Ping(2),
+        }))
+        .unwrap();
+        router.dispatch_remote_event("remote", Ping::TOPIC, foreign);
+
+        assert_eq!(rx.next().await, Some(Ping(2)));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Echo(String);
+
+    impl RpcMessage for Echo {
+        const ID: &'static str = "Echo";
+        type Item = String;
+        type Error = ();
+    }
+
+    #[actix_rt::test]
+    async fn raw_calls_are_decoded_with_their_codec() {
+        let mut router = Router::new();
+        let _ = router.bind("/test", |echo: Echo| future::ok::<_, ()>(echo.0));
+
+        for codec in vec![Codec::Json, Codec::Msgpack] {
+            let reply = router
+                .forward_raw(RpcRawCall {
+                    caller: "local".into(),
+                    addr: "/test/Echo".into(),
+                    body: codec.to_vec(&Echo("hello".into())).unwrap(),
+                    deadline: None,
+                    codec: Some(codec),
+                })
+                .await
+                .unwrap();
+            let reply: Result<String, ()> = codec.from_read(reply.as_slice()).unwrap();
+            assert_eq!(reply, Ok("hello".to_string()), "{:?}", codec);
+        }
+    }
+}
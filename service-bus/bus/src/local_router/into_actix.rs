@@ -1,5 +1,5 @@
 use actix::prelude::*;
-use futures::SinkExt;
+use futures::{FutureExt, SinkExt};
 use std::marker::PhantomData;
 
 use crate::*;
@@ -57,3 +57,31 @@ impl<T: RpcStreamMessage, H: RpcStreamHandler<T> + 'static> Handler<RpcStreamCal
         ActorResponse::r#async(send_all.into_actor(self))
     }
 }
+
+pub struct EventHandlerWrapper<E, H>(pub(super) H, PhantomData<E>);
+
+impl<E, H> EventHandlerWrapper<E, H> {
+    pub fn new(h: H) -> Self {
+        EventHandlerWrapper(h, PhantomData)
+    }
+}
+
+impl<E: 'static, H: 'static> Actor for EventHandlerWrapper<E, H> {
+    type Context = Context<Self>;
+}
+
+impl<E: 'static, H: 'static> Unpin for EventHandlerWrapper<E, H> {}
+
+impl<E: BusEvent, H: EventHandler<E> + 'static> Handler<event::RpcRawEvent>
+    for EventHandlerWrapper<E, H>
+{
+    type Result = ActorResponse<Self, (), Error>;
+
+    fn handle(&mut self, msg: event::RpcRawEvent, _ctx: &mut Self::Context) -> Self::Result {
+        let event: E = match event::decode(&msg.body) {
+            Ok(event) => event,
+            Err(e) => return ActorResponse::reply(Err(e)),
+        };
+        ActorResponse::r#async(self.0.handle(msg.caller, event).map(Ok).into_actor(self))
+    }
+}
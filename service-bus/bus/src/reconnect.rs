@@ -0,0 +1,426 @@
+//! Connection to a remote router that survives transport failures.
+//!
+//! `ReconnectingConnection` remembers every service bound and topic subscribed
+//! through it. Whenever the underlying connection drops, it reconnects with
+//! exponential backoff and registers them again on the new connection.
+use futures::{channel::mpsc, prelude::*};
+use std::{cell::RefCell, collections::HashSet, io, rc::Rc, time::Duration};
+
+use ya_sb_proto::codec::{GsbMessage, ProtocolError};
+
+use crate::connection::{self, CallRequestHandler, ConnectionRef};
+use crate::{Error, ResponseChunk, RpcRawCall};
+
+/// Delays between reconnection attempts. Starting at `min_delay`, the delay
+/// doubles after each failed attempt up to `max_delay`.
+#[derive(Clone, Debug)]
+pub struct ReconnectConfig {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            min_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Changes of the connection state, see `ReconnectingConnection::events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Connection was lost. Calls fail until it is reestablished.
+    Disconnected,
+    /// Connection was reestablished after `attempts` tries and all
+    /// registrations were replayed.
+    Reconnected { attempts: u32 },
+    /// Attempt to reconnect failed, next one follows after `retry_in`.
+    ReconnectFailed { attempts: u32, retry_in: Duration },
+}
+
+struct State<Transport, H>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    connection: Option<ConnectionRef<Transport, H>>,
+    bindings: HashSet<String>,
+    topics: HashSet<String>,
+    listeners: Vec<mpsc::UnboundedSender<ConnectionEvent>>,
+}
+
+impl<Transport, H> State<Transport, H>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    fn emit(&mut self, event: ConnectionEvent) {
+        self.listeners
+            .retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+pub struct ReconnectingConnection<Transport, H>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    state: Rc<RefCell<State<Transport, H>>>,
+}
+
+impl<Transport, H> Clone for ReconnectingConnection<Transport, H>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    fn clone(&self) -> Self {
+        ReconnectingConnection {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<Transport, H> ReconnectingConnection<Transport, H>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError>
+        + Stream<Item = Result<GsbMessage, ProtocolError>>
+        + Unpin
+        + 'static,
+    H: CallRequestHandler + Clone + Unpin + 'static,
+{
+    fn current(&self) -> Result<ConnectionRef<Transport, H>, Error> {
+        self.state.borrow().connection.clone().ok_or(Error::Closed)
+    }
+
+    pub fn connected(&self) -> bool {
+        self.current().map(|c| c.connected()).unwrap_or(false)
+    }
+
+    /// Stream of connection state changes. Dropping it unsubscribes.
+    pub fn events(&self) -> mpsc::UnboundedReceiver<ConnectionEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.state.borrow_mut().listeners.push(tx);
+        rx
+    }
+
+    /// Binds `addr` on the router, now and after every reconnect.
+    ///
+    /// While disconnected the binding is only recorded and succeeds.
+    pub fn bind(
+        &self,
+        addr: impl Into<String>,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        let addr = addr.into();
+        let state = self.state.clone();
+        let connection = {
+            let mut state = state.borrow_mut();
+            state.bindings.insert(addr.clone());
+            state.connection.clone()
+        };
+        async move {
+            if let Some(connection) = connection {
+                if let Err(e) = connection.bind(addr.clone()).await {
+                    state.borrow_mut().bindings.remove(&addr);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub fn unbind(
+        &self,
+        addr: impl Into<String>,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        let addr = addr.into();
+        let connection = {
+            let mut state = self.state.borrow_mut();
+            state.bindings.remove(&addr);
+            state.connection.clone()
+        };
+        async move {
+            match connection {
+                Some(connection) => connection.unbind(addr).await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Subscribes `topic` on the router, now and after every reconnect.
+    ///
+    /// While disconnected the subscription is only recorded and succeeds.
+    pub fn subscribe(
+        &self,
+        topic: impl Into<String>,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        let topic = topic.into();
+        let state = self.state.clone();
+        let connection = {
+            let mut state = state.borrow_mut();
+            state.topics.insert(topic.clone());
+            state.connection.clone()
+        };
+        async move {
+            if let Some(connection) = connection {
+                if let Err(e) = connection.subscribe(topic.clone()).await {
+                    state.borrow_mut().topics.remove(&topic);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub fn unsubscribe(
+        &self,
+        topic: impl Into<String>,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        let topic = topic.into();
+        let connection = {
+            let mut state = self.state.borrow_mut();
+            state.topics.remove(&topic);
+            state.connection.clone()
+        };
+        async move {
+            match connection {
+                Some(connection) => connection.unsubscribe(topic).await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    pub fn broadcast(
+        &self,
+        caller: impl Into<String>,
+        topic: impl Into<String>,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        match self.current() {
+            Ok(connection) => connection.broadcast(caller, topic, body).left_future(),
+            Err(e) => future::err(e).right_future(),
+        }
+    }
+
+    pub fn call(
+        &self,
+        caller: impl Into<String>,
+        addr: impl Into<String>,
+        body: impl Into<Vec<u8>>,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> {
+        match self.current() {
+            Ok(connection) => connection.call(caller, addr, body).left_future(),
+            Err(e) => future::err(e).right_future(),
+        }
+    }
+
+    /// Same as `call`, keeping the deadline and payload codec set by the caller.
+    pub fn raw_call(&self, call: RpcRawCall) -> impl Future<Output = Result<Vec<u8>, Error>> {
+        match self.current() {
+            Ok(connection) => connection.raw_call(call).left_future(),
+            Err(e) => future::err(e).right_future(),
+        }
+    }
+
+    pub fn call_streaming(
+        &self,
+        caller: impl Into<String>,
+        addr: impl Into<String>,
+        body: impl Into<Vec<u8>>,
+    ) -> impl Stream<Item = Result<ResponseChunk, Error>> {
+        match self.current() {
+            Ok(connection) => connection.call_streaming(caller, addr, body).left_stream(),
+            Err(e) => stream::once(future::err(e)).right_stream(),
+        }
+    }
+
+    /// Watches the current connection and replaces it once it is lost.
+    async fn supervise<F, Fut>(self, config: ReconnectConfig, connect: F, handler: H)
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = io::Result<Transport>>,
+    {
+        loop {
+            let connection = match self.current() {
+                Ok(connection) => connection,
+                Err(_) => return,
+            };
+            connection.closed().await;
+            {
+                let mut state = self.state.borrow_mut();
+                state.connection = None;
+                state.emit(ConnectionEvent::Disconnected);
+            }
+            log::warn!("lost connection to router, reconnecting");
+
+            let mut delay = config.min_delay;
+            let mut attempts = 0;
+            loop {
+                tokio::time::delay_for(delay).await;
+                attempts += 1;
+                match connect().await {
+                    Ok(transport) => {
+                        let connection =
+                            connection::connect_with_handler(transport, handler.clone());
+                        // registrations made after the snapshot go to the new connection
+                        // directly, so none of them is lost or registered twice
+                        let (bindings, topics) = {
+                            let mut state = self.state.borrow_mut();
+                            state.connection = Some(connection.clone());
+                            (state.bindings.clone(), state.topics.clone())
+                        };
+                        replay(&connection, bindings, topics).await;
+                        self.state
+                            .borrow_mut()
+                            .emit(ConnectionEvent::Reconnected { attempts });
+                        log::info!("reconnected to router after {} attempt(s)", attempts);
+                        break;
+                    }
+                    Err(e) => {
+                        delay = (delay * 2).min(config.max_delay);
+                        log::debug!("reconnect attempt {} failed: {}", attempts, e);
+                        self.state
+                            .borrow_mut()
+                            .emit(ConnectionEvent::ReconnectFailed {
+                                attempts,
+                                retry_in: delay,
+                            });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Registers recorded bindings and topics on a fresh connection.
+async fn replay<Transport, H>(
+    connection: &ConnectionRef<Transport, H>,
+    bindings: HashSet<String>,
+    topics: HashSet<String>,
+) where
+    Transport: Sink<GsbMessage, Error = ProtocolError> + Unpin + 'static,
+    H: CallRequestHandler + 'static,
+{
+    for addr in bindings {
+        if let Err(e) = connection.bind(addr.clone()).await {
+            log::error!("failed to bind {} after reconnect: {}", addr, e);
+        }
+    }
+    for topic in topics {
+        if let Err(e) = connection.subscribe(topic.clone()).await {
+            log::error!("failed to subscribe {} after reconnect: {}", topic, e);
+        }
+    }
+    log::debug!("replayed registrations on new connection");
+}
+
+/// Connects through `connect` and keeps reconnecting whenever the connection drops.
+///
+/// The first attempt is made immediately and its failure is returned to the caller.
+/// Each connection gets its own clone of `handler`.
+pub async fn connect_with_handler<Transport, H, F, Fut>(
+    config: ReconnectConfig,
+    connect: F,
+    handler: H,
+) -> io::Result<ReconnectingConnection<Transport, H>>
+where
+    Transport: Sink<GsbMessage, Error = ProtocolError>
+        + Stream<Item = Result<GsbMessage, ProtocolError>>
+        + Unpin
+        + 'static,
+    H: CallRequestHandler + Clone + Unpin + 'static,
+    F: Fn() -> Fut + 'static,
+    Fut: Future<Output = io::Result<Transport>> + 'static,
+{
+    let transport = connect().await?;
+    let connection = connection::connect_with_handler(transport, handler.clone());
+    let reconnecting = ReconnectingConnection {
+        state: Rc::new(RefCell::new(State {
+            connection: Some(connection),
+            bindings: Default::default(),
+            topics: Default::default(),
+            listeners: Default::default(),
+        })),
+    };
+    actix::Arbiter::spawn(reconnecting.clone().supervise(config, connect, handler));
+    Ok(reconnecting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::test_transport::transport;
+    use std::cell::RefCell;
+    use ya_sb_proto::{RegisterReply, SubscribeReply};
+
+    /// Answers registrations made over one transport, recording them in `log`.
+    fn serve_router(
+        router_tx: mpsc::UnboundedSender<GsbMessage>,
+        mut router_rx: mpsc::UnboundedReceiver<GsbMessage>,
+        log: Rc<RefCell<Vec<String>>>,
+    ) {
+        actix::Arbiter::spawn(async move {
+            while let Some(msg) = router_rx.next().await {
+                let reply = match msg {
+                    GsbMessage::RegisterRequest(request) => {
+                        log.borrow_mut().push(request.service_id);
+                        GsbMessage::RegisterReply(RegisterReply::default())
+                    }
+                    GsbMessage::SubscribeRequest(request) => {
+                        log.borrow_mut().push(request.topic);
+                        GsbMessage::SubscribeReply(SubscribeReply::default())
+                    }
+                    msg => panic!("unexpected message: {:?}", msg),
+                };
+                let _ = router_tx.unbounded_send(reply);
+            }
+        })
+    }
+
+    #[actix_rt::test]
+    async fn registrations_are_replayed_after_reconnect() {
+        let (routers_tx, mut routers) = mpsc::unbounded();
+        let connect = move || {
+            let (transport, router_tx, router_rx) = transport();
+            routers_tx.unbounded_send((router_tx, router_rx)).unwrap();
+            future::ok::<_, io::Error>(transport)
+        };
+        let config = ReconnectConfig {
+            min_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+        };
+        let handler = |_: String, _: String, _: String, _: Vec<u8>| {
+            stream::empty::<Result<ResponseChunk, Error>>()
+        };
+        let connection = connect_with_handler(config, connect, handler)
+            .await
+            .unwrap();
+        let mut events = connection.events();
+
+        let (router_tx, router_rx) = routers.next().await.unwrap();
+        let first_log = Rc::new(RefCell::new(Vec::new()));
+        serve_router(router_tx.clone(), router_rx, first_log.clone());
+        connection.bind("/public/test").await.unwrap();
+        connection.subscribe("test-topic").await.unwrap();
+        assert_eq!(*first_log.borrow(), vec!["/public/test", "test-topic"]);
+
+        router_tx.close_channel();
+        assert_eq!(events.next().await, Some(ConnectionEvent::Disconnected));
+        assert!(!connection.connected());
+        // recorded only, registered once reconnected
+        connection.bind("/public/late").await.unwrap();
+
+        let (router_tx, router_rx) = routers.next().await.unwrap();
+        let second_log = Rc::new(RefCell::new(Vec::new()));
+        serve_router(router_tx, router_rx, second_log.clone());
+        assert_eq!(
+            events.next().await,
+            Some(ConnectionEvent::Reconnected { attempts: 1 })
+        );
+        let mut replayed = second_log.borrow().clone();
+        replayed.sort();
+        assert_eq!(replayed, vec!["/public/late", "/public/test", "test-topic"]);
+        assert!(connection.connected());
+    }
+}
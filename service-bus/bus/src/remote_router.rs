@@ -4,15 +4,16 @@ use std::{collections::HashSet, time::Duration};
 
 use crate::{
     connection::{self, ConnectionRef, LocalRouterHandler, TcpTransport},
-    Error, RpcRawCall, RpcRawStreamCall,
+    Codec, Error, RpcRawCall, RpcRawStreamCall,
 };
 
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
 
-type RemoteConncetion = ConnectionRef<TcpTransport, LocalRouterHandler>;
+pub(crate) type RemoteConncetion = ConnectionRef<TcpTransport, LocalRouterHandler>;
 
 pub struct RemoteRouter {
     local_bindings: HashSet<String>,
+    local_topics: HashSet<String>,
     pending_calls: Vec<oneshot::Sender<RemoteConncetion>>,
     connection: Option<RemoteConncetion>,
 }
@@ -39,23 +40,44 @@ impl RemoteRouter {
         let connect_fut = connection::tcp(addr)
             .map_err(move |e| Error::BusConnectionFail(addr, e))
             .into_actor(self)
-            .then(|tcp_transport, act, ctx| {
+            .then(|tcp_transport, act, _ctx| {
                 let tcp_transport = match tcp_transport {
                     Ok(v) => v,
                     Err(e) => return fut::Either::Left(fut::err(e)),
                 };
-                let connection = connection::connect(tcp_transport);
+                let connection: RemoteConncetion = connection::connect(tcp_transport);
+                let token = ya_sb_proto::gsb_auth_token().unwrap_or_default();
+                let hello = connection
+                    .hello(app_name(), token, Codec::preferred())
+                    .map_ok(move |_codec| connection);
+                fut::Either::Right(hello.into_actor(act))
+            })
+            .then(|connection: Result<RemoteConncetion, Error>, act, ctx| {
+                let connection = match connection {
+                    Ok(v) => v,
+                    Err(e) => return fut::Either::Left(fut::err(e)),
+                };
                 act.connection = Some(connection.clone());
                 act.clean_pending_calls(connection.clone(), ctx);
+                let topics = act.local_topics.clone();
+                let bindings = future::try_join_all(
+                    act.local_bindings
+                        .clone()
+                        .into_iter()
+                        .map(|service_id| connection.bind(service_id)),
+                );
                 fut::Either::Right(
-                    future::try_join_all(
-                        act.local_bindings
-                            .clone()
-                            .into_iter()
-                            .map(move |service_id| connection.bind(service_id)),
-                    )
-                    .and_then(|_| async { Ok(log::debug!("registered all services")) })
-                    .into_actor(act),
+                    bindings
+                        .and_then(move |_| {
+                            log::debug!("registered all services");
+                            future::try_join_all(
+                                topics
+                                    .into_iter()
+                                    .map(move |topic| connection.subscribe(topic)),
+                            )
+                        })
+                        .and_then(|_| async { Ok(log::debug!("subscribed all topics")) })
+                        .into_actor(act),
                 )
             })
             .then(|v: Result<(), Error>, _, _| {
@@ -98,11 +120,19 @@ impl RemoteRouter {
     }
 }
 
+fn app_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+        .unwrap_or_default()
+}
+
 impl Default for RemoteRouter {
     fn default() -> Self {
         Self {
             connection: Default::default(),
             local_bindings: Default::default(),
+            local_topics: Default::default(),
             pending_calls: Default::default(),
         }
     }
@@ -161,13 +191,74 @@ impl Handler<UpdateService> for RemoteRouter {
     }
 }
 
+pub struct SubscribeTopic(pub String);
+
+impl Message for SubscribeTopic {
+    type Result = ();
+}
+
+impl Handler<SubscribeTopic> for RemoteRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeTopic, _ctx: &mut Self::Context) -> Self::Result {
+        let topic = msg.0;
+        if let Some(c) = &mut self.connection {
+            Arbiter::spawn(
+                c.subscribe(topic.clone()).then(|v| async {
+                    v.unwrap_or_else(|e| log::error!("subscribe error: {}", e))
+                }),
+            )
+        }
+        log::trace!("Subscribing topic '{}'", topic);
+        self.local_topics.insert(topic);
+    }
+}
+
+pub struct BroadcastEvent {
+    pub caller: String,
+    pub topic: String,
+    pub body: Vec<u8>,
+}
+
+impl Message for BroadcastEvent {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<BroadcastEvent> for RemoteRouter {
+    type Result = ActorResponse<Self, (), Error>;
+
+    fn handle(&mut self, msg: BroadcastEvent, _ctx: &mut Self::Context) -> Self::Result {
+        ActorResponse::r#async(
+            self.connection()
+                .and_then(|connection| connection.broadcast(msg.caller, msg.topic, msg.body))
+                .into_actor(self),
+        )
+    }
+}
+
+/// Resolves to the connection to the router once it is established, for calls
+/// whose typed payloads are to be encoded with the codec it negotiated.
+pub(crate) struct GetConnection;
+
+impl Message for GetConnection {
+    type Result = Result<RemoteConncetion, Error>;
+}
+
+impl Handler<GetConnection> for RemoteRouter {
+    type Result = ActorResponse<Self, RemoteConncetion, Error>;
+
+    fn handle(&mut self, _msg: GetConnection, _ctx: &mut Self::Context) -> Self::Result {
+        ActorResponse::r#async(self.connection().into_actor(self))
+    }
+}
+
 impl Handler<RpcRawCall> for RemoteRouter {
     type Result = ActorResponse<Self, Vec<u8>, Error>;
 
     fn handle(&mut self, msg: RpcRawCall, _ctx: &mut Self::Context) -> Self::Result {
         ActorResponse::r#async(
             self.connection()
-                .and_then(|connection| connection.call(msg.caller, msg.addr, msg.body))
+                .and_then(|connection| connection.raw_call(msg))
                 .into_actor(self),
         )
     }
@@ -183,11 +274,16 @@ impl Handler<RpcRawStreamCall> for RemoteRouter {
                     let reply = msg.reply.sink_map_err(|e| Error::GsbFailure(e.to_string()));
                     futures::pin_mut!(reply);
 
+                    let call = RpcRawCall {
+                        caller: msg.caller,
+                        addr: msg.addr,
+                        body: msg.body,
+                        deadline: msg.deadline,
+                        codec: msg.codec,
+                    };
                     let result = SinkExt::send_all(
                         &mut reply,
-                        &mut connection
-                            .call_streaming(msg.caller, msg.addr, msg.body)
-                            .map(|v| Ok(v)),
+                        &mut connection.raw_call_streaming(call).map(|v| Ok(v)),
                     )
                     .await;
                     result
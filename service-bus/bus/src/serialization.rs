@@ -1,33 +1,142 @@
-#[cfg(feature = "msgpack")]
-pub use rmp_serde::{
-    decode::Error as DecodeError, encode::Error as EncodeError, from_read, to_vec_named as to_vec,
-};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Read;
+use std::sync::atomic::{AtomicU8, Ordering};
 
-#[allow(dead_code)]
-#[cfg(feature = "json")]
-mod json {
-    use serde_json::Error;
+use ya_sb_proto::PayloadCodec;
 
-    #[derive(Debug, thiserror::Error)]
-    #[error("{0}")]
-    pub struct DecodeError(#[from] Error);
+/// Encoding of typed `RpcMessage` payloads.
+///
+/// Every build decodes all codecs. Cargo features only select the default one,
+/// used for untagged payloads and on connections which did not negotiate a codec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    Json,
+    Msgpack,
+    Cbor,
+}
+
+impl Default for Codec {
+    #[cfg(feature = "msgpack")]
+    fn default() -> Self {
+        Codec::Msgpack
+    }
+
+    #[cfg(not(feature = "msgpack"))]
+    fn default() -> Self {
+        Codec::Json
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct DecodeError(String);
 
-    #[derive(Debug, thiserror::Error)]
-    #[error("{0}")]
-    pub struct EncodeError(Error);
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct EncodeError(String);
+
+impl Codec {
+    const ALL: [Codec; 3] = [Codec::Msgpack, Codec::Cbor, Codec::Json];
+
+    /// Codecs offered during GSB hello, binary ones first.
+    pub fn preferred() -> Vec<Codec> {
+        Self::ALL.to_vec()
+    }
+
+    pub fn to_vec<T: Serialize>(self, value: &T) -> Result<Vec<u8>, EncodeError> {
+        match self {
+            Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Codec::Msgpack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Codec::Cbor => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        }
+        .map_err(EncodeError)
+    }
+
+    pub fn from_read<T: DeserializeOwned, R: Read>(self, read: R) -> Result<T, DecodeError> {
+        match self {
+            Codec::Json => serde_json::from_reader(read).map_err(|e| e.to_string()),
+            Codec::Msgpack => rmp_serde::from_read(read).map_err(|e| e.to_string()),
+            Codec::Cbor => serde_cbor::from_reader(read).map_err(|e| e.to_string()),
+        }
+        .map_err(DecodeError)
+    }
 
-    #[inline]
-    pub fn to_vec<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, EncodeError> {
-        serde_json::to_vec(value).map_err(EncodeError)
+    pub(crate) fn from_proto(codec: i32) -> Option<Codec> {
+        match PayloadCodec::from_i32(codec)? {
+            PayloadCodec::Raw => None,
+            PayloadCodec::Json => Some(Codec::Json),
+            PayloadCodec::Msgpack => Some(Codec::Msgpack),
+            PayloadCodec::Cbor => Some(Codec::Cbor),
+        }
     }
 
-    #[inline]
-    pub fn from_read<T: serde::de::DeserializeOwned, R: std::io::Read>(
-        read: R,
-    ) -> Result<T, DecodeError> {
-        serde_json::from_reader(read).map_err(DecodeError)
+    pub(crate) fn to_proto(codec: Option<Codec>) -> PayloadCodec {
+        match codec {
+            None => PayloadCodec::Raw,
+            Some(Codec::Json) => PayloadCodec::Json,
+            Some(Codec::Msgpack) => PayloadCodec::Msgpack,
+            Some(Codec::Cbor) => PayloadCodec::Cbor,
+        }
     }
 }
 
-#[cfg(feature = "json")]
-pub use json::*;
+/// `PayloadCodec` value negotiated by a single connection, `Raw` until then.
+#[derive(Debug, Default)]
+pub(crate) struct NegotiatedCodec(AtomicU8);
+
+impl NegotiatedCodec {
+    pub fn get(&self) -> Option<Codec> {
+        Codec::from_proto(self.0.load(Ordering::Relaxed) as i32)
+    }
+
+    pub fn set(&self, codec: Option<Codec>) {
+        self.0
+            .store(Codec::to_proto(codec) as u8, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        name: String,
+        data: Vec<u8>,
+        fields: HashMap<String, Option<u64>>,
+    }
+
+    #[test]
+    fn every_codec_round_trips() {
+        let payload = Payload {
+            name: "payload".to_string(),
+            data: vec![0, 1, 255],
+            fields: vec![("some".to_string(), Some(7)), ("none".to_string(), None)]
+                .into_iter()
+                .collect(),
+        };
+        for codec in Codec::preferred() {
+            let bytes = codec.to_vec(&payload).unwrap();
+            let decoded: Payload = codec.from_read(bytes.as_slice()).unwrap();
+            assert_eq!(decoded, payload, "{:?}", codec);
+            assert_eq!(
+                Codec::from_proto(Codec::to_proto(Some(codec)) as i32),
+                Some(codec)
+            );
+        }
+    }
+
+    #[test]
+    fn binary_codecs_are_offered_first() {
+        assert_eq!(
+            Codec::preferred(),
+            vec![Codec::Msgpack, Codec::Cbor, Codec::Json]
+        );
+        let negotiated = NegotiatedCodec::default();
+        assert_eq!(negotiated.get(), None);
+        negotiated.set(Some(Codec::Cbor));
+        assert_eq!(negotiated.get(), Some(Codec::Cbor));
+    }
+}
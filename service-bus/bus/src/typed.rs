@@ -1,12 +1,15 @@
 use crate::error::Error;
 use crate::local_router::{router, Router};
+use crate::timeout::IntoDuration;
 use crate::{
-    Handle, RpcEndpoint, RpcEnvelope, RpcHandler, RpcMessage, RpcStreamHandler, RpcStreamMessage,
+    BusEvent, EventHandler, Handle, RpcEndpoint, RpcEnvelope, RpcHandler, RpcMessage,
+    RpcStreamHandler, RpcStreamMessage,
 };
 use futures::prelude::*;
 use futures::FutureExt;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 /// Binds RpcHandler to given service address.
 ///
@@ -61,10 +64,48 @@ where
     router().lock().unwrap().bind(addr, WithCaller(f))
 }
 
+/// Calls `handler` with every published event of type `E`.
+///
+/// ## Example
+///
+/// ```no_run
+/// use ya_service_bus::{typed as bus, BusEvent, EventScope};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Clone, Debug, Serialize, Deserialize)]
+/// struct OfferPublished(String);
+///
+/// impl BusEvent for OfferPublished {
+///     const TOPIC: &'static str = "market/offers";
+///     const SCOPE: EventScope = EventScope::Network;
+/// }
+///
+/// async fn run() {
+///     let _ = bus::subscribe(|e: OfferPublished| async move {
+///         log::info!("published {:?}", e)
+///     });
+///     let _ = bus::publish(OfferPublished("offer-1".into())).await;
+/// }
+/// ```
+#[inline]
+pub fn subscribe<E: BusEvent>(handler: impl EventHandler<E> + 'static) -> Handle {
+    router().lock().unwrap().subscribe(handler)
+}
+
+/// Publishes `event` to its subscribers, see [`EventScope`](crate::EventScope).
+///
+/// Local subscribers are notified immediately, the returned future only
+/// reports the broadcast of network events through the router.
+#[inline]
+pub fn publish<E: BusEvent>(event: E) -> impl Future<Output = Result<(), Error>> + Unpin {
+    router().lock().unwrap().publish("local", &event)
+}
+
 #[derive(Clone)]
 pub struct Endpoint {
     router: Arc<Mutex<Router>>,
     addr: String,
+    timeout: Option<Duration>,
 }
 
 impl Endpoint {
@@ -73,14 +114,19 @@ impl Endpoint {
         self.addr.as_ref()
     }
 
+    /// Sets deadline of every call made through this endpoint to `timeout` from the call.
+    /// Calls not answered in time fail with `Error::Timeout` (or `GsbFailure` reported by
+    /// the router) and the called service can observe the deadline in `RpcEnvelope`.
+    pub fn with_timeout(mut self, timeout: impl IntoDuration) -> Self {
+        self.timeout = Some(timeout.into_duration());
+        self
+    }
+
     pub fn call<T: RpcMessage + Unpin>(
         &self,
         msg: T,
     ) -> impl Future<Output = Result<Result<T::Item, T::Error>, Error>> + Unpin {
-        self.router
-            .lock()
-            .unwrap()
-            .forward(&self.addr, RpcEnvelope::local(msg))
+        self.forward(RpcEnvelope::local(msg))
     }
 
     pub fn call_as<T: RpcMessage + Unpin>(
@@ -88,10 +134,30 @@ impl Endpoint {
         caller: impl ToString,
         msg: T,
     ) -> impl Future<Output = Result<Result<T::Item, T::Error>, Error>> + Unpin {
-        self.router
-            .lock()
-            .unwrap()
-            .forward(&self.addr, RpcEnvelope::with_caller(caller, msg))
+        self.forward(RpcEnvelope::with_caller(caller, msg))
+    }
+
+    fn forward<T: RpcMessage + Unpin>(
+        &self,
+        msg: RpcEnvelope<T>,
+    ) -> impl Future<Output = Result<Result<T::Item, T::Error>, Error>> + Unpin {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => {
+                return self
+                    .router
+                    .lock()
+                    .unwrap()
+                    .forward(&self.addr, msg)
+                    .left_future()
+            }
+        };
+        let msg = msg.with_deadline(SystemTime::now() + timeout);
+        let call = self.router.lock().unwrap().forward(&self.addr, msg);
+        tokio::time::timeout(timeout, call)
+            .map(|result| result.unwrap_or(Err(Error::Timeout)))
+            .boxed_local()
+            .right_future()
     }
 
     pub fn call_streaming<T: RpcStreamMessage>(
@@ -124,6 +190,7 @@ pub fn service(addr: impl Into<String>) -> Endpoint {
     Endpoint {
         router: router(),
         addr: addr.into(),
+        timeout: None,
     }
 }
 
@@ -140,6 +207,16 @@ impl<
     }
 }
 
+impl<E: BusEvent, Output: Future<Output = ()> + 'static, F: FnMut(E) -> Output + 'static>
+    EventHandler<E> for F
+{
+    type Result = Output;
+
+    fn handle(&mut self, _caller: String, event: E) -> Self::Result {
+        self(event)
+    }
+}
+
 struct WithCaller<F>(F);
 
 impl<
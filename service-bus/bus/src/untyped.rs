@@ -1,6 +1,7 @@
 use super::Handle;
 use crate::error::Error;
 use crate::local_router::router;
+use crate::RpcRawCall;
 
 use futures::Future;
 
@@ -15,10 +16,21 @@ pub fn send(
         .forward_bytes(addr, caller, bytes.into())
 }
 
+/// Same as `send`, keeping the deadline and payload codec of `call`.
+pub fn send_raw(call: RpcRawCall) -> impl Future<Output = Result<Vec<u8>, Error>> + Unpin {
+    router().lock().unwrap().forward_raw(call)
+}
+
 pub trait RawHandler {
     type Result: Future<Output = Result<Vec<u8>, Error>>;
 
     fn handle(&mut self, caller: &str, addr: &str, msg: &[u8]) -> Self::Result;
+
+    /// Same as `handle`, for handlers interested in the deadline and payload codec
+    /// set by the caller.
+    fn handle_raw(&mut self, call: RpcRawCall) -> Self::Result {
+        self.handle(&call.caller, &call.addr, call.body.as_ref())
+    }
 }
 
 impl<
@@ -33,6 +45,29 @@ impl<
     }
 }
 
+/// Handler of whole `RpcRawCall`s, see `subscribe_raw`.
+struct RawCallHandler<F>(F);
+
+impl<Output: Future<Output = Result<Vec<u8>, Error>>, F: FnMut(RpcRawCall) -> Output + 'static>
+    RawHandler for RawCallHandler<F>
+{
+    type Result = Output;
+
+    fn handle(&mut self, caller: &str, addr: &str, msg: &[u8]) -> Self::Result {
+        self.handle_raw(RpcRawCall {
+            caller: caller.into(),
+            addr: addr.into(),
+            body: msg.into(),
+            deadline: None,
+            codec: None,
+        })
+    }
+
+    fn handle_raw(&mut self, call: RpcRawCall) -> Self::Result {
+        (self.0)(call)
+    }
+}
+
 mod raw_actor {
     use super::{Error, RawHandler};
     use crate::RpcRawCall;
@@ -51,12 +86,7 @@ mod raw_actor {
         type Result = ActorResponse<Self, Vec<u8>, Error>;
 
         fn handle(&mut self, msg: RpcRawCall, _ctx: &mut Self::Context) -> Self::Result {
-            ActorResponse::r#async(
-                self.inner
-                    .handle(&msg.caller, &msg.addr, msg.body.as_ref())
-                    .boxed_local()
-                    .into_actor(self),
-            )
+            ActorResponse::r#async(self.inner.handle_raw(msg).boxed_local().into_actor(self))
         }
     }
 
@@ -71,3 +101,12 @@ pub fn subscribe(addr: &str, h: impl RawHandler + Unpin + 'static) -> Handle {
         .unwrap()
         .bind_raw(addr, raw_actor::recipient(h))
 }
+
+/// Same as `subscribe`, for handlers forwarding calls with their deadline and payload codec.
+pub fn subscribe_raw<Output, F>(addr: &str, h: F) -> Handle
+where
+    Output: Future<Output = Result<Vec<u8>, Error>>,
+    F: FnMut(RpcRawCall) -> Output + Unpin + 'static,
+{
+    subscribe(addr, RawCallHandler(h))
+}
@@ -62,6 +62,10 @@ pub enum GsbMessage {
     BroadcastReply(BroadcastReply),
     Ping,
     Pong,
+    HelloRequest(HelloRequest),
+    HelloReply(HelloReply),
+    CancelCall(CancelCall),
+    CallCredit(CallCredit),
 }
 
 impl GsbMessage {
@@ -81,10 +85,26 @@ impl GsbMessage {
             GsbMessage::BroadcastReply(msg) => (MessageType::BroadcastReply, Box::new(msg)),
             GsbMessage::Ping => (MessageType::Ping, Box::new(Ping {})),
             GsbMessage::Pong => (MessageType::Pong, Box::new(Pong {})),
+            GsbMessage::HelloRequest(msg) => (MessageType::HelloRequest, Box::new(msg)),
+            GsbMessage::HelloReply(msg) => (MessageType::HelloReply, Box::new(msg)),
+            GsbMessage::CancelCall(msg) => (MessageType::CancelCall, Box::new(msg)),
+            GsbMessage::CallCredit(msg) => (MessageType::CallCredit, Box::new(msg)),
         }
     }
 }
 
+impl Into<GsbMessage> for HelloRequest {
+    fn into(self) -> GsbMessage {
+        GsbMessage::HelloRequest(self)
+    }
+}
+
+impl Into<GsbMessage> for HelloReply {
+    fn into(self) -> GsbMessage {
+        GsbMessage::HelloReply(self)
+    }
+}
+
 impl Into<GsbMessage> for RegisterRequest {
     fn into(self) -> GsbMessage {
         GsbMessage::RegisterRequest(self)
@@ -115,6 +135,18 @@ impl Into<GsbMessage> for CallRequest {
     }
 }
 
+impl Into<GsbMessage> for CancelCall {
+    fn into(self) -> GsbMessage {
+        GsbMessage::CancelCall(self)
+    }
+}
+
+impl Into<GsbMessage> for CallCredit {
+    fn into(self) -> GsbMessage {
+        GsbMessage::CallCredit(self)
+    }
+}
+
 impl Into<GsbMessage> for CallReply {
     fn into(self) -> GsbMessage {
         GsbMessage::CallReply(self)
@@ -208,6 +240,10 @@ fn decode_message(
             Some(MessageType::BroadcastReply) => BroadcastReply::decode(buf.as_ref())?.into(),
             Some(MessageType::Ping) => Ping::decode(buf.as_ref())?.into(),
             Some(MessageType::Pong) => Pong::decode(buf.as_ref())?.into(),
+            Some(MessageType::HelloRequest) => HelloRequest::decode(buf.as_ref())?.into(),
+            Some(MessageType::HelloReply) => HelloReply::decode(buf.as_ref())?.into(),
+            Some(MessageType::CancelCall) => CancelCall::decode(buf.as_ref())?.into(),
+            Some(MessageType::CallCredit) => CallCredit::decode(buf.as_ref())?.into(),
             None => return Err(ProtocolError::UnrecognizedMessageType(header.msg_type)),
         };
         Ok(Some(msg))
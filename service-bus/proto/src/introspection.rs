@@ -0,0 +1,98 @@
+//! Router introspection data.
+//!
+//! Router answers calls to [`BUS_ID`] by itself. Replies are `Result`s encoded with
+//! the payload codec of the request (JSON if untagged), the same way as typed
+//! `ya-service-bus` replies are.
+use serde::{Deserialize, Serialize};
+
+pub const BUS_ID: &str = "/local/bus";
+
+pub const GET_STATUS: &str = "GetStatus";
+pub const SET_TRACE: &str = "SetTrace";
+
+/// Upper bounds of call latency histogram buckets, in milliseconds.
+/// The last bucket collects everything above the last bound.
+pub const LATENCY_BUCKETS_MS: [u64; 8] = [1, 5, 10, 50, 100, 500, 1000, 5000];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusStatus {
+    pub endpoints: Vec<EndpointInfo>,
+    pub subscriptions: Vec<SubscriptionInfo>,
+    pub pending_calls: Vec<PendingCallInfo>,
+    pub call_stats: Vec<CallStats>,
+    pub trace: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointInfo {
+    pub service_id: String,
+    pub owner: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionInfo {
+    pub topic: String,
+    pub subscribers: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingCallInfo {
+    pub request_id: String,
+    pub caller: String,
+    pub address: String,
+    pub age_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallStats {
+    pub address: String,
+    pub calls: u64,
+    pub failures: u64,
+    pub total_ms: u64,
+    /// Call counts per [`LATENCY_BUCKETS_MS`] bucket, with one extra overflow bucket.
+    pub latency_histogram: Vec<u64>,
+}
+
+impl CallStats {
+    pub fn new(address: String) -> Self {
+        CallStats {
+            address,
+            calls: 0,
+            failures: 0,
+            total_ms: 0,
+            latency_histogram: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+        }
+    }
+
+    pub fn record(&mut self, latency_ms: u64, success: bool) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.calls += 1;
+        if !success {
+            self.failures += 1;
+        }
+        self.total_ms += latency_ms;
+        self.latency_histogram[bucket] += 1;
+    }
+
+    pub fn avg_ms(&self) -> Option<u64> {
+        match self.calls {
+            0 => None,
+            calls => Some(self.total_ms / calls),
+        }
+    }
+}
+
+/// Address prefixes of calls logged by the router.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceFilter {
+    pub prefixes: Vec<String>,
+}
@@ -11,6 +11,7 @@ mod gsb_api {
 
 #[cfg(feature = "with-codec")]
 pub mod codec;
+pub mod introspection;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
@@ -29,6 +30,10 @@ pub enum MessageType {
     BroadcastReply = 11,
     Ping = 12,
     Pong = 13,
+    HelloRequest = 14,
+    HelloReply = 15,
+    CancelCall = 16,
+    CallCredit = 17,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -71,6 +76,7 @@ impl TryFrom<i32> for CallReplyCode {
         Ok(match value {
             0 => CallReplyCode::CallReplyOk,
             400 => CallReplyCode::CallReplyBadRequest,
+            403 => CallReplyCode::CallReplyForbidden,
             500 => CallReplyCode::ServiceFailure,
             _ => return Err(EnumError(value)),
         })
@@ -91,6 +97,14 @@ impl TryFrom<i32> for CallReplyType {
 
 pub const GSB_URL_ENV_VAR: &str = "GSB_URL";
 pub const DEFAULT_GSB_URL: &str = "tcp://127.0.0.1:7464";
+pub const GSB_AUTH_TOKEN_ENV_VAR: &str = "GSB_AUTH_TOKEN";
+
+/// Token used to authenticate against the router, if any.
+pub fn gsb_auth_token() -> Option<String> {
+    std::env::var(GSB_AUTH_TOKEN_ENV_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+}
 
 pub fn gsb_addr(gsb_url: Option<Url>) -> SocketAddr {
     let gsb_url = gsb_url.unwrap_or_else(|| {
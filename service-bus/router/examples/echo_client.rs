@@ -17,6 +17,9 @@ async fn run_client() {
         address: "echo/test".to_string(),
         request_id: request_id.clone(),
         data: hello_msg.to_string().into_bytes(),
+        timeout: 0,
+        credit: 0,
+        codec: 0,
     };
     writer.send(call_request.into()).await.expect("Send failed");
 
@@ -1,6 +1,8 @@
 use std::env;
 use structopt::{clap, StructOpt};
-use ya_sb_proto::{DEFAULT_GSB_URL, GSB_URL_ENV_VAR};
+use ya_sb_proto::{gsb_addr, DEFAULT_GSB_URL, GSB_URL_ENV_VAR};
+use ya_sb_router::acl::AccessControl;
+use ya_sb_router::peer::{self, PeerConfig, GSB_PEERS_ENV_VAR, GSB_PEER_EXPORT_ENV_VAR};
 
 #[derive(StructOpt)]
 #[structopt(about = "Service Bus Router")]
@@ -9,6 +11,12 @@ use ya_sb_proto::{DEFAULT_GSB_URL, GSB_URL_ENV_VAR};
 struct Options {
     #[structopt(short = "l", env = GSB_URL_ENV_VAR, default_value = DEFAULT_GSB_URL)]
     gsb_url: url::Url,
+    /// Address of a router to peer with (may be repeated)
+    #[structopt(long = "peer", env = GSB_PEERS_ENV_VAR, use_delimiter = true)]
+    peers: Vec<String>,
+    /// Prefix of own services announced to peers (may be repeated)
+    #[structopt(long = "export", env = GSB_PEER_EXPORT_ENV_VAR, use_delimiter = true, default_value = peer::DEFAULT_EXPORT_PREFIX)]
+    export_prefixes: Vec<String>,
     #[structopt(long, default_value = "debug")]
     log_level: String,
 }
@@ -22,7 +30,16 @@ async fn main() -> anyhow::Result<()> {
     );
     env_logger::init();
 
-    ya_sb_router::bind_gsb_router(Some(options.gsb_url)).await?;
+    let peering = PeerConfig {
+        peers: peer::parse_peers(&options.peers.join(","))?,
+        export_prefixes: options.export_prefixes,
+    };
+    ya_sb_router::bind_tcp_router_with_config(
+        gsb_addr(Some(options.gsb_url)),
+        AccessControl::from_env()?,
+        peering,
+    )
+    .await?;
     tokio::signal::ctrl_c().await?;
     println!();
     log::info!("SIGINT received, exiting");
@@ -0,0 +1,208 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const GSB_ACL_FILE_ENV_VAR: &str = "GSB_ACL_FILE";
+
+const ANY: &str = "*";
+
+/// Service-id prefixes and topics a single client is allowed to use.
+///
+/// A prefix matches the address equal to it and every address below it,
+/// e.g. `/local/exeunit/1` matches `/local/exeunit/1/Exec` but not `/local/exeunit/10`.
+/// `*` matches everything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientAcl {
+    #[serde(default)]
+    pub register: Vec<String>,
+    #[serde(default)]
+    pub call: Vec<String>,
+    #[serde(default)]
+    pub subscribe: Vec<String>,
+    /// Whether the client may identify itself as a peer router.
+    #[serde(default)]
+    pub peer: bool,
+}
+
+impl ClientAcl {
+    pub fn allow_all() -> Self {
+        ClientAcl {
+            register: vec![ANY.to_string()],
+            call: vec![ANY.to_string()],
+            subscribe: vec![ANY.to_string()],
+            peer: false,
+        }
+    }
+
+    pub fn can_register(&self, service_id: &str) -> bool {
+        matches_any(&self.register, service_id)
+    }
+
+    pub fn can_call(&self, address: &str) -> bool {
+        matches_any(&self.call, address)
+    }
+
+    /// Topics a client may subscribe to are also the ones it may broadcast on.
+    pub fn can_subscribe(&self, topic: &str) -> bool {
+        matches_any(&self.subscribe, topic)
+    }
+}
+
+fn matches_any(patterns: &[String], addr: &str) -> bool {
+    patterns.iter().any(|pattern| matches(pattern, addr))
+}
+
+fn matches(pattern: &str, addr: &str) -> bool {
+    if pattern == ANY || pattern == addr {
+        return true;
+    }
+    addr.starts_with(pattern) && (pattern.ends_with('/') || addr[pattern.len()..].starts_with('/'))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientEntry {
+    name: String,
+    token: String,
+    #[serde(flatten)]
+    acl: ClientAcl,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AclFile {
+    #[serde(default)]
+    anonymous: Option<ClientAcl>,
+    #[serde(default)]
+    clients: Vec<ClientEntry>,
+}
+
+/// Decides which clients may connect to the router and what they may do.
+///
+/// Tokens are opaque strings, known only from the ACL file.
+#[derive(Clone, Debug)]
+pub struct AccessControl {
+    anonymous: Option<Arc<ClientAcl>>,
+    clients: HashMap<String, (String, Arc<ClientAcl>)>,
+}
+
+impl Default for AccessControl {
+    /// Every client is anonymous and allowed to do everything.
+    fn default() -> Self {
+        AccessControl {
+            anonymous: Some(Arc::new(ClientAcl::allow_all())),
+            clients: HashMap::new(),
+        }
+    }
+}
+
+impl AccessControl {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: AclFile = serde_json::from_str(json)?;
+        let mut clients = HashMap::new();
+        for entry in file.clients {
+            if entry.token.is_empty() {
+                anyhow::bail!("empty token for client {}", entry.name);
+            }
+            if clients
+                .insert(entry.token, (entry.name.clone(), Arc::new(entry.acl)))
+                .is_some()
+            {
+                anyhow::bail!("duplicate token for client {}", entry.name);
+            }
+        }
+        Ok(AccessControl {
+            anonymous: file.anonymous.map(Arc::new),
+            clients,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("unable to read ACL file {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
+
+    /// Reads ACL file pointed by `GSB_ACL_FILE`. Without it everything is allowed.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var(GSB_ACL_FILE_ENV_VAR) {
+            Ok(path) if !path.is_empty() => Self::from_file(path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn anonymous(&self) -> Option<Arc<ClientAcl>> {
+        self.anonymous.clone()
+    }
+
+    /// Returns client name and its ACL for a known token.
+    pub fn authenticate(&self, token: &str) -> Option<(String, Arc<ClientAcl>)> {
+        if token.is_empty() {
+            return self.anonymous().map(|acl| ("anonymous".to_string(), acl));
+        }
+        self.clients.get(token).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACL: &str = r#"{
+        "clients": [{
+            "name": "exe-unit",
+            "token": "secret",
+            "register": ["/local/exeunit/1"],
+            "call": ["/local/activity", "/public/activity/"]
+        }]
+    }"#;
+
+    #[test]
+    fn prefix_matches_on_path_boundary() {
+        assert!(matches("/local/exeunit/1", "/local/exeunit/1"));
+        assert!(matches("/local/exeunit/1", "/local/exeunit/1/Exec"));
+        assert!(!matches("/local/exeunit/1", "/local/exeunit/10"));
+        assert!(matches("/public/", "/public/activity"));
+        assert!(matches("*", "any-topic"));
+    }
+
+    #[test]
+    fn default_allows_anonymous() {
+        let acl = AccessControl::default();
+        let (_, client) = acl.authenticate("").unwrap();
+        assert!(client.can_register("/net/0x00"));
+        assert!(client.can_subscribe("market-offers"));
+    }
+
+    #[test]
+    fn token_scopes_client() {
+        let acl = AccessControl::from_json(ACL).unwrap();
+        assert!(acl.authenticate("").is_none());
+        assert!(acl.authenticate("invalid").is_none());
+
+        let (name, client) = acl.authenticate("secret").unwrap();
+        assert_eq!(name, "exe-unit");
+        assert!(client.can_register("/local/exeunit/1"));
+        assert!(!client.can_register("/local/exeunit/2"));
+        assert!(client.can_call("/local/activity/SetState"));
+        assert!(client.can_call("/public/activity/x"));
+        assert!(!client.can_call("/local/identity/Sign"));
+        assert!(!client.can_subscribe("market-offers"));
+        assert!(!client.peer);
+    }
+
+    #[test]
+    fn peering_needs_acl_flag() {
+        let (_, client) = AccessControl::default().authenticate("").unwrap();
+        assert!(!client.peer);
+
+        let acl = r#"{"clients": [{"name": "hub", "token": "peer-secret", "peer": true}]}"#;
+        let acl = AccessControl::from_json(acl).unwrap();
+        let (_, client) = acl.authenticate("peer-secret").unwrap();
+        assert!(client.peer);
+    }
+}
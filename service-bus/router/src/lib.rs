@@ -9,14 +9,22 @@ use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::net::{TcpListener, TcpStream};
 use tokio_util::codec::*;
 
 use ya_sb_proto::codec::{GsbMessage, GsbMessageCodec, ProtocolError};
+use ya_sb_proto::introspection::{self as bus, BusStatus, CallStats, TraceFilter};
 use ya_sb_proto::*;
-use ya_sb_util::PrefixLookupBag;
+use ya_sb_util::{topic, PrefixLookupBag};
 
+pub mod acl;
 mod dispatcher;
+mod payload;
+pub mod peer;
+
+use acl::{AccessControl, ClientAcl};
+use peer::PeerConfig;
 
 lazy_static! {
     pub static ref GSB_PING_TIMEOUT: u64 = env::var("GSB_PING_TIMEOUT")
@@ -25,6 +33,16 @@ lazy_static! {
         .unwrap();
 }
 
+/// How often pending calls are checked against their deadlines.
+const CALL_EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Comma separated address prefixes of calls to be logged by the router.
+pub const GSB_TRACE_ENV_VAR: &str = "GSB_TRACE";
+
+/// Comma separated payload codecs the router lets clients negotiate (`json`, `msgpack`, `cbor`).
+/// All of them by default.
+pub const GSB_CODECS_ENV_VAR: &str = "GSB_CODECS";
+
 type ServiceId = String;
 type RequestId = String;
 type TopicId = String;
@@ -35,10 +53,76 @@ fn is_valid_service_id(service_id: &ServiceId) -> bool {
         .all(|c| c.is_ascii_alphanumeric() || c == '/' || c == '_' || c == '-')
 }
 
-fn is_valid_topic_id(topic_id: &TopicId) -> bool {
-    topic_id
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+fn is_introspection_address(address: &str) -> bool {
+    address == bus::BUS_ID || address.starts_with(&format!("{}/", bus::BUS_ID))
+}
+
+fn is_traced(prefixes: &[String], address: &str) -> bool {
+    prefixes
+        .iter()
+        .any(|prefix| address.starts_with(prefix.as_str()))
+}
+
+/// Deadline of a call received now, on the router's own clock.
+fn deadline_of(timeout_ms: u64) -> Option<Instant> {
+    match timeout_ms {
+        0 => None,
+        timeout_ms => Some(Instant::now() + std::time::Duration::from_millis(timeout_ms)),
+    }
+}
+
+fn parse_codec(name: &str) -> Option<PayloadCodec> {
+    Some(match name.trim().to_lowercase().as_str() {
+        "json" => PayloadCodec::Json,
+        "msgpack" => PayloadCodec::Msgpack,
+        "cbor" => PayloadCodec::Cbor,
+        _ => return None,
+    })
+}
+
+fn codecs_from_env() -> Vec<PayloadCodec> {
+    match env::var(GSB_CODECS_ENV_VAR) {
+        Ok(names) => names
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .filter_map(|name| {
+                parse_codec(name).or_else(|| {
+                    log::warn!("Ignoring unknown payload codec: {}", name);
+                    None
+                })
+            })
+            .collect(),
+        Err(_) => vec![
+            PayloadCodec::Json,
+            PayloadCodec::Msgpack,
+            PayloadCodec::Cbor,
+        ],
+    }
+}
+
+/// Clients which did not negotiate a codec decode typed payloads with their
+/// build-time default, which is JSON unless built with the `msgpack` feature.
+fn accepts_codec<A: Hash + Eq>(
+    client_codecs: &HashMap<A, PayloadCodec>,
+    addr: &A,
+    codec: PayloadCodec,
+) -> bool {
+    match codec {
+        PayloadCodec::Raw | PayloadCodec::Json => true,
+        _ => client_codecs.contains_key(addr),
+    }
+}
+
+fn trace_prefixes_from_env() -> Vec<String> {
+    env::var(GSB_TRACE_ENV_VAR)
+        .map(|prefixes| {
+            prefixes
+                .split(',')
+                .map(|prefix| prefix.trim().to_string())
+                .filter(|prefix| !prefix.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 struct PendingCall<A>
@@ -46,7 +130,10 @@ where
     A: Hash + Eq,
 {
     caller_addr: A,
+    callee_addr: A,
     service_id: ServiceId,
+    started: Instant,
+    deadline: Option<Instant>,
 }
 
 struct RawRouter<A, M, E>
@@ -62,6 +149,21 @@ where
     topic_subscriptions: HashMap<TopicId, HashSet<A>>,
     reversed_subscriptions: HashMap<A, HashSet<TopicId>>,
     last_seen: HashMap<A, NaiveDateTime>,
+    access_control: AccessControl,
+    client_acls: HashMap<A, Arc<ClientAcl>>,
+    client_names: HashMap<A, String>,
+    client_codecs: HashMap<A, PayloadCodec>,
+    allowed_codecs: Vec<PayloadCodec>,
+    call_stats: HashMap<ServiceId, CallStats>,
+    trace_prefixes: Vec<String>,
+    router_id: String,
+    peering: PeerConfig,
+    // Links to peer routers, with their router IDs
+    peer_links: HashMap<A, String>,
+    // One link per peer router, used to announce own services and topics
+    export_links: HashSet<A>,
+    // Links opened by this router, which sent hello over them
+    dialed_links: HashSet<A>,
 }
 
 impl<A, M, E> RawRouter<A, M, E>
@@ -70,7 +172,7 @@ where
     M: Send + From<GsbMessage> + 'static,
     E: Send + Debug + 'static,
 {
-    pub fn new() -> Self {
+    pub fn new(access_control: AccessControl, peering: PeerConfig) -> Self {
         RawRouter {
             dispatcher: dispatcher::MessageDispatcher::new(),
             registered_endpoints: PrefixLookupBag::default(),
@@ -81,18 +183,38 @@ where
             topic_subscriptions: HashMap::new(),
             reversed_subscriptions: HashMap::new(),
             last_seen: HashMap::new(),
+            access_control,
+            client_acls: HashMap::new(),
+            client_names: HashMap::new(),
+            client_codecs: HashMap::new(),
+            allowed_codecs: codecs_from_env(),
+            call_stats: HashMap::new(),
+            trace_prefixes: trace_prefixes_from_env(),
+            router_id: uuid::Uuid::new_v4().to_hyphenated().to_string(),
+            peering,
+            peer_links: HashMap::new(),
+            export_links: HashSet::new(),
+            dialed_links: HashSet::new(),
         }
     }
 
     pub fn connect<B: Sink<M, Error = E> + Send + 'static>(&mut self, addr: A, sink: B) {
         log::debug!("Accepted connection from {}", addr);
         self.dispatcher.register(addr.clone(), sink).unwrap();
+        if let Some(acl) = self.access_control.anonymous() {
+            self.client_acls.insert(addr.clone(), acl);
+        }
         self.last_seen.insert(addr, Utc::now().naive_utc());
     }
 
     pub fn disconnect(&mut self, addr: &A) {
         log::debug!("Closing connection with {}", addr);
         self.last_seen.remove(addr);
+        self.client_acls.remove(addr);
+        self.client_names.remove(addr);
+        self.client_codecs.remove(addr);
+        self.dialed_links.remove(addr);
+        let is_peer = self.remove_peer(addr);
 
         // IDs of all endpoints registered by this server
         let service_ids = self
@@ -103,6 +225,11 @@ where
         for service_id in service_ids.iter() {
             log::debug!("unregistering service: {}", service_id);
             self.registered_endpoints.remove(service_id);
+            if !is_peer && self.peering.is_exported(service_id) {
+                self.send_to_peers(UnregisterRequest {
+                    service_id: service_id.clone(),
+                });
+            }
         }
 
         // IDs of all pending call requests unanswered by this server
@@ -118,6 +245,7 @@ where
 
         // Answer all pending calls with ServiceFailure reply
         for (request_id, pending_call) in pending_calls {
+            self.record_call(&pending_call, false);
             self.client_calls
                 .get_mut(&pending_call.caller_addr)
                 .unwrap()
@@ -135,13 +263,14 @@ where
             self.send_message_safe(&pending_call.caller_addr, msg);
         }
 
-        // Remove all pending calls coming from this client
+        // Remove all pending calls coming from this client and let the services know
         for request_id in self.client_calls.remove(addr).unwrap_or(HashSet::new()) {
             let pending_call = self.pending_calls.remove(&request_id).unwrap();
             self.endpoint_calls
                 .get_mut(&pending_call.service_id)
                 .unwrap()
                 .remove(&request_id);
+            self.send_message_safe(&pending_call.callee_addr, CancelCall { request_id });
         }
 
         // Unsubscribe from all topics
@@ -151,6 +280,9 @@ where
                     .get_mut(&topic_id)
                     .unwrap()
                     .remove(&addr);
+                if !is_peer && !self.has_local_subscribers(&topic_id) {
+                    self.send_to_peers(UnsubscribeRequest { topic: topic_id });
+                }
             }
         }
 
@@ -173,6 +305,237 @@ where
             .unwrap_or_else(|err| log::error!("Send message failed: {:?}", err));
     }
 
+    fn client_acl(&self, addr: &A) -> Option<&ClientAcl> {
+        self.client_acls.get(addr).map(|acl| acl.as_ref())
+    }
+
+    fn client_label(&self, addr: &A) -> String {
+        match self.client_names.get(addr) {
+            Some(name) => format!("{} ({})", addr, name),
+            None => addr.to_string(),
+        }
+    }
+
+    fn record_call(&mut self, pending_call: &PendingCall<A>, success: bool) {
+        let latency_ms = pending_call.started.elapsed().as_millis() as u64;
+        self.call_stats
+            .entry(pending_call.service_id.clone())
+            .or_insert_with(|| CallStats::new(pending_call.service_id.clone()))
+            .record(latency_ms, success);
+    }
+
+    /// Fails after refusing the client, so that its connection gets closed.
+    fn hello(&mut self, addr: &A, msg: HelloRequest) -> anyhow::Result<()> {
+        log::debug!(
+            "Received HelloRequest from {}. name = {}, version = {}",
+            addr,
+            &msg.name,
+            &msg.version
+        );
+        if !msg.name.is_empty() {
+            self.client_names.insert(addr.clone(), msg.name.clone());
+        }
+        let mut peer_id = None;
+        let mut authenticated = true;
+        let is_router = !msg.router_id.is_empty() && msg.router_id != self.router_id;
+        let reply = match self.access_control.authenticate(&msg.token) {
+            Some((client_name, acl)) if is_router && !acl.peer => {
+                log::warn!("{} ({}) is not allowed to peer", addr, client_name);
+                authenticated = false;
+                HelloReply {
+                    code: HelloReplyCode::HelloUnauthorized as i32,
+                    message: "Peering not allowed".to_string(),
+                    codec: PayloadCodec::Raw as i32,
+                    router_id: self.router_id.clone(),
+                }
+            }
+            Some((client_name, acl)) => {
+                log::debug!("{} authenticated as {}", addr, client_name);
+                self.client_acls.insert(addr.clone(), acl);
+                let codec = self.negotiate_codec(addr, &msg.codecs);
+                if is_router {
+                    peer_id = Some(msg.router_id);
+                }
+                HelloReply {
+                    code: HelloReplyCode::HelloOk as i32,
+                    message: format!("Authenticated as {}", client_name),
+                    codec: codec as i32,
+                    router_id: self.router_id.clone(),
+                }
+            }
+            None => {
+                log::warn!("{} ({}) failed to authenticate", addr, msg.name);
+                authenticated = false;
+                HelloReply {
+                    code: HelloReplyCode::HelloUnauthorized as i32,
+                    message: "Invalid token".to_string(),
+                    codec: PayloadCodec::Raw as i32,
+                    router_id: self.router_id.clone(),
+                }
+            }
+        };
+        self.send_message(addr, reply)?;
+        if !authenticated {
+            anyhow::bail!("Closing unauthenticated connection with {}", addr);
+        }
+        if let Some(peer_id) = peer_id {
+            self.add_peer(addr, peer_id);
+        }
+        Ok(())
+    }
+
+    /// Opens peering over a link established by this router.
+    pub fn dial_peer(&mut self, addr: &A) {
+        log::debug!("Sending hello to peer router {}", addr);
+        self.dialed_links.insert(addr.clone());
+        self.client_acls
+            .insert(addr.clone(), Arc::new(ClientAcl::allow_all()));
+        let msg = HelloRequest {
+            name: format!("router {}", self.router_id),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            token: gsb_auth_token().unwrap_or_default(),
+            codecs: self
+                .allowed_codecs
+                .iter()
+                .map(|codec| *codec as i32)
+                .collect(),
+            router_id: self.router_id.clone(),
+        };
+        self.send_message_safe(addr, msg);
+    }
+
+    /// Handles replies to requests this router sent to its peers.
+    fn peer_reply(&mut self, addr: &A, msg: GsbMessage) -> anyhow::Result<()> {
+        if !self.peer_links.contains_key(addr) && !self.dialed_links.contains(addr) {
+            anyhow::bail!("Unexpected message received: {:?}", msg);
+        }
+        match msg {
+            GsbMessage::HelloReply(reply) => {
+                if reply.code != HelloReplyCode::HelloOk as i32 {
+                    anyhow::bail!("Peer router {} refused hello: {}", addr, reply.message);
+                }
+                if reply.router_id.is_empty() || reply.router_id == self.router_id {
+                    anyhow::bail!("{} is not a peer router", addr);
+                }
+                if !self.client_codecs.contains_key(addr) {
+                    self.client_codecs.insert(addr.clone(), PayloadCodec::Json);
+                }
+                self.add_peer(addr, reply.router_id);
+            }
+            GsbMessage::RegisterReply(reply) if reply.code != 0 => {
+                log::warn!(
+                    "Peer router {} refused registration: {}",
+                    addr,
+                    reply.message
+                )
+            }
+            GsbMessage::SubscribeReply(reply) if reply.code != 0 => {
+                log::warn!(
+                    "Peer router {} refused subscription: {}",
+                    addr,
+                    reply.message
+                )
+            }
+            msg => log::trace!("Reply from peer router {}: {:?}", addr, msg),
+        }
+        Ok(())
+    }
+
+    /// Announces own services and topics over `addr`, unless the peer router
+    /// is already reachable over another link.
+    fn add_peer(&mut self, addr: &A, router_id: String) {
+        let redundant = self.peer_links.values().any(|id| id == &router_id);
+        log::info!("Peering with router {} over {}", router_id, addr);
+        self.client_names
+            .insert(addr.clone(), format!("router {}", router_id));
+        self.peer_links.insert(addr.clone(), router_id);
+        if redundant {
+            log::debug!("Router is already reachable over another link");
+            return;
+        }
+        self.export_links.insert(addr.clone());
+
+        let service_ids: Vec<ServiceId> = self
+            .reversed_endpoints
+            .iter()
+            .filter(|(owner, _)| !self.is_peer(owner))
+            .flat_map(|(_, service_ids)| service_ids.iter())
+            .filter(|service_id| self.peering.is_exported(service_id))
+            .cloned()
+            .collect();
+        for service_id in service_ids {
+            self.send_message_safe(addr, RegisterRequest { service_id });
+        }
+        let topics: Vec<TopicId> = self
+            .topic_subscriptions
+            .keys()
+            .filter(|topic| self.has_local_subscribers(topic))
+            .cloned()
+            .collect();
+        for topic in topics {
+            self.send_message_safe(addr, SubscribeRequest { topic });
+        }
+    }
+
+    /// Returns whether `addr` was a peer link. If it was the one used to announce
+    /// services to its router, another link to the same router takes over.
+    fn remove_peer(&mut self, addr: &A) -> bool {
+        let router_id = match self.peer_links.remove(addr) {
+            Some(router_id) => router_id,
+            None => return false,
+        };
+        log::info!("Lost link {} to peer router {}", addr, router_id);
+        if self.export_links.remove(addr) {
+            let other_link = self
+                .peer_links
+                .iter()
+                .find(|(_, id)| **id == router_id)
+                .map(|(link, _)| link.clone());
+            if let Some(link) = other_link {
+                self.peer_links.remove(&link);
+                self.add_peer(&link, router_id);
+            }
+        }
+        true
+    }
+
+    fn is_peer(&self, addr: &A) -> bool {
+        self.peer_links.contains_key(addr)
+    }
+
+    fn has_local_subscribers(&self, topic: &TopicId) -> bool {
+        self.topic_subscriptions
+            .get(topic)
+            .map_or(false, |subscribers| {
+                subscribers.iter().any(|addr| !self.is_peer(addr))
+            })
+    }
+
+    fn send_to_peers<T>(&mut self, msg: T)
+    where
+        T: Into<GsbMessage> + Clone,
+    {
+        let links: Vec<A> = self.export_links.iter().cloned().collect();
+        for link in links {
+            self.send_message_safe(&link, msg.clone());
+        }
+    }
+
+    /// Picks the first codec offered by the client which is allowed by the router.
+    /// `Raw` means the client keeps its build-time default.
+    fn negotiate_codec(&mut self, addr: &A, offered: &[i32]) -> PayloadCodec {
+        let codec = offered
+            .iter()
+            .filter_map(|codec| PayloadCodec::from_i32(*codec))
+            .find(|codec| self.allowed_codecs.contains(codec))
+            .unwrap_or(PayloadCodec::Raw);
+        log::debug!("{} negotiated payload codec {:?}", addr, codec);
+        if codec != PayloadCodec::Raw {
+            self.client_codecs.insert(addr.clone(), codec);
+        }
+        codec
+    }
+
     fn register_endpoint(&mut self, addr: &A, msg: RegisterRequest) -> anyhow::Result<()> {
         log::trace!("{} is registering endpoint {}", addr, &msg.service_id);
         let msg = if !is_valid_service_id(&msg.service_id) {
@@ -180,6 +543,19 @@ where
                 code: RegisterReplyCode::RegisterBadRequest as i32,
                 message: "Illegal service ID".to_string(),
             }
+        } else if is_introspection_address(&msg.service_id) {
+            RegisterReply {
+                code: RegisterReplyCode::RegisterConflict as i32,
+                message: format!("Service ID '{}' is reserved by the router", msg.service_id),
+            }
+        } else if !self
+            .client_acl(addr)
+            .map_or(false, |acl| acl.can_register(&msg.service_id))
+        {
+            RegisterReply {
+                code: RegisterReplyCode::RegisterForbidden as i32,
+                message: format!("Registering '{}' is not allowed", msg.service_id),
+            }
         } else {
             match self.registered_endpoints.entry(msg.service_id.clone()) {
                 Entry::Occupied(_) => RegisterReply {
@@ -192,6 +568,9 @@ where
                         .entry(addr.clone())
                         .or_insert_with(|| HashSet::new())
                         .insert(msg.service_id.clone());
+                    if !self.is_peer(addr) && self.peering.is_exported(&msg.service_id) {
+                        self.send_to_peers(msg.clone());
+                    }
                     RegisterReply {
                         code: RegisterReplyCode::RegisteredOk as i32,
                         message: format!("Service ID '{}' successfully registered", msg.service_id),
@@ -217,6 +596,9 @@ where
                     .ok_or(anyhow::anyhow!("Address not found: {}", addr))?
                     .remove(&msg.service_id);
                 log::debug!("Service successfully unregistered");
+                if !self.is_peer(addr) && self.peering.is_exported(&msg.service_id) {
+                    self.send_to_peers(msg.clone());
+                }
                 UnregisterReply {
                     code: UnregisterReplyCode::UnregisteredOk as i32,
                 }
@@ -239,6 +621,46 @@ where
             &msg.address,
             &msg.request_id
         );
+        if !self
+            .client_acl(caller_addr)
+            .map_or(false, |acl| acl.can_call(&msg.address))
+        {
+            log::debug!("{} is not allowed to call {}", caller_addr, &msg.address);
+            let msg = CallReply {
+                request_id: msg.request_id,
+                code: CallReplyCode::CallReplyForbidden as i32,
+                reply_type: CallReplyType::Full as i32,
+                data: format!("Calling '{}' is not allowed", msg.address).into_bytes(),
+            };
+            return self.send_message(caller_addr, msg);
+        }
+        if is_traced(&self.trace_prefixes, &msg.address) {
+            log::info!(
+                "[trace] call {} -> {} request_id = {} ({} bytes)",
+                self.client_label(caller_addr),
+                &msg.address,
+                &msg.request_id,
+                msg.data.len()
+            );
+        }
+        if is_introspection_address(&msg.address) {
+            return self.introspect(caller_addr, msg);
+        }
+        let codec = match PayloadCodec::from_i32(msg.codec) {
+            Some(codec) if codec == PayloadCodec::Raw || self.allowed_codecs.contains(&codec) => {
+                codec
+            }
+            _ => {
+                log::debug!("{} used disallowed codec {}", caller_addr, msg.codec);
+                let msg = CallReply {
+                    request_id: msg.request_id,
+                    code: CallReplyCode::CallReplyBadRequest as i32,
+                    reply_type: CallReplyType::Full as i32,
+                    data: format!("Payload codec {} is not allowed", msg.codec).into_bytes(),
+                };
+                return self.send_message(caller_addr, msg);
+            }
+        };
         let server_addr = match self.pending_calls.entry(msg.request_id.clone()) {
             Entry::Occupied(_) => Err("CallRequest with this ID already exists".to_string()),
             Entry::Vacant(call_entry) => match self.registered_endpoints.get(&msg.address) {
@@ -246,10 +668,17 @@ where
                     "No service registered under given address '{}'.",
                     &msg.address
                 )),
+                Some(addr) if !accepts_codec(&self.client_codecs, addr, codec) => Err(format!(
+                    "Service '{}' does not accept {:?} payloads.",
+                    &msg.address, codec
+                )),
                 Some(addr) => {
                     call_entry.insert(PendingCall {
                         caller_addr: caller_addr.clone(),
+                        callee_addr: addr.clone(),
                         service_id: msg.address.clone(),
+                        started: Instant::now(),
+                        deadline: deadline_of(msg.timeout),
                     });
                     self.endpoint_calls
                         .entry(msg.address.clone())
@@ -291,6 +720,17 @@ where
             Entry::Occupied(entry) => {
                 let pending_call = entry.get();
                 let caller_addr = pending_call.caller_addr.clone();
+                if is_traced(&self.trace_prefixes, &pending_call.service_id) {
+                    log::info!(
+                        "[trace] reply {} <- {} request_id = {} code = {} type = {} after {:?}",
+                        caller_addr,
+                        &pending_call.service_id,
+                        &msg.request_id,
+                        msg.code,
+                        msg.reply_type,
+                        pending_call.started.elapsed()
+                    );
+                }
                 if msg.reply_type == CallReplyType::Full as i32 {
                     self.endpoint_calls
                         .get_mut(&pending_call.service_id)
@@ -306,35 +746,240 @@ where
                             pending_call.caller_addr
                         ))?
                         .remove(&msg.request_id);
-                    entry.remove_entry();
+                    let (_, pending_call) = entry.remove_entry();
+                    self.record_call(&pending_call, msg.code == CallReplyCode::CallReplyOk as i32);
                 }
                 self.send_message(&caller_addr, msg)
             }
-            Entry::Vacant(_) => Ok(log::error!(
-                "Got reply for unknown request ID: {}",
+            Entry::Vacant(_) => Ok(log::debug!(
+                "Got reply for unknown or expired request ID: {}",
                 msg.request_id
             )),
         }
     }
 
+    /// Answers pending calls with `ServiceFailure` once their deadlines have passed.
+    /// Replies sent by the services afterwards are dropped.
+    pub fn expire_calls(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<RequestId> = self
+            .pending_calls
+            .iter()
+            .filter(|(_, pending_call)| pending_call.deadline.map_or(false, |d| d <= now))
+            .map(|(request_id, _)| request_id.clone())
+            .collect();
+        for request_id in expired {
+            self.expire_call(request_id);
+        }
+    }
+
+    fn expire_call(&mut self, request_id: RequestId) {
+        let pending_call = self.remove_pending_call(&request_id).unwrap();
+        log::debug!(
+            "CallRequest {} to {} expired",
+            request_id,
+            pending_call.service_id
+        );
+        self.record_call(&pending_call, false);
+        self.send_message_safe(
+            &pending_call.callee_addr,
+            CancelCall {
+                request_id: request_id.clone(),
+            },
+        );
+        let msg = CallReply {
+            request_id,
+            code: CallReplyCode::ServiceFailure as i32,
+            reply_type: CallReplyType::Full as i32,
+            data: format!(
+                "Service {} call deadline exceeded.",
+                pending_call.service_id
+            )
+            .into_bytes(),
+        };
+        self.send_message_safe(&pending_call.caller_addr, msg);
+    }
+
+    fn remove_pending_call(&mut self, request_id: &RequestId) -> Option<PendingCall<A>> {
+        let pending_call = self.pending_calls.remove(request_id)?;
+        if let Some(request_ids) = self.endpoint_calls.get_mut(&pending_call.service_id) {
+            request_ids.remove(request_id);
+        }
+        if let Some(request_ids) = self.client_calls.get_mut(&pending_call.caller_addr) {
+            request_ids.remove(request_id);
+        }
+        Some(pending_call)
+    }
+
+    fn cancel(&mut self, caller_addr: &A, msg: CancelCall) -> anyhow::Result<()> {
+        log::debug!(
+            "Received CancelCall from {} request_id = {}",
+            caller_addr,
+            &msg.request_id
+        );
+        match self.pending_calls.get(&msg.request_id) {
+            Some(pending_call) if &pending_call.caller_addr == caller_addr => (),
+            _ => {
+                log::debug!("Nothing to cancel for request ID: {}", msg.request_id);
+                return Ok(());
+            }
+        }
+        let pending_call = self.remove_pending_call(&msg.request_id).unwrap();
+        if is_traced(&self.trace_prefixes, &pending_call.service_id) {
+            log::info!(
+                "[trace] cancel {} -> {} request_id = {}",
+                self.client_label(caller_addr),
+                &pending_call.service_id,
+                &msg.request_id
+            );
+        }
+        self.send_message(&pending_call.callee_addr, msg)
+    }
+
+    fn credit(&mut self, caller_addr: &A, msg: CallCredit) -> anyhow::Result<()> {
+        log::trace!(
+            "Received CallCredit from {} request_id = {} credit = {}",
+            caller_addr,
+            &msg.request_id,
+            msg.credit
+        );
+        let callee_addr = match self.pending_calls.get(&msg.request_id) {
+            Some(pending_call) if &pending_call.caller_addr == caller_addr => {
+                pending_call.callee_addr.clone()
+            }
+            _ => {
+                log::debug!("Got credit for unknown request ID: {}", msg.request_id);
+                return Ok(());
+            }
+        };
+        self.send_message(&callee_addr, msg)
+    }
+
+    /// Answers calls to [`bus::BUS_ID`] services served by the router itself.
+    fn introspect(&mut self, caller_addr: &A, msg: CallRequest) -> anyhow::Result<()> {
+        let method = msg.address[bus::BUS_ID.len()..].trim_start_matches('/');
+        let codec = PayloadCodec::from_i32(msg.codec).unwrap_or(PayloadCodec::Raw);
+        let data = match method {
+            bus::GET_STATUS => Some(payload::to_vec(codec, &Ok::<_, String>(self.status()))?),
+            bus::SET_TRACE => Some(payload::to_vec(
+                codec,
+                &payload::from_slice::<TraceFilter>(codec, &msg.data)
+                    .map(|filter| {
+                        log::info!("Tracing calls to {:?}", filter.prefixes);
+                        self.trace_prefixes = filter.prefixes;
+                        self.trace_prefixes.clone()
+                    })
+                    .map_err(|e| format!("Invalid trace filter: {}", e)),
+            )?),
+            _ => None,
+        };
+        let msg = match data {
+            Some(data) => CallReply {
+                request_id: msg.request_id,
+                code: CallReplyCode::CallReplyOk as i32,
+                reply_type: CallReplyType::Full as i32,
+                data,
+            },
+            None => CallReply {
+                request_id: msg.request_id,
+                code: CallReplyCode::CallReplyBadRequest as i32,
+                reply_type: CallReplyType::Full as i32,
+                data: format!(
+                    "No service registered under given address '{}'.",
+                    msg.address
+                )
+                .into_bytes(),
+            },
+        };
+        self.send_message(caller_addr, msg)
+    }
+
+    fn status(&self) -> BusStatus {
+        let mut endpoints: Vec<_> = self
+            .reversed_endpoints
+            .iter()
+            .flat_map(|(addr, service_ids)| {
+                let owner = self.client_label(addr);
+                service_ids.iter().map(move |service_id| bus::EndpointInfo {
+                    service_id: service_id.clone(),
+                    owner: owner.clone(),
+                })
+            })
+            .collect();
+        endpoints.sort_by(|a, b| a.service_id.cmp(&b.service_id));
+
+        let mut subscriptions: Vec<_> = self
+            .topic_subscriptions
+            .iter()
+            .filter(|(_, subscribers)| !subscribers.is_empty())
+            .map(|(topic, subscribers)| {
+                let mut subscribers: Vec<_> = subscribers
+                    .iter()
+                    .map(|addr| self.client_label(addr))
+                    .collect();
+                subscribers.sort();
+                bus::SubscriptionInfo {
+                    topic: topic.clone(),
+                    subscribers,
+                }
+            })
+            .collect();
+        subscriptions.sort_by(|a, b| a.topic.cmp(&b.topic));
+
+        let mut pending_calls: Vec<_> = self
+            .pending_calls
+            .iter()
+            .map(|(request_id, pending_call)| bus::PendingCallInfo {
+                request_id: request_id.clone(),
+                caller: self.client_label(&pending_call.caller_addr),
+                address: pending_call.service_id.clone(),
+                age_ms: pending_call.started.elapsed().as_millis() as u64,
+            })
+            .collect();
+        pending_calls.sort_by(|a, b| b.age_ms.cmp(&a.age_ms));
+
+        let mut call_stats: Vec<_> = self.call_stats.values().cloned().collect();
+        call_stats.sort_by(|a, b| a.address.cmp(&b.address));
+
+        BusStatus {
+            endpoints,
+            subscriptions,
+            pending_calls,
+            call_stats,
+            trace: self.trace_prefixes.clone(),
+        }
+    }
+
     fn subscribe(&mut self, addr: &A, msg: SubscribeRequest) -> anyhow::Result<()> {
         log::debug!(
             "Received SubscribeRequest from {} topic = {}",
             addr,
             &msg.topic
         );
-        let msg = if !is_valid_topic_id(&msg.topic) {
+        let msg = if !topic::is_valid_filter(&msg.topic) {
             SubscribeReply {
                 code: SubscribeReplyCode::SubscribeBadRequest as i32,
                 message: format!("Invalid topic ID: {}", msg.topic),
             }
+        } else if !self
+            .client_acl(addr)
+            .map_or(false, |acl| acl.can_subscribe(&msg.topic))
+        {
+            SubscribeReply {
+                code: SubscribeReplyCode::SubscribeForbidden as i32,
+                message: format!("Subscribing to '{}' is not allowed", msg.topic),
+            }
         } else {
+            let announce = !self.is_peer(addr) && !self.has_local_subscribers(&msg.topic);
             if self
                 .topic_subscriptions
                 .entry(msg.topic.clone())
                 .or_insert_with(|| HashSet::new())
                 .insert(addr.clone())
             {
+                if announce {
+                    self.send_to_peers(msg.clone());
+                }
                 self.reversed_subscriptions
                     .entry(addr.clone())
                     .or_insert_with(|| HashSet::new())
@@ -371,6 +1016,9 @@ where
                 .ok_or(anyhow::anyhow!("Address not found: {}", addr))?
                 .remove(&msg.topic);
             log::debug!("Successfully unsubscribed");
+            if !self.is_peer(addr) && !self.has_local_subscribers(&msg.topic) {
+                self.send_to_peers(msg.clone());
+            }
             UnsubscribeReply {
                 code: UnsubscribeReplyCode::UnsubscribedOk as i32,
             }
@@ -390,23 +1038,47 @@ where
             &msg.topic,
             &msg.caller,
         );
-        let reply = if is_valid_topic_id(&msg.topic) {
+        let reply = if !topic::is_valid_topic(&msg.topic) {
             BroadcastReply {
-                code: BroadcastReplyCode::BroadcastOk as i32,
-                message: "OK".to_string(),
+                code: BroadcastReplyCode::BroadcastBadRequest as i32,
+                message: format!("Invalid topic ID: {}", msg.topic),
+            }
+        } else if !self
+            .client_acl(addr)
+            .map_or(false, |acl| acl.can_subscribe(&msg.topic))
+        {
+            BroadcastReply {
+                code: BroadcastReplyCode::BroadcastForbidden as i32,
+                message: format!("Broadcasting to '{}' is not allowed", msg.topic),
             }
         } else {
             BroadcastReply {
-                code: BroadcastReplyCode::BroadcastBadRequest as i32,
-                message: format!("Invalid topic ID: {}", msg.topic),
+                code: BroadcastReplyCode::BroadcastOk as i32,
+                message: "OK".to_string(),
             }
         };
-        self.send_message_safe(addr, reply);
+        let accepted = reply.code == BroadcastReplyCode::BroadcastOk as i32;
+        // Peers deliver broadcasts the same way as to clients, not expecting a reply
+        let from_peer = self.is_peer(addr);
+        if !from_peer {
+            self.send_message_safe(addr, reply);
+        } else if !accepted {
+            log::warn!("Dropping broadcast from peer {}: {}", addr, reply.message);
+        }
+        if !accepted {
+            return Ok(());
+        }
 
-        let subscribers = match self.topic_subscriptions.get(&msg.topic) {
-            Some(subscribers) => subscribers.iter().map(|a| a.clone()).collect(),
-            None => vec![],
-        };
+        // Broadcasts from peers are delivered to own clients only. A subscriber
+        // matched by several filters gets a single copy.
+        let subscribers: HashSet<A> = self
+            .topic_subscriptions
+            .iter()
+            .filter(|(filter, _)| topic::matches(filter, &msg.topic))
+            .flat_map(|(_, subscribers)| subscribers.iter())
+            .filter(|a| !from_peer || !self.is_peer(a))
+            .cloned()
+            .collect();
         subscribers.iter().for_each(|addr| {
             self.send_message_safe(addr, msg.clone());
         });
@@ -457,14 +1129,24 @@ where
     pub fn handle_message(&mut self, addr: A, msg: GsbMessage) -> anyhow::Result<()> {
         self.update_last_seen(&addr)?;
         match msg {
+            GsbMessage::HelloRequest(msg) => self.hello(&addr, msg),
             GsbMessage::RegisterRequest(msg) => self.register_endpoint(&addr, msg),
             GsbMessage::UnregisterRequest(msg) => self.unregister_endpoint(&addr, msg),
             GsbMessage::CallRequest(msg) => self.call(&addr, msg),
             GsbMessage::CallReply(msg) => self.reply(&addr, msg),
+            GsbMessage::CancelCall(msg) => self.cancel(&addr, msg),
+            GsbMessage::CallCredit(msg) => self.credit(&addr, msg),
             GsbMessage::SubscribeRequest(msg) => self.subscribe(&addr, msg),
             GsbMessage::UnsubscribeRequest(msg) => self.unsubscribe(&addr, msg),
             GsbMessage::BroadcastRequest(msg) => self.broadcast(&addr, msg),
+            GsbMessage::Ping => self.send_message(&addr, GsbMessage::Pong),
             GsbMessage::Pong => self.pong(&addr),
+            msg @ GsbMessage::HelloReply(_)
+            | msg @ GsbMessage::RegisterReply(_)
+            | msg @ GsbMessage::UnregisterReply(_)
+            | msg @ GsbMessage::SubscribeReply(_)
+            | msg @ GsbMessage::UnsubscribeReply(_)
+            | msg @ GsbMessage::BroadcastReply(_) => self.peer_reply(&addr, msg),
             _ => anyhow::bail!("Unexpected message received: {:?}", msg),
         }
     }
@@ -476,6 +1158,8 @@ where
 {
     router: Arc<Mutex<RawRouter<A, M, E>>>,
     ping_abort_handle: AbortHandle,
+    expiry_abort_handle: AbortHandle,
+    peer_abort_handles: Vec<AbortHandle>,
 }
 
 impl<A, M, E> Router<A, M, E>
@@ -485,7 +1169,16 @@ where
     E: Send + Sync + Debug + 'static,
 {
     pub fn new() -> Self {
-        let router = Arc::new(Mutex::new(RawRouter::new()));
+        Self::with_access_control(AccessControl::default())
+    }
+
+    pub fn with_access_control(access_control: AccessControl) -> Self {
+        Self::with_config(access_control, PeerConfig::default())
+    }
+
+    /// Peers listed in `peering` are not connected to, see [`Router::spawn_peer_link`].
+    pub fn with_config(access_control: AccessControl, peering: PeerConfig) -> Self {
+        let router = Arc::new(Mutex::new(RawRouter::new(access_control, peering)));
         let router1 = router.clone();
         let (ping_abort_handle, abort_registration) = AbortHandle::new_pair();
 
@@ -502,9 +1195,24 @@ where
             abort_registration,
         ));
 
+        // Single timer failing calls which missed their deadlines
+        let router2 = router.clone();
+        let (expiry_abort_handle, abort_registration) = AbortHandle::new_pair();
+        tokio::spawn(Abortable::new(
+            async move {
+                loop {
+                    tokio::time::delay_for(CALL_EXPIRY_INTERVAL).await;
+                    router2.lock().await.expire_calls();
+                }
+            },
+            abort_registration,
+        ));
+
         Router {
             router,
             ping_abort_handle,
+            expiry_abort_handle,
+            peer_abort_handles: Vec::new(),
         }
     }
 
@@ -517,16 +1225,7 @@ where
         let router = self.router.clone();
         tokio::spawn(async move {
             router.lock().await.connect(addr.clone(), writer);
-
-            reader
-                .err_into()
-                .try_for_each(|msg: GsbMessage| async {
-                    router.lock().await.handle_message(addr.clone(), msg)
-                })
-                .await
-                .unwrap_or_else(|e| handle_message_error(e));
-
-            router.lock().await.disconnect(&addr);
+            serve_connection(router, addr, reader).await;
         });
     }
 
@@ -551,6 +1250,59 @@ where
     }
 }
 
+impl Router<SocketAddr, GsbMessage, ProtocolError> {
+    /// Keeps a link to the peer router listening on `peer`, reconnecting with backoff.
+    pub fn spawn_peer_link(&mut self, peer: SocketAddr) {
+        let router = self.router.clone();
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        tokio::spawn(Abortable::new(
+            async move {
+                let mut delay = peer::MIN_RECONNECT_DELAY;
+                loop {
+                    match TcpStream::connect(&peer).await {
+                        Ok(sock) => {
+                            log::info!("Connected to peer router {}", peer);
+                            delay = peer::MIN_RECONNECT_DELAY;
+                            let (writer, reader) =
+                                Framed::new(sock, GsbMessageCodec::default()).split();
+                            {
+                                let mut router = router.lock().await;
+                                router.connect(peer, writer);
+                                router.dial_peer(&peer);
+                            }
+                            serve_connection(router.clone(), peer, reader).await;
+                        }
+                        Err(e) => log::debug!("Failed to connect to peer router {}: {}", peer, e),
+                    }
+                    tokio::time::delay_for(delay).await;
+                    delay = (delay * 2).min(peer::MAX_RECONNECT_DELAY);
+                }
+            },
+            abort_registration,
+        ));
+        self.peer_abort_handles.push(abort_handle);
+    }
+}
+
+async fn serve_connection<A, M, E, R>(router: Arc<Mutex<RawRouter<A, M, E>>>, addr: A, reader: R)
+where
+    A: Send + Sync + Hash + Eq + Display + Clone + 'static,
+    M: Send + From<GsbMessage> + 'static,
+    E: Send + Sync + Debug + 'static,
+    R: TryStream<Ok = GsbMessage>,
+    R::Error: Into<anyhow::Error>,
+{
+    reader
+        .err_into()
+        .try_for_each(|msg: GsbMessage| async {
+            router.lock().await.handle_message(addr.clone(), msg)
+        })
+        .await
+        .unwrap_or_else(|e| handle_message_error(e));
+
+    router.lock().await.disconnect(&addr);
+}
+
 fn handle_message_error(e: anyhow::Error) {
     match e.root_cause().downcast_ref::<std::io::Error>() {
         Some(err) => {
@@ -570,14 +1322,41 @@ where
 {
     fn drop(&mut self) {
         self.ping_abort_handle.abort();
+        self.expiry_abort_handle.abort();
+        for abort_handle in &self.peer_abort_handles {
+            abort_handle.abort();
+        }
     }
 }
 
 pub async fn bind_gsb_router(gsb_url: Option<url::Url>) -> Result<(), std::io::Error> {
-    bind_tcp_router(gsb_addr(gsb_url)).await
+    let access_control = AccessControl::from_env().map_err(|e| {
+        log::error!("Failed to load GSB access control list: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+    })?;
+    let peering = PeerConfig::from_env().map_err(|e| {
+        log::error!("Failed to load GSB peer routers: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+    })?;
+    bind_tcp_router_with_config(gsb_addr(gsb_url), access_control, peering).await
 }
 
 pub async fn bind_tcp_router(addr: SocketAddr) -> Result<(), std::io::Error> {
+    bind_tcp_router_with_acl(addr, AccessControl::default()).await
+}
+
+pub async fn bind_tcp_router_with_acl(
+    addr: SocketAddr,
+    access_control: AccessControl,
+) -> Result<(), std::io::Error> {
+    bind_tcp_router_with_config(addr, access_control, PeerConfig::default()).await
+}
+
+pub async fn bind_tcp_router_with_config(
+    addr: SocketAddr,
+    access_control: AccessControl,
+    peering: PeerConfig,
+) -> Result<(), std::io::Error> {
     let mut listener = TcpListener::bind(&addr)
         .map_err(|e| {
             log::error!("Failed to bind TCP listener at {}: {}", addr, e);
@@ -585,8 +1364,12 @@ pub async fn bind_tcp_router(addr: SocketAddr) -> Result<(), std::io::Error> {
         })
         .await?;
 
-    let router = Router::new();
+    let peers = peering.peers.clone();
+    let mut router = Router::with_config(access_control, peering);
     log::info!("Router listening on: {}", addr);
+    for peer in peers {
+        router.spawn_peer_link(peer);
+    }
 
     tokio::spawn(async move {
         let conn_stream = listener.incoming().map_ok(|sock| {
@@ -609,3 +1392,233 @@ pub async fn tcp_connect(
     let framed = tokio_util::codec::Framed::new(sock, GsbMessageCodec::default());
     framed.split()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+
+    type TestRouter = RawRouter<String, GsbMessage, mpsc::SendError>;
+
+    fn connect(router: &mut TestRouter, addr: &str) -> mpsc::Receiver<GsbMessage> {
+        let (tx, rx) = mpsc::channel(16);
+        router.connect(addr.to_string(), tx);
+        rx
+    }
+
+    fn call(address: &str, data: Vec<u8>) -> CallRequest {
+        CallRequest {
+            caller: "caller".to_string(),
+            address: address.to_string(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            data,
+            ..Default::default()
+        }
+    }
+
+    async fn next_reply(rx: &mut mpsc::Receiver<GsbMessage>) -> CallReply {
+        match rx.next().await {
+            Some(GsbMessage::CallReply(reply)) => reply,
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn unauthorized_hello_closes_connection() {
+        let acl = r#"{"clients": [{"name": "client", "token": "secret"}]}"#;
+        let acl = AccessControl::from_json(acl).unwrap();
+        let mut router = TestRouter::new(acl, PeerConfig::default());
+        let mut rx = connect(&mut router, "client");
+
+        let hello = HelloRequest {
+            name: "client".to_string(),
+            token: "invalid".to_string(),
+            ..Default::default()
+        };
+        assert!(router
+            .handle_message("client".to_string(), hello.into())
+            .is_err());
+        match rx.next().await {
+            Some(GsbMessage::HelloReply(reply)) => {
+                assert_eq!(reply.code, HelloReplyCode::HelloUnauthorized as i32)
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn introspection_reports_status_and_sets_trace() {
+        let mut router = TestRouter::new(AccessControl::default(), PeerConfig::default());
+        let mut service = connect(&mut router, "service");
+        let mut client = connect(&mut router, "client");
+        let register = RegisterRequest {
+            service_id: "/local/echo".to_string(),
+        };
+        router
+            .handle_message("service".to_string(), register.into())
+            .unwrap();
+        service.next().await.unwrap();
+
+        let filter = TraceFilter {
+            prefixes: vec!["/local/echo".to_string()],
+        };
+        let set_trace = call(
+            &format!("{}/{}", bus::BUS_ID, bus::SET_TRACE),
+            serde_json::to_vec(&filter).unwrap(),
+        );
+        router
+            .handle_message("client".to_string(), set_trace.into())
+            .unwrap();
+        let reply = next_reply(&mut client).await;
+        assert_eq!(reply.code, CallReplyCode::CallReplyOk as i32);
+        let trace: Result<Vec<String>, String> = serde_json::from_slice(&reply.data).unwrap();
+        assert_eq!(trace.unwrap(), filter.prefixes);
+
+        let get_status = call(&format!("{}/{}", bus::BUS_ID, bus::GET_STATUS), Vec::new());
+        router
+            .handle_message("client".to_string(), get_status.into())
+            .unwrap();
+        let reply = next_reply(&mut client).await;
+        assert_eq!(reply.code, CallReplyCode::CallReplyOk as i32);
+        let status: Result<BusStatus, String> = serde_json::from_slice(&reply.data).unwrap();
+        let status = status.unwrap();
+        assert_eq!(status.trace, filter.prefixes);
+        assert_eq!(status.endpoints.len(), 1);
+        assert_eq!(status.endpoints[0].service_id, "/local/echo");
+        assert_eq!(status.endpoints[0].owner, "service");
+
+        let unknown = call(&format!("{}/Unknown", bus::BUS_ID), Vec::new());
+        router
+            .handle_message("client".to_string(), unknown.into())
+            .unwrap();
+        let reply = next_reply(&mut client).await;
+        assert_eq!(reply.code, CallReplyCode::CallReplyBadRequest as i32);
+    }
+
+    #[tokio::test]
+    async fn calls_expire_after_timeout_on_router_clock() {
+        let mut router = TestRouter::new(AccessControl::default(), PeerConfig::default());
+        let mut service = connect(&mut router, "service");
+        let mut client = connect(&mut router, "client");
+        let register = RegisterRequest {
+            service_id: "/local/slow".to_string(),
+        };
+        router
+            .handle_message("service".to_string(), register.into())
+            .unwrap();
+        service.next().await.unwrap();
+
+        let mut slow = call("/local/slow", Vec::new());
+        slow.timeout = 20;
+        let request_id = slow.request_id.clone();
+        router
+            .handle_message("client".to_string(), slow.into())
+            .unwrap();
+        match service.next().await {
+            Some(GsbMessage::CallRequest(call)) => assert_eq!(call.request_id, request_id),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+
+        router.expire_calls();
+        assert!(router.pending_calls.contains_key(&request_id));
+        tokio::time::delay_for(std::time::Duration::from_millis(30)).await;
+        router.expire_calls();
+        assert!(router.pending_calls.is_empty());
+
+        let reply = next_reply(&mut client).await;
+        assert_eq!(reply.request_id, request_id);
+        assert_eq!(reply.code, CallReplyCode::ServiceFailure as i32);
+        match service.next().await {
+            Some(GsbMessage::CancelCall(cancel)) => assert_eq!(cancel.request_id, request_id),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    /// Hands the next message sent over a link to the router on its other end.
+    async fn pump(link: &mut mpsc::Receiver<GsbMessage>, router: &mut TestRouter, addr: &str) {
+        let msg = link.next().await.unwrap();
+        router.handle_message(addr.to_string(), msg).unwrap();
+    }
+
+    #[tokio::test]
+    async fn hello_from_router_needs_peer_acl() {
+        let mut router = TestRouter::new(AccessControl::default(), PeerConfig::default());
+        let mut rx = connect(&mut router, "client");
+
+        let hello = HelloRequest {
+            name: "client".to_string(),
+            router_id: "other-router".to_string(),
+            ..Default::default()
+        };
+        assert!(router
+            .handle_message("client".to_string(), hello.into())
+            .is_err());
+        match rx.next().await {
+            Some(GsbMessage::HelloReply(reply)) => {
+                assert_eq!(reply.code, HelloReplyCode::HelloUnauthorized as i32)
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+        assert!(!router.is_peer(&"client".to_string()));
+    }
+
+    #[tokio::test]
+    async fn calls_reach_service_of_peer_router() {
+        let acl = r#"{"anonymous": {"register": ["*"], "call": ["*"], "subscribe": ["*"], "peer": true}}"#;
+        let mut router1 = TestRouter::new(AccessControl::default(), PeerConfig::default());
+        let mut router2 = TestRouter::new(
+            AccessControl::from_json(acl).unwrap(),
+            PeerConfig::default(),
+        );
+        // router1 dials router2
+        let mut to_router2 = connect(&mut router1, "router2");
+        let mut to_router1 = connect(&mut router2, "router1");
+        router1.dial_peer(&"router2".to_string());
+        pump(&mut to_router2, &mut router2, "router1").await;
+        pump(&mut to_router1, &mut router1, "router2").await;
+        assert!(router1.is_peer(&"router2".to_string()));
+        assert!(router2.is_peer(&"router1".to_string()));
+
+        let mut service = connect(&mut router2, "service");
+        let register = RegisterRequest {
+            service_id: "/net/0x01/echo".to_string(),
+        };
+        router2
+            .handle_message("service".to_string(), register.into())
+            .unwrap();
+        service.next().await.unwrap();
+        // announcement and its reply
+        pump(&mut to_router1, &mut router1, "router2").await;
+        pump(&mut to_router2, &mut router2, "router1").await;
+
+        let mut client = connect(&mut router1, "client");
+        let request = call("/net/0x01/echo", b"ping".to_vec());
+        let request_id = request.request_id.clone();
+        router1
+            .handle_message("client".to_string(), request.into())
+            .unwrap();
+        pump(&mut to_router2, &mut router2, "router1").await;
+        match service.next().await {
+            Some(GsbMessage::CallRequest(call)) => {
+                assert_eq!(call.request_id, request_id);
+                assert_eq!(call.data, b"ping".to_vec());
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+
+        let reply = CallReply {
+            request_id: request_id.clone(),
+            code: CallReplyCode::CallReplyOk as i32,
+            reply_type: CallReplyType::Full as i32,
+            data: b"pong".to_vec(),
+        };
+        router2
+            .handle_message("service".to_string(), reply.into())
+            .unwrap();
+        pump(&mut to_router1, &mut router1, "router2").await;
+        let reply = next_reply(&mut client).await;
+        assert_eq!(reply.request_id, request_id);
+        assert_eq!(reply.code, CallReplyCode::CallReplyOk as i32);
+        assert_eq!(reply.data, b"pong".to_vec());
+    }
+}
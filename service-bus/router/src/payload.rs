@@ -0,0 +1,21 @@
+//! Encoding of typed payloads answered by the router itself.
+use serde::{de::DeserializeOwned, Serialize};
+
+use ya_sb_proto::PayloadCodec;
+
+/// Untagged payloads are expected to be JSON, the default of `ya-service-bus`.
+pub fn to_vec<T: Serialize>(codec: PayloadCodec, value: &T) -> anyhow::Result<Vec<u8>> {
+    Ok(match codec {
+        PayloadCodec::Raw | PayloadCodec::Json => serde_json::to_vec(value)?,
+        PayloadCodec::Msgpack => rmp_serde::to_vec_named(value)?,
+        PayloadCodec::Cbor => serde_cbor::to_vec(value)?,
+    })
+}
+
+pub fn from_slice<T: DeserializeOwned>(codec: PayloadCodec, data: &[u8]) -> anyhow::Result<T> {
+    Ok(match codec {
+        PayloadCodec::Raw | PayloadCodec::Json => serde_json::from_slice(data)?,
+        PayloadCodec::Msgpack => rmp_serde::from_read_ref(data)?,
+        PayloadCodec::Cbor => serde_cbor::from_slice(data)?,
+    })
+}
@@ -0,0 +1,102 @@
+//! Federation of routers.
+//!
+//! Routers connect to their peers as clients do, and announce over the link
+//! services registered by their own clients under exported prefixes, together
+//! with topics their own clients subscribe to. Peers then forward calls and
+//! broadcasts to the router owning the address or subscribed to the topic.
+//! A router accepts peering only from clients whose ACL entry allows `peer`.
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+/// Comma separated addresses (`host:port` or `tcp://host:port`) of routers to peer with.
+pub const GSB_PEERS_ENV_VAR: &str = "GSB_PEERS";
+/// Comma separated prefixes of services announced to peer routers.
+pub const GSB_PEER_EXPORT_ENV_VAR: &str = "GSB_PEER_EXPORT";
+pub const DEFAULT_EXPORT_PREFIX: &str = "/net/";
+
+pub(crate) const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+pub(crate) const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct PeerConfig {
+    /// Routers this router keeps links to. Links are reestablished when lost.
+    pub peers: Vec<SocketAddr>,
+    /// Prefixes of services registered by own clients which are announced to peers.
+    pub export_prefixes: Vec<String>,
+}
+
+impl Default for PeerConfig {
+    fn default() -> Self {
+        PeerConfig {
+            peers: Vec::new(),
+            export_prefixes: vec![DEFAULT_EXPORT_PREFIX.to_string()],
+        }
+    }
+}
+
+impl PeerConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = PeerConfig::default();
+        if let Ok(peers) = env::var(GSB_PEERS_ENV_VAR) {
+            config.peers = parse_peers(&peers)?;
+        }
+        if let Ok(prefixes) = env::var(GSB_PEER_EXPORT_ENV_VAR) {
+            config.export_prefixes = split_list(&prefixes).map(ToString::to_string).collect();
+        }
+        Ok(config)
+    }
+
+    pub fn is_exported(&self, service_id: &str) -> bool {
+        self.export_prefixes
+            .iter()
+            .any(|prefix| service_id.starts_with(prefix.as_str()))
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+}
+
+pub fn parse_peers(peers: &str) -> anyhow::Result<Vec<SocketAddr>> {
+    let mut addrs = Vec::new();
+    for peer in split_list(peers) {
+        let addr = peer
+            .trim_start_matches("tcp://")
+            .to_socket_addrs()
+            .map_err(|e| anyhow::anyhow!("invalid peer address {}: {}", peer, e))?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("unresolved peer address {}", peer))?;
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_peer_list() {
+        let peers = parse_peers("127.0.0.1:7465, tcp://127.0.0.1:7466,,127.0.0.1:7465").unwrap();
+        assert_eq!(
+            peers,
+            vec![
+                "127.0.0.1:7465".parse::<SocketAddr>().unwrap(),
+                "127.0.0.1:7466".parse().unwrap()
+            ]
+        );
+        assert!(parse_peers("127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn exports_net_by_default() {
+        let config = PeerConfig::default();
+        assert!(config.is_exported("/net/0x1234"));
+        assert!(!config.is_exported("/local/identity"));
+    }
+}
@@ -7,6 +7,8 @@ pub mod futures;
 #[cfg(feature = "with-futures")]
 pub use crate::futures::IntoFlatten as _;
 
+pub mod topic;
+
 use std::collections::{hash_map::Entry, HashMap};
 
 struct RevPrefixes<'a>(&'a str);
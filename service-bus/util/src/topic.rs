@@ -0,0 +1,88 @@
+//! Broadcast topic names and subscription filters.
+//!
+//! Topics are hierarchical: one or more `/` separated segments made of
+//! alphanumeric characters, `_` and `-`, e.g. `market/offers/subnet-1`.
+//! Broadcasts are always sent to a concrete topic, while subscriptions may use
+//! a filter where `*` stands for exactly one segment and a trailing `**` for
+//! one or more segments, e.g. `market/offers/*` or `market/**`.
+
+pub const ANY_SEGMENT: &str = "*";
+pub const ANY_SUFFIX: &str = "**";
+
+const SEPARATOR: char = '/';
+
+fn is_valid_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Concrete topic, valid as a broadcast destination.
+pub fn is_valid_topic(topic: &str) -> bool {
+    topic.split(SEPARATOR).all(is_valid_segment)
+}
+
+/// Topic or wildcard filter, valid as a subscription.
+pub fn is_valid_filter(filter: &str) -> bool {
+    let mut segments = filter.split(SEPARATOR).peekable();
+    while let Some(segment) = segments.next() {
+        let is_last = segments.peek().is_none();
+        if !(is_valid_segment(segment)
+            || segment == ANY_SEGMENT
+            || (segment == ANY_SUFFIX && is_last))
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether a subscription `filter` covers broadcasts sent to `topic`.
+pub fn matches(filter: &str, topic: &str) -> bool {
+    let mut topic_segments = topic.split(SEPARATOR);
+    for filter_segment in filter.split(SEPARATOR) {
+        if filter_segment == ANY_SUFFIX {
+            return topic_segments.next().is_some();
+        }
+        match topic_segments.next() {
+            Some(segment) if filter_segment == ANY_SEGMENT || filter_segment == segment => (),
+            _ => return false,
+        }
+    }
+    topic_segments.next().is_none()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(is_valid_topic("market-offers"));
+        assert!(is_valid_topic("market/offers/subnet_1"));
+        assert!(!is_valid_topic(""));
+        assert!(!is_valid_topic("/market"));
+        assert!(!is_valid_topic("market//offers"));
+        assert!(!is_valid_topic("market/*"));
+
+        assert!(is_valid_filter("market/offers/*"));
+        assert!(is_valid_filter("*/offers"));
+        assert!(is_valid_filter("market/**"));
+        assert!(!is_valid_filter("market/**/offers"));
+        assert!(!is_valid_filter("market/off*"));
+        assert!(!is_valid_filter("market/"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("market-offers", "market-offers"));
+        assert!(!matches("market", "market/offers"));
+        assert!(matches("market/offers/*", "market/offers/subnet-1"));
+        assert!(!matches("market/offers/*", "market/offers"));
+        assert!(!matches("market/offers/*", "market/offers/subnet-1/x"));
+        assert!(matches("*/offers", "market/offers"));
+        assert!(matches("market/**", "market/offers/subnet-1"));
+        assert!(!matches("market/**", "market"));
+    }
+}